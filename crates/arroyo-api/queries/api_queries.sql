@@ -284,24 +284,47 @@ LIMIT cast(:limit as integer);
 
 ----------- udfs -----------------------
 
---: DbUdf (description?, dylib_url?)
+--: DbUdf (description?, dylib_url?, cargo_lock?)
 
---! create_udf (dylib_url?)
-INSERT INTO udfs (pub_id, organization_id, created_by, prefix, name, language, definition, description, dylib_url)
-VALUES (:pub_id, :organization_id, :created_by, :prefix, :name,  :language, :definition, :description, :dylib_url);
+--! create_udf (dylib_url?, cargo_lock?)
+INSERT INTO udfs (pub_id, organization_id, created_by, prefix, name, language, definition, description, dylib_url, cargo_lock)
+VALUES (:pub_id, :organization_id, :created_by, :prefix, :name,  :language, :definition, :description, :dylib_url, :cargo_lock);
+
+--! update_udf (dylib_url?, cargo_lock?)
+UPDATE udfs
+SET
+   updated_at = :updated_at,
+   prefix = :prefix,
+   language = :language,
+   definition = :definition,
+   description = :description,
+   dylib_url = :dylib_url,
+   cargo_lock = :cargo_lock
+WHERE organization_id = :organization_id AND name = :name;
     
 --! get_udf: DbUdf
-SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url
+SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url, cargo_lock
 FROM udfs
 WHERE organization_id = :organization_id AND pub_id = :pub_id;
 
 --! get_udf_by_name: DbUdf
-SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url
+SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url, cargo_lock
 FROM udfs
 WHERE organization_id = :organization_id AND name = :name;
 
 --! get_udfs: DbUdf
-SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url
+SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url, cargo_lock
+FROM udfs
+WHERE organization_id = :organization_id
+    AND (created_at < (
+        SELECT created_at FROM udfs
+        WHERE pub_id = :starting_after
+    ) OR :starting_after = '')
+ORDER BY created_at DESC
+LIMIT CASE WHEN :limit > 0 THEN :limit ELSE NULL END;
+
+--! get_all_udfs: DbUdf
+SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url, cargo_lock
 FROM udfs
 WHERE organization_id = :organization_id;
 
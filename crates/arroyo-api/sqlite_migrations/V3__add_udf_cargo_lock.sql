@@ -0,0 +1 @@
+ALTER TABLE udfs ADD COLUMN cargo_lock TEXT;
@@ -2,6 +2,7 @@ use axum::extract::{Path, State};
 use axum::Json;
 use axum_extra::extract::WithRejection;
 use std::collections::BTreeMap;
+use std::time::Duration;
 
 use arroyo_connectors::connector_for_type;
 use arroyo_rpc::api_types::connections::{
@@ -22,6 +23,8 @@ use crate::rest_utils::{
 use crate::AuthData;
 use cornucopia_async::Database;
 
+const PROFILE_TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 impl TryFrom<DbConnectionProfile> for ConnectionProfile {
     type Error = String;
 
@@ -76,7 +79,15 @@ pub async fn test_connection_profile(
         )));
     };
 
-    let result = rx.await.map_err(log_and_map)?;
+    // connectors are expected to time out their own checks, but the request must not hang if
+    // one doesn't
+    let result = match tokio::time::timeout(PROFILE_TEST_TIMEOUT, rx).await {
+        Ok(result) => result.map_err(log_and_map)?,
+        Err(_) => TestSourceMessage::fail(format!(
+            "Timed out after {}s testing the connection profile",
+            PROFILE_TEST_TIMEOUT.as_secs()
+        )),
+    };
 
     Ok(Json(result))
 }
@@ -98,8 +109,22 @@ pub async fn create_connection_profile(
 ) -> Result<Json<ConnectionProfile>, ErrorResp> {
     let auth_data = authenticate(&state.database, bearer_auth).await?;
 
-    connector_for_type(&req.connector)
-        .ok_or_else(|| bad_request("Unknown connector type".to_string()))?
+    let connector = connector_for_type(&req.connector)
+        .ok_or_else(|| bad_request("Unknown connector type".to_string()))?;
+
+    if let Some(schema) = connector.metadata().connection_config {
+        let schema: serde_json::Value = serde_json::from_str(&schema).map_err(log_and_map)?;
+        let mut errors = vec![];
+        config_schema_errors(&schema, &req.config, "", &mut errors);
+        if !errors.is_empty() {
+            return Err(bad_request(format!(
+                "Invalid config: {}",
+                errors.join("; ")
+            )));
+        }
+    }
+
+    connector
         .validate_config(&req.config)
         .map_err(|e| bad_request(format!("Invalid config: {:?}", e)))?;
 
@@ -130,6 +155,131 @@ pub async fn create_connection_profile(
     Ok(Json(connection_profile))
 }
 
+/// Checks a connection profile config against the connector's declared JSON schema, collecting
+/// an error for each unknown, missing, or mistyped field. This covers the subset of JSON schema
+/// used by connector configs (types, properties, required, additionalProperties, and oneOf).
+fn config_schema_errors(
+    schema: &serde_json::Value,
+    value: &serde_json::Value,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    let field = |path: &str| {
+        if path.is_empty() {
+            "config".to_string()
+        } else {
+            format!("`{}`", path)
+        }
+    };
+
+    if let Some(variants) = schema.get("oneOf").and_then(|v| v.as_array()) {
+        let matches = variants.iter().any(|variant| {
+            let mut variant_errors = vec![];
+            config_schema_errors(variant, value, path, &mut variant_errors);
+            variant_errors.is_empty()
+        });
+
+        if !matches {
+            let titles: Vec<_> = variants
+                .iter()
+                .filter_map(|v| v.get("title").and_then(|t| t.as_str()))
+                .collect();
+            if titles.is_empty() {
+                errors.push(format!(
+                    "{} does not match any allowed variant",
+                    field(path)
+                ));
+            } else {
+                errors.push(format!(
+                    "{} does not match any of the allowed variants ({})",
+                    field(path),
+                    titles.join(", ")
+                ));
+            }
+        }
+        return;
+    }
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<_> = match expected {
+            serde_json::Value::String(t) => vec![t.as_str()],
+            serde_json::Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            _ => vec![],
+        };
+
+        let matches = |t: &str| match t {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            "null" => value.is_null(),
+            _ => true,
+        };
+
+        if !types.is_empty() && !types.iter().any(|t| matches(t)) {
+            errors.push(format!(
+                "{} must be of type {}",
+                field(path),
+                types.join(" or ")
+            ));
+            return;
+        }
+    }
+
+    if let Some(items) = schema.get("items") {
+        if let Some(values) = value.as_array() {
+            for (i, v) in values.iter().enumerate() {
+                config_schema_errors(items, v, &format!("{}[{}]", path, i), errors);
+            }
+        }
+    }
+
+    let Some(object) = value.as_object() else {
+        return;
+    };
+
+    let properties = schema.get("properties").and_then(|p| p.as_object());
+
+    if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+        for name in required.iter().filter_map(|r| r.as_str()) {
+            if object.get(name).map(|v| v.is_null()).unwrap_or(true) {
+                errors.push(format!(
+                    "missing required field {}",
+                    field(&join_path(path, name))
+                ));
+            }
+        }
+    }
+
+    for (name, v) in object {
+        let path = join_path(path, name);
+        match properties.and_then(|p| p.get(name)) {
+            Some(_) if v.is_null() => {}
+            Some(property) => config_schema_errors(property, v, &path, errors),
+            None => match schema.get("additionalProperties") {
+                Some(serde_json::Value::Bool(true)) => {}
+                Some(additional @ serde_json::Value::Object(_)) => {
+                    config_schema_errors(additional, v, &path, errors)
+                }
+                _ if properties.is_some() => {
+                    errors.push(format!("unknown field {}", field(&path)));
+                }
+                _ => {}
+            },
+        }
+    }
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
 /// List all connection profiles
 #[utoipa::path(
     get,
@@ -250,3 +400,62 @@ pub(crate) async fn get_all_connection_profiles<'a>(
 
     Ok(data)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::config_schema_errors;
+    use arroyo_connectors::connector_for_type;
+    use serde_json::json;
+
+    fn errors(config: serde_json::Value) -> Vec<String> {
+        let schema = connector_for_type("kafka")
+            .unwrap()
+            .metadata()
+            .connection_config
+            .unwrap();
+        let schema = serde_json::from_str(&schema).unwrap();
+
+        let mut errors = vec![];
+        config_schema_errors(&schema, &config, "", &mut errors);
+        errors
+    }
+
+    #[test]
+    fn test_config_schema_errors() {
+        assert!(errors(json!({
+            "bootstrapServers": "localhost:9092",
+            "authentication": {},
+        }))
+        .is_empty());
+
+        assert_eq!(
+            errors(json!({
+                "bootstrapServer": "localhost:9092",
+                "authentication": {},
+            })),
+            vec![
+                "missing required field `bootstrapServers`",
+                "unknown field `bootstrapServer`"
+            ]
+        );
+
+        assert_eq!(
+            errors(json!({
+                "bootstrapServers": 9092,
+                "authentication": {},
+            })),
+            vec!["`bootstrapServers` must be of type string"]
+        );
+
+        let errs = errors(json!({
+            "bootstrapServers": "localhost:9092",
+            "authentication": {"protocol": "SASL_SSL"},
+        }));
+        assert_eq!(errs.len(), 1);
+        assert!(
+            errs[0].starts_with("`authentication` does not match any of the allowed variants"),
+            "{:?}",
+            errs
+        );
+    }
+}
@@ -466,6 +466,7 @@ pub(crate) async fn expand_schema(
         Format::Parquet(_) => Ok(schema),
         Format::RawString(_) => Ok(schema),
         Format::RawBytes(_) => Ok(schema),
+        Format::ArrowIpc(_) => Ok(schema),
         Format::Protobuf(_) => {
             expand_proto_schema(
                 connector,
@@ -713,21 +714,20 @@ async fn expand_json_schema(
     }
 
     if let Some(d) = &schema.definition {
-        let arrow = match d {
-            SchemaDefinition::JsonSchema(json) => json::schema::to_arrow(name, json)
+        schema.fields = match d {
+            SchemaDefinition::JsonSchema(json) => json::schema::to_source_fields(name, json)
                 .map_err(|e| bad_request(format!("Invalid json-schema: {}", e)))?,
-            SchemaDefinition::RawSchema(_) => raw_schema(),
+            SchemaDefinition::RawSchema(_) => {
+                let fields: Result<_, String> = raw_schema()
+                    .fields
+                    .into_iter()
+                    .map(|f| (**f).clone().try_into())
+                    .collect();
+
+                fields.map_err(|e| bad_request(format!("Failed to convert schema: {}", e)))?
+            }
             _ => return Err(bad_request("Invalid schema type for json format")),
         };
-
-        let fields: Result<_, String> = arrow
-            .fields
-            .into_iter()
-            .map(|f| (**f).clone().try_into())
-            .collect();
-
-        schema.fields =
-            fields.map_err(|e| bad_request(format!("Failed to convert schema: {}", e)))?;
     }
 
     Ok(schema)
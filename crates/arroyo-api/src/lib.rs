@@ -37,8 +37,11 @@ use crate::pipelines::{
     __path_validate_query,
 };
 use crate::rest::__path_ping;
-use crate::rest_utils::{service_unavailable, ErrorResp};
-use crate::udfs::{__path_create_udf, __path_delete_udf, __path_get_udfs, __path_validate_udf};
+use crate::rest_utils::{service_unavailable, ErrorCode, ErrorResp};
+use crate::udfs::{
+    __path_create_udf, __path_delete_udf, __path_get_udfs, __path_validate_udf,
+    __path_validate_udf_stream,
+};
 use arroyo_rpc::api_types::{checkpoints::*, connections::*, metrics::*, pipelines::*, udfs::*, *};
 use arroyo_rpc::config::config;
 use arroyo_rpc::formats::*;
@@ -115,9 +118,9 @@ pub(crate) fn to_micros(dt: OffsetDateTime) -> u64 {
     (dt.unix_timestamp_nanos() / 1_000) as u64
 }
 
-pub async fn compiler_service() -> Result<CompilerGrpcClient<Channel>, ErrorResp> {
+pub async fn compiler_service(addr: &str) -> Result<CompilerGrpcClient<Channel>, ErrorResp> {
     // TODO: cache this
-    CompilerGrpcClient::connect(config().compiler_endpoint().to_string())
+    CompilerGrpcClient::connect(addr.to_string())
         .await
         .map_err(|e| {
             error!("Failed to connect to compiler service: {}", e);
@@ -131,7 +134,12 @@ pub async fn start_server(database: DatabaseSource, guard: ShutdownGuard) -> any
     let listener = TcpListener::bind(addr).await?;
     let local_addr = listener.local_addr()?;
 
-    let app = rest::create_rest_app(database, &config.controller_endpoint()).layer(
+    let app = rest::create_rest_app(
+        database,
+        &config.controller_endpoint(),
+        &config.compiler_endpoint(),
+    )
+    .layer(
         CompressionLayer::new().zstd(true).compress_when(
             DefaultPredicate::new()
                 // compression doesn't work for server-sent events
@@ -218,6 +226,7 @@ impl IntoResponse for HttpError {
         ping,
         validate_query,
         validate_udf,
+        validate_udf_stream,
         create_pipeline,
         create_preview_pipeline,
         patch_pipeline,
@@ -249,6 +258,7 @@ impl IntoResponse for HttpError {
     ),
     components(schemas(
         ErrorResp,
+        ErrorCode,
         PipelinePost,
         PreviewPost,
         PipelinePatch,
@@ -297,6 +307,7 @@ impl IntoResponse for HttpError {
         ParquetFormat,
         RawStringFormat,
         RawBytesFormat,
+        ArrowIpcFormat,
         TimestampFormat,
         Framing,
         FramingMethod,
@@ -43,7 +43,7 @@ use tracing::warn;
 
 use crate::jobs::get_action;
 use crate::queries::api_queries;
-use crate::queries::api_queries::{fetch_get_udfs, DbPipeline, DbPipelineJob};
+use crate::queries::api_queries::{fetch_get_all_udfs, DbPipeline, DbPipelineJob};
 use crate::rest::AppState;
 use crate::rest_utils::{
     authenticate, bad_request, log_and_map, not_found, paginate_results, required_field,
@@ -55,7 +55,7 @@ use crate::AuthData;
 use crate::{connection_tables, to_micros};
 use arroyo_rpc::config::config;
 use arroyo_types::to_millis;
-use cornucopia_async::{Database, DatabaseSource};
+use cornucopia_async::Database;
 use petgraph::prelude::EdgeRef;
 
 async fn compile_sql(
@@ -64,11 +64,12 @@ async fn compile_sql(
     parallelism: usize,
     auth_data: &AuthData,
     validate_only: bool,
-    db: &DatabaseSource,
+    state: &AppState,
 ) -> Result<CompiledSql, ErrorResp> {
+    let db = &state.database;
     let mut schema_provider = ArroyoSchemaProvider::new();
 
-    let global_udfs = fetch_get_udfs(&db.client().await?, &auth_data.organization_id)
+    let global_udfs = fetch_get_all_udfs(&db.client().await?, &auth_data.organization_id)
         .await?
         .into_iter()
         .map(|u| u.into())
@@ -95,7 +96,8 @@ async fn compile_sql(
     }
 
     if !local_udfs.is_empty() {
-        let mut compiler_service: CompilerGrpcClient<_> = compiler_service().await?;
+        let mut compiler_service: CompilerGrpcClient<_> =
+            compiler_service(&state.compiler_addr).await?;
 
         for udf in local_udfs {
             match udf.language {
@@ -114,7 +116,9 @@ async fn compile_sql(
                             &mut compiler_service,
                             &udf.definition,
                             UdfLanguage::Rust,
+                            None,
                             true,
+                            *config().compiler.build_timeout,
                         )
                         .await?;
 
@@ -300,8 +304,10 @@ pub(crate) async fn create_pipeline_int(
     is_preview: bool,
     enable_sinks: bool,
     auth: AuthData,
-    db: &DatabaseSource,
+    state: &AppState,
 ) -> Result<String, ErrorResp> {
+    let db = &state.database;
+
     if parallelism > auth.org_metadata.max_parallelism as u64 {
         return Err(bad_request(format!(
             "Your plan allows you to run pipelines up to parallelism {};
@@ -312,8 +318,15 @@ pub(crate) async fn create_pipeline_int(
 
     let pub_id = generate_id(IdTypes::Pipeline);
 
-    let mut compiled =
-        compile_sql(query.clone(), &udfs, parallelism as usize, &auth, false, db).await?;
+    let mut compiled = compile_sql(
+        query.clone(),
+        &udfs,
+        parallelism as usize,
+        &auth,
+        false,
+        state,
+    )
+    .await?;
 
     if compiled.program.graph.node_count() > auth.org_metadata.max_operators as usize {
         return Err(bad_request(
@@ -352,6 +365,7 @@ pub(crate) async fn create_pipeline_int(
                             operator_config: default_sink().encode_to_vec(),
                         }),
                         parallelism: 1,
+                        metadata: Default::default(),
                     });
 
                     let edges: Vec<_> = g
@@ -384,6 +398,7 @@ pub(crate) async fn create_pipeline_int(
             that the schema_registry is configured correctly and running.\nDetails: {}",
                 error_chain(e)
             ),
+            code: None,
         })?;
 
     let proto_program: ArrowProgram = compiled.program.clone().into();
@@ -546,7 +561,7 @@ pub async fn validate_query(
         1,
         &auth_data,
         true,
-        &state.database,
+        &state,
     )
     .await
     {
@@ -598,7 +613,7 @@ pub async fn create_pipeline(
         false,
         true,
         auth_data.clone(),
-        &state.database,
+        &state,
     )
     .await?;
 
@@ -637,7 +652,7 @@ pub async fn create_preview_pipeline(
         true,
         req.enable_sinks,
         auth_data.clone(),
-        &state.database,
+        &state,
     )
     .await?;
 
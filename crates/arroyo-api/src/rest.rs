@@ -29,7 +29,7 @@ use crate::pipelines::{
     get_pipelines, patch_pipeline, restart_pipeline, validate_query,
 };
 use crate::rest_utils::not_found;
-use crate::udfs::{create_udf, delete_udf, get_udfs, validate_udf};
+use crate::udfs::{create_udf, delete_udf, get_udfs, validate_udf, validate_udf_stream};
 use crate::ApiDoc;
 use arroyo_rpc::config::config;
 use cornucopia_async::DatabaseSource;
@@ -41,6 +41,7 @@ struct Assets;
 #[derive(Clone)]
 pub struct AppState {
     pub(crate) controller_addr: String,
+    pub(crate) compiler_addr: String,
     pub(crate) database: DatabaseSource,
 }
 
@@ -117,7 +118,11 @@ async fn index_html() -> Response {
     }
 }
 
-pub fn create_rest_app(database: DatabaseSource, controller_addr: &str) -> Router {
+pub fn create_rest_app(
+    database: DatabaseSource,
+    controller_addr: &str,
+    compiler_addr: &str,
+) -> Router {
     // TODO: enable in development only!!!
     let cors = CorsLayer::new()
         .allow_methods(cors::Any)
@@ -160,6 +165,7 @@ pub fn create_rest_app(database: DatabaseSource, controller_addr: &str) -> Route
         .route("/udfs", post(create_udf))
         .route("/udfs", get(get_udfs))
         .route("/udfs/validate", post(validate_udf))
+        .route("/udfs/validate/stream", post(validate_udf_stream))
         .route("/udfs/:id", delete(delete_udf))
         .route("/pipelines", post(create_pipeline))
         .route("/pipelines/preview", post(create_preview_pipeline))
@@ -182,6 +188,7 @@ pub fn create_rest_app(database: DatabaseSource, controller_addr: &str) -> Route
         .fallback(static_handler)
         .with_state(AppState {
             controller_addr: controller_addr.to_string(),
+            compiler_addr: compiler_addr.to_string(),
             database,
         })
         .layer(cors)
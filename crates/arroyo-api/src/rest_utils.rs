@@ -18,12 +18,31 @@ pub type BearerAuth = Option<TypedHeader<Authorization<Bearer>>>;
 
 const DEFAULT_ITEMS_PER_PAGE: u32 = 10;
 
+/// Machine-readable codes that let clients distinguish errors without parsing messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ToSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    NotFound,
+    UdfNameConflict,
+    UdfCompileError,
+    UdfBuildTimeout,
+}
+
 #[derive(Debug, ToSchema, Serialize, Deserialize)]
 pub struct ErrorResp {
     #[serde(skip)]
     pub(crate) status_code: StatusCode,
     #[serde(rename = "error")]
     pub(crate) message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) code: Option<ErrorCode>,
+}
+
+impl ErrorResp {
+    pub(crate) fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -63,6 +82,7 @@ impl From<DbError> for ErrorResp {
                 ErrorResp {
                     status_code: StatusCode::INTERNAL_SERVER_ERROR,
                     message: e,
+                    code: None,
                 }
             }
         }
@@ -80,6 +100,7 @@ impl IntoResponse for ApiError {
         ErrorResp {
             status_code: status,
             message,
+            code: None,
         }
         .into_response()
     }
@@ -94,6 +115,7 @@ where
     ErrorResp {
         status_code: StatusCode::INTERNAL_SERVER_ERROR,
         message: "Something went wrong".to_string(),
+        code: None,
     }
 }
 
@@ -116,13 +138,15 @@ pub(crate) fn bad_request(message: impl Into<String>) -> ErrorResp {
     ErrorResp {
         status_code: StatusCode::BAD_REQUEST,
         message: message.into(),
+        code: None,
     }
 }
 
 pub(crate) fn service_unavailable(object: &str) -> ErrorResp {
     ErrorResp {
         status_code: StatusCode::SERVICE_UNAVAILABLE,
-        message: format!("{} not available", object),
+        message: format!("{} not available; try again shortly", object),
+        code: None,
     }
 }
 
@@ -130,6 +154,7 @@ pub(crate) fn internal_server_error(message: impl Into<String>) -> ErrorResp {
     ErrorResp {
         status_code: StatusCode::INTERNAL_SERVER_ERROR,
         message: message.into(),
+        code: None,
     }
 }
 
@@ -137,6 +162,7 @@ pub(crate) fn not_found(object: &str) -> ErrorResp {
     ErrorResp {
         status_code: StatusCode::NOT_FOUND,
         message: format!("{} not found", object),
+        code: Some(ErrorCode::NotFound),
     }
 }
 
@@ -154,6 +180,7 @@ pub fn validate_pagination_params(
             return Err(ErrorResp {
                 status_code: StatusCode::BAD_REQUEST,
                 message: "Limit must be greater than 0".to_string(),
+                code: None,
             });
         }
     }
@@ -2,27 +2,42 @@ use crate::queries::api_queries;
 use crate::queries::api_queries::DbUdf;
 use crate::rest::AppState;
 use crate::rest_utils::{
-    authenticate, bad_request, internal_server_error, map_insert_err, not_found, ApiError,
-    BearerAuth, ErrorResp,
+    authenticate, bad_request, internal_server_error, map_insert_err, not_found, paginate_results,
+    service_unavailable, validate_pagination_params, ApiError, BearerAuth, ErrorCode, ErrorResp,
 };
 use crate::{compiler_service, to_micros};
+use anyhow::bail;
+use arroyo_planner::is_builtin_function;
 use arroyo_rpc::api_types::udfs::{
-    GlobalUdf, UdfLanguage, UdfPost, UdfValidationResult, ValidateUdfPost,
+    GlobalUdf, UdfLanguage, UdfPost, UdfValidationResult, ValidateUdfPost, ValidateUdfQueryParams,
 };
-use arroyo_rpc::api_types::GlobalUdfCollection;
+use arroyo_rpc::api_types::{GlobalUdfCollection, PaginationQueryParams};
 use arroyo_rpc::config::config;
 use arroyo_rpc::grpc::rpc::compiler_grpc_client::CompilerGrpcClient;
-use arroyo_rpc::grpc::rpc::{BuildUdfReq, UdfCrate};
+use arroyo_rpc::grpc::rpc::{build_udf_event, BuildUdfEvent, BuildUdfReq, UdfCrate};
 use arroyo_rpc::public_ids::{generate_id, IdTypes};
 use arroyo_udf_host::ParsedUdfFile;
 use arroyo_udf_python::PythonUDF;
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::sse::Event;
+use axum::response::Sse;
 use axum::Json;
 use axum_extra::extract::WithRejection;
+use cornucopia_async::DbError;
+use futures_util::stream::Stream;
+use std::convert::Infallible;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::mpsc::channel;
+use tokio::time::Instant;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
 use tonic::transport::Channel;
-use tracing::error;
+use tonic::{Code, Request, Status};
+use tracing::{debug, error};
 
 const PLUGIN_VERSION: &str = "^0.2.0";
 
@@ -42,26 +57,32 @@ impl From<DbUdf> for GlobalUdf {
             updated_at: to_micros(val.updated_at),
             description: val.description,
             dylib_url: val.dylib_url,
+            cargo_lock: val.cargo_lock,
             language: UdfLanguage::from_str(&val.language).unwrap_or_default(),
         }
     }
 }
 
 /// Create a global UDF
+///
+/// If `upsert` is set and a UDF with the same name already exists, it is updated in place. The
+/// response is 201 for a newly-created UDF and 200 for an update.
 #[utoipa::path(
     post,
     path = "/v1/udfs",
     tag = "udfs",
     request_body = UdfPost,
     responses(
-        (status = 200, description = "Created UDF", body = Udf),
+        (status = 200, description = "Updated UDF, when `upsert` was set", body = Udf),
+        (status = 201, description = "Created UDF", body = Udf),
+        (status = 400, description = "Invalid UDF or name conflict", body = ErrorResp),
     ),
 )]
 pub async fn create_udf(
     State(state): State<AppState>,
     bearer_auth: BearerAuth,
     WithRejection(Json(req), _): WithRejection<Json<UdfPost>, ApiError>,
-) -> Result<Json<GlobalUdf>, ErrorResp> {
+) -> Result<(StatusCode, Json<GlobalUdf>), ErrorResp> {
     let auth_data = authenticate(&state.database, bearer_auth).await.unwrap();
 
     // let transaction = client.transaction().await.map_err(log_and_map)?;
@@ -72,24 +93,62 @@ pub async fn create_udf(
 
     // build udf
     let build_udf_resp = build_udf(
-        &mut compiler_service().await?,
+        &mut compiler_service(&state.compiler_addr).await?,
         &req.definition,
         req.language,
+        req.cargo_lock.as_deref(),
         true,
+        *config().compiler.build_timeout,
     )
     .await?;
 
     if !build_udf_resp.errors.is_empty() {
-        return Err(bad_request("UDF is invalid"));
+        return Err(bad_request(format!(
+            "UDF is invalid:\n{}",
+            build_udf_resp.errors.join("\n")
+        ))
+        .with_code(ErrorCode::UdfCompileError));
     }
 
     let client = state.database.client().await?;
 
     let udf_name = build_udf_resp.name.expect("udf name not set for valid UDF");
+    let description = req.description.unwrap_or_default();
+
+    let update = || async {
+        api_queries::execute_update_udf(
+            &client,
+            &OffsetDateTime::now_utc(),
+            &req.prefix,
+            &req.language.to_string(),
+            &req.definition,
+            &description,
+            &build_udf_resp.url,
+            &build_udf_resp.cargo_lock,
+            &auth_data.organization_id,
+            &udf_name,
+        )
+        .await?;
+
+        api_queries::fetch_get_udf_by_name(&client, &auth_data.organization_id, &udf_name)
+            .await?
+            .into_iter()
+            .next()
+            .map(|udf| (StatusCode::OK, Json(GlobalUdf::from(udf))))
+            .ok_or_else(|| internal_server_error("Failed to fetch updated UDF"))
+    };
+
+    if req.upsert
+        && !api_queries::fetch_get_udf_by_name(&client, &auth_data.organization_id, &udf_name)
+            .await?
+            .is_empty()
+    {
+        return update().await;
+    }
 
     // check for duplicates
     let pub_id = generate_id(IdTypes::Udf);
-    api_queries::execute_create_udf(
+    let created = api_queries::execute_create_udf(
         &client,
         &pub_id,
         &auth_data.organization_id,
@@ -98,11 +157,23 @@ pub async fn create_udf(
         &udf_name,
         &req.language.to_string(),
         &req.definition,
-        &req.description.unwrap_or_default(),
+        &description,
         &build_udf_resp.url,
+        &build_udf_resp.cargo_lock,
     )
-    .await
-    .map_err(|e| map_insert_err("udf", e))?;
+    .await;
+
+    match created {
+        Ok(_) => {}
+        // another request created the UDF after our lookup; apply ours on top of it
+        Err(DbError::DuplicateViolation) if req.upsert => {
+            return update().await;
+        }
+        Err(e @ DbError::DuplicateViolation) => {
+            return Err(map_insert_err("udf", e).with_code(ErrorCode::UdfNameConflict));
+        }
+        Err(e) => return Err(e.into()),
+    }
 
     let created_udf = api_queries::fetch_get_udf(&client, &auth_data.organization_id, &pub_id)
         .await?
@@ -113,14 +184,19 @@ pub async fn create_udf(
 
     // transaction.commit().await.map_err(log_and_map)?;
 
-    Ok(Json(created_udf))
+    Ok((StatusCode::CREATED, Json(created_udf)))
 }
 
 /// Get Global UDFs
+///
+/// UDFs are returned newest first. If no `limit` is given, all UDFs are returned.
 #[utoipa::path(
     get,
     path = "/v1/udfs",
     tag = "udfs",
+    params(
+        PaginationQueryParams
+    ),
     responses(
         (status = 200, description = "List of UDFs", body = GlobalUdfCollection),
     ),
@@ -128,15 +204,32 @@ pub async fn create_udf(
 pub async fn get_udfs(
     State(state): State<AppState>,
     bearer_auth: BearerAuth,
+    query_params: Query<PaginationQueryParams>,
 ) -> Result<Json<GlobalUdfCollection>, ErrorResp> {
-    let auth_data = authenticate(&state.database, bearer_auth).await.unwrap();
+    let auth_data = authenticate(&state.database, bearer_auth).await?;
+
+    let (starting_after, limit) =
+        validate_pagination_params(query_params.starting_after.clone(), query_params.limit)?;
 
-    let udfs =
-        api_queries::fetch_get_udfs(&state.database.client().await?, &auth_data.organization_id)
-            .await?;
+    // without a limit all UDFs are returned, as they were before this endpoint was paginated
+    let limit = query_params.limit.map(|_| limit);
+
+    let udfs = api_queries::fetch_get_udfs(
+        &state.database.client().await?,
+        &auth_data.organization_id,
+        &starting_after.unwrap_or("".to_string()),
+        &(limit.unwrap_or(0) as i32), // is 1 more than the requested limit, or 0 for no limit
+    )
+    .await?;
+
+    let (udfs, has_more) = match limit {
+        Some(limit) => paginate_results(udfs, limit),
+        None => (udfs, false),
+    };
 
     Ok(Json(GlobalUdfCollection {
         data: udfs.into_iter().map(|u| u.into()).collect(),
+        has_more,
     }))
 }
 
@@ -177,6 +270,7 @@ pub struct UdfResp {
     pub errors: Vec<String>,
     pub name: Option<String>,
     pub url: Option<String>,
+    pub cargo_lock: Option<String>,
 }
 
 impl From<anyhow::Error> for UdfResp {
@@ -185,105 +279,654 @@ impl From<anyhow::Error> for UdfResp {
             errors: vec![value.to_string()],
             name: None,
             url: None,
+            cargo_lock: None,
         }
     }
 }
 
+/// UDFs share a namespace with the built-in SQL functions, so a UDF with the same name as a
+/// built-in would silently change what existing queries call
+fn check_builtin_collision(name: &str) -> anyhow::Result<()> {
+    if is_builtin_function(name) {
+        bail!(
+            "UDF name '{}' conflicts with a built-in function; choose a different name",
+            name
+        );
+    }
+    Ok(())
+}
+
+fn rust_udf_crate(udf_definition: &str, cargo_lock: Option<&str>) -> anyhow::Result<UdfCrate> {
+    // use the arroyo-udf lib to do some validation and to get the function name
+    let file = ParsedUdfFile::try_parse(udf_definition)?;
+    check_builtin_collision(&file.udf.name)?;
+
+    let mut dependencies = file.dependencies;
+    let plugin_dep = if config().compiler.use_local_udf_crate {
+        toml::Value::Table(
+            [(
+                "path".to_string(),
+                toml::Value::String(LOCAL_UDF_LIB_CRATE.to_string()),
+            )]
+            .into_iter()
+            .collect(),
+        )
+    } else {
+        toml::Value::String(PLUGIN_VERSION.to_string())
+    };
+
+    dependencies.insert("arroyo-udf-plugin".to_string(), plugin_dep);
+
+    Ok(UdfCrate {
+        name: file.udf.name,
+        definition: udf_definition.to_string(),
+        dependencies: dependencies.to_string(),
+        cargo_lock: cargo_lock.map(|s| s.to_string()),
+    })
+}
+
 pub async fn build_udf(
     compiler_service: &mut CompilerGrpcClient<Channel>,
     udf_definition: &str,
     language: UdfLanguage,
+    cargo_lock: Option<&str>,
     save: bool,
+    timeout: Duration,
 ) -> Result<UdfResp, ErrorResp> {
     match language {
         UdfLanguage::Python => match PythonUDF::parse(udf_definition).await {
-            Ok(udf) => Ok(UdfResp {
-                errors: vec![],
-                name: Some(Arc::unwrap_or_clone(udf.name)),
-                url: None,
-            }),
+            Ok(udf) => match check_builtin_collision(&udf.name) {
+                Ok(()) => Ok(UdfResp {
+                    errors: vec![],
+                    name: Some(Arc::unwrap_or_clone(udf.name)),
+                    url: None,
+                    cargo_lock: None,
+                }),
+                Err(e) => Ok(e.into()),
+            },
             Err(e) => Ok(UdfResp {
                 errors: vec![e.to_string()],
                 name: None,
                 url: None,
+                cargo_lock: None,
             }),
         },
         UdfLanguage::Rust => {
-            // use the arroyo-udf lib to do some validation and to get the function name
-            let file = match ParsedUdfFile::try_parse(udf_definition) {
-                Ok(p) => p,
+            let udf_crate = match rust_udf_crate(udf_definition, cargo_lock) {
+                Ok(c) => c,
                 Err(e) => return Ok(e.into()),
             };
+            let name = udf_crate.name.clone();
 
-            let mut dependencies = file.dependencies;
-            let plugin_dep = if config().compiler.use_local_udf_crate {
-                toml::Value::Table(
-                    [(
-                        "path".to_string(),
-                        toml::Value::String(LOCAL_UDF_LIB_CRATE.to_string()),
-                    )]
-                    .into_iter()
-                    .collect(),
-                )
-            } else {
-                toml::Value::String(PLUGIN_VERSION.to_string())
-            };
+            // the timeout is sent to the compiler as the gRPC deadline, which cancels the
+            // build there; we also enforce it locally in case the compiler is unresponsive
+            let mut request = Request::new(BuildUdfReq {
+                udf_crate: Some(udf_crate),
+                save,
+            });
+            request.set_timeout(timeout);
 
-            dependencies.insert("arroyo-udf-plugin".to_string(), plugin_dep);
-
-            let check_udfs_resp = match compiler_service
-                .build_udf(BuildUdfReq {
-                    udf_crate: Some(UdfCrate {
-                        name: file.udf.name.clone(),
-                        definition: udf_definition.to_string(),
-                        dependencies: dependencies.to_string(),
-                    }),
-                    save,
-                })
-                .await
-            {
-                Ok(resp) => resp.into_inner(),
-                Err(e) => {
-                    error!("compiler service failed to validate UDF: {}", e.message());
-                    return Err(internal_server_error(format!(
-                        "Failed to validate UDF: {}",
-                        e.message()
-                    )));
-                }
+            let Ok(result) =
+                tokio::time::timeout(timeout, compiler_service.build_udf(request)).await
+            else {
+                error!("timed out after {:?} waiting for UDF build", timeout);
+                return Err(build_timed_out(timeout));
             };
 
+            let check_udfs_resp = result.map_err(|e| build_error(e, timeout))?.into_inner();
+
             Ok(UdfResp {
                 errors: check_udfs_resp.errors,
-                name: Some(file.udf.name),
+                name: Some(name),
                 url: check_udfs_resp.udf_path,
+                cargo_lock: check_udfs_resp.cargo_lock,
             })
         }
     }
 }
 
+/// Maps a failed call to the compiler service to the error returned to the client
+fn build_error(e: Status, timeout: Duration) -> ErrorResp {
+    match e.code() {
+        Code::DeadlineExceeded | Code::Cancelled => {
+            error!("UDF build timed out: {}", e.message());
+            build_timed_out(timeout)
+        }
+        Code::Unavailable => {
+            error!("compiler service is unavailable: {}", e.message());
+            service_unavailable("compiler-service")
+        }
+        _ => {
+            error!("compiler service failed to validate UDF: {}", e.message());
+            internal_server_error(format!("Failed to validate UDF: {}", e.message()))
+        }
+    }
+}
+
+fn build_timed_out(timeout: Duration) -> ErrorResp {
+    ErrorResp {
+        status_code: StatusCode::SERVICE_UNAVAILABLE,
+        message: format!("UDF build did not finish within {:?}", timeout),
+        code: Some(ErrorCode::UdfBuildTimeout),
+    }
+}
+
 /// Validate UDFs
 #[utoipa::path(
     post,
     path = "/v1/udfs/validate",
     tag = "udfs",
     request_body = ValidateUdfPost,
+    params(ValidateUdfQueryParams),
     responses(
         (status = 200, description = "Validated query", body = UdfValidationResult),
     ),
 )]
 pub async fn validate_udf(
+    State(state): State<AppState>,
+    query_params: Query<ValidateUdfQueryParams>,
     WithRejection(Json(req), _): WithRejection<Json<ValidateUdfPost>, ApiError>,
 ) -> Result<Json<UdfValidationResult>, ErrorResp> {
+    // the generated crate is shown as written; environment variables in dependencies are not
+    // substituted
+    let generated = match req.language {
+        UdfLanguage::Rust if query_params.include_generated.unwrap_or(false) => {
+            rust_udf_crate(&req.definition, req.cargo_lock.as_deref())
+                .and_then(|c| Ok((c.cargo_toml(false)?, c.lib_rs().to_string())))
+                .ok()
+        }
+        _ => None,
+    };
+
     let check_udfs_resp = build_udf(
-        &mut compiler_service().await?,
+        &mut compiler_service(&state.compiler_addr).await?,
         &req.definition,
         req.language,
+        req.cargo_lock.as_deref(),
         false,
+        *config().compiler.build_timeout,
     )
     .await?;
 
+    let (cargo_toml, lib_rs) = generated.unzip();
+
     Ok(Json(UdfValidationResult {
         udf_name: check_udfs_resp.name,
         errors: check_udfs_resp.errors,
+        cargo_lock: check_udfs_resp.cargo_lock,
+        cargo_toml,
+        lib_rs,
     }))
 }
+
+fn result_event(result: UdfValidationResult) -> Result<Event, Infallible> {
+    Ok(Event::default().event("result").json_data(result).unwrap())
+}
+
+/// Validate UDFs, streaming the build output
+#[utoipa::path(
+    post,
+    path = "/v1/udfs/validate/stream",
+    tag = "udfs",
+    request_body = ValidateUdfPost,
+    responses(
+        (status = 200, description = "Build output as 'text/event-stream'; each line of output is a 'log' event, followed by a final 'result' event containing the UdfValidationResult"),
+    ),
+)]
+pub async fn validate_udf_stream(
+    State(state): State<AppState>,
+    WithRejection(Json(req), _): WithRejection<Json<ValidateUdfPost>, ApiError>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ErrorResp> {
+    let (tx, rx) = channel(32);
+
+    // python UDFs aren't compiled, so there's nothing to stream
+    let udf_crate = match req.language {
+        UdfLanguage::Python => None,
+        UdfLanguage::Rust => match rust_udf_crate(&req.definition, req.cargo_lock.as_deref()) {
+            Ok(c) => Some(c),
+            Err(e) => {
+                let resp: UdfResp = e.into();
+                tx.send(result_event(UdfValidationResult {
+                    udf_name: resp.name,
+                    errors: resp.errors,
+                    cargo_lock: None,
+                    cargo_toml: None,
+                    lib_rs: None,
+                }))
+                .await
+                .unwrap();
+                return Ok(Sse::new(ReceiverStream::new(rx)));
+            }
+        },
+    };
+
+    let Some(udf_crate) = udf_crate else {
+        let resp = build_udf(
+            &mut compiler_service(&state.compiler_addr).await?,
+            &req.definition,
+            req.language,
+            None,
+            false,
+            *config().compiler.build_timeout,
+        )
+        .await?;
+
+        tx.send(result_event(UdfValidationResult {
+            udf_name: resp.name,
+            errors: resp.errors,
+            cargo_lock: None,
+            cargo_toml: None,
+            lib_rs: None,
+        }))
+        .await
+        .unwrap();
+        return Ok(Sse::new(ReceiverStream::new(rx)));
+    };
+
+    let rx = stream_udf_build(
+        &mut compiler_service(&state.compiler_addr).await?,
+        udf_crate,
+        *config().compiler.build_timeout,
+    )
+    .await?;
+
+    Ok(Sse::new(rx))
+}
+
+/// Starts building the crate, returning a stream of its build output followed by the result. The
+/// build is cancelled if it doesn't finish within `timeout` or if the stream is dropped.
+async fn stream_udf_build(
+    compiler_service: &mut CompilerGrpcClient<Channel>,
+    udf_crate: UdfCrate,
+    timeout: Duration,
+) -> Result<ReceiverStream<Result<Event, Infallible>>, ErrorResp> {
+    let (tx, rx) = channel(32);
+    let name = udf_crate.name.clone();
+
+    // as in build_udf, the timeout is sent as the gRPC deadline and also enforced locally
+    let deadline = Instant::now() + timeout;
+    let mut request = Request::new(BuildUdfReq {
+        udf_crate: Some(udf_crate),
+        save: false,
+    });
+    request.set_timeout(timeout);
+
+    let Ok(result) =
+        tokio::time::timeout_at(deadline, compiler_service.build_udf_stream(request)).await
+    else {
+        error!("timed out after {:?} waiting for UDF build", timeout);
+        return Err(build_timed_out(timeout));
+    };
+
+    let mut stream = result.map_err(|e| build_error(e, timeout))?.into_inner();
+
+    let failed = |name: &str, message: String| {
+        result_event(UdfValidationResult {
+            udf_name: Some(name.to_string()),
+            errors: vec![message],
+            cargo_lock: None,
+            cargo_toml: None,
+            lib_rs: None,
+        })
+    };
+
+    // dropping `stream` when this task exits cancels the call, which stops the build
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                event = stream.next() => event,
+                _ = tx.closed() => {
+                    debug!("UDF build stream closed by client; cancelling build");
+                    break;
+                }
+                _ = tokio::time::sleep_until(deadline) => {
+                    error!("timed out after {:?} waiting for UDF build", timeout);
+                    let _ = tx.send(failed(&name, build_timed_out(timeout).message)).await;
+                    break;
+                }
+            };
+
+            let event = match event {
+                None => break,
+                Some(Ok(BuildUdfEvent {
+                    event: Some(build_udf_event::Event::Log(line)),
+                })) => Ok(Event::default().event("log").data(line)),
+                Some(Ok(BuildUdfEvent {
+                    event: Some(build_udf_event::Event::Result(resp)),
+                })) => result_event(UdfValidationResult {
+                    udf_name: Some(name.clone()),
+                    errors: resp.errors,
+                    cargo_lock: resp.cargo_lock,
+                    cargo_toml: None,
+                    lib_rs: None,
+                }),
+                Some(Ok(BuildUdfEvent { event: None })) => continue,
+                Some(Err(e)) => failed(&name, build_error(e, timeout).message),
+            };
+
+            if tx.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(ReceiverStream::new(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_udf, rust_udf_crate, stream_udf_build, validate_udf};
+    use crate::rest::AppState;
+    use crate::rest_utils::ErrorCode;
+    use arroyo_rpc::api_types::udfs::{UdfLanguage, ValidateUdfPost, ValidateUdfQueryParams};
+    use arroyo_rpc::grpc::rpc::compiler_grpc_client::CompilerGrpcClient;
+    use arroyo_rpc::grpc::rpc::compiler_grpc_server::{CompilerGrpc, CompilerGrpcServer};
+    use arroyo_rpc::grpc::rpc::{
+        build_udf_event, BuildUdfEvent, BuildUdfReq, BuildUdfResp, GetUdfPathReq, GetUdfPathResp,
+        UdfCrate,
+    };
+    use axum::extract::{Query, State};
+    use axum::http::StatusCode;
+    use axum::Json;
+    use axum_extra::extract::WithRejection;
+    use cornucopia_async::DatabaseSource;
+    use std::marker::PhantomData;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tokio::net::TcpListener;
+    use tokio::sync::{mpsc, oneshot};
+    use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+    use tokio_stream::StreamExt;
+    use tonic::transport::{Channel, Server};
+    use tonic::{Request, Response, Status};
+
+    /// A compiler that accepts builds but never finishes them
+    struct HangingCompiler;
+
+    #[tonic::async_trait]
+    impl CompilerGrpc for HangingCompiler {
+        async fn build_udf(
+            &self,
+            _: Request<BuildUdfReq>,
+        ) -> Result<Response<BuildUdfResp>, Status> {
+            std::future::pending().await
+        }
+
+        type BuildUdfStreamStream = ReceiverStream<Result<BuildUdfEvent, Status>>;
+
+        async fn build_udf_stream(
+            &self,
+            _: Request<BuildUdfReq>,
+        ) -> Result<Response<Self::BuildUdfStreamStream>, Status> {
+            std::future::pending().await
+        }
+
+        async fn get_udf_path(
+            &self,
+            _: Request<GetUdfPathReq>,
+        ) -> Result<Response<GetUdfPathResp>, Status> {
+            Err(Status::unimplemented("not supported by the test compiler"))
+        }
+    }
+
+    /// A compiler that reports every build as successful
+    struct SucceedingCompiler;
+
+    #[tonic::async_trait]
+    impl CompilerGrpc for SucceedingCompiler {
+        async fn build_udf(
+            &self,
+            request: Request<BuildUdfReq>,
+        ) -> Result<Response<BuildUdfResp>, Status> {
+            let udf_crate = request.into_inner().udf_crate.unwrap();
+            Ok(Response::new(BuildUdfResp {
+                errors: vec![],
+                udf_path: None,
+                cargo_lock: Some(format!("# lockfile for {}", udf_crate.name)),
+            }))
+        }
+
+        type BuildUdfStreamStream = ReceiverStream<Result<BuildUdfEvent, Status>>;
+
+        async fn build_udf_stream(
+            &self,
+            _: Request<BuildUdfReq>,
+        ) -> Result<Response<Self::BuildUdfStreamStream>, Status> {
+            Err(Status::unimplemented("not supported by the test compiler"))
+        }
+
+        async fn get_udf_path(
+            &self,
+            _: Request<GetUdfPathReq>,
+        ) -> Result<Response<GetUdfPathResp>, Status> {
+            Err(Status::unimplemented("not supported by the test compiler"))
+        }
+    }
+
+    /// A compiler that streams a line of build output but never finishes the build; it signals
+    /// `cancelled` once the client goes away
+    struct StalledStreamCompiler {
+        cancelled: Mutex<Option<oneshot::Sender<()>>>,
+    }
+
+    #[tonic::async_trait]
+    impl CompilerGrpc for StalledStreamCompiler {
+        async fn build_udf(
+            &self,
+            _: Request<BuildUdfReq>,
+        ) -> Result<Response<BuildUdfResp>, Status> {
+            Err(Status::unimplemented("not supported by the test compiler"))
+        }
+
+        type BuildUdfStreamStream = ReceiverStream<Result<BuildUdfEvent, Status>>;
+
+        async fn build_udf_stream(
+            &self,
+            _: Request<BuildUdfReq>,
+        ) -> Result<Response<Self::BuildUdfStreamStream>, Status> {
+            let (tx, rx) = mpsc::channel(32);
+            tx.send(Ok(BuildUdfEvent {
+                event: Some(build_udf_event::Event::Log("Compiling udf".to_string())),
+            }))
+            .await
+            .unwrap();
+
+            let cancelled = self.cancelled.lock().unwrap().take().unwrap();
+            tokio::spawn(async move {
+                tx.closed().await;
+                let _ = cancelled.send(());
+            });
+
+            Ok(Response::new(ReceiverStream::new(rx)))
+        }
+
+        async fn get_udf_path(
+            &self,
+            _: Request<GetUdfPathReq>,
+        ) -> Result<Response<GetUdfPathResp>, Status> {
+            Err(Status::unimplemented("not supported by the test compiler"))
+        }
+    }
+
+    async fn stalled_stream_compiler() -> (CompilerGrpcClient<Channel>, oneshot::Receiver<()>) {
+        let (tx, rx) = oneshot::channel();
+        let addr = serve_compiler(StalledStreamCompiler {
+            cancelled: Mutex::new(Some(tx)),
+        })
+        .await;
+        let channel = Channel::from_shared(addr).unwrap().connect_lazy();
+        (CompilerGrpcClient::new(channel), rx)
+    }
+
+    fn udf_crate() -> UdfCrate {
+        rust_udf_crate("#[udf] fn my_sqr(x: i64) -> i64 { x * x }", None).unwrap()
+    }
+
+    /// Serves the compiler on a local port, returning its address
+    async fn serve_compiler(compiler: impl CompilerGrpc) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(CompilerGrpcServer::new(compiler))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_build_udf_compiler_unavailable() {
+        // bind and immediately release a port so that nothing is listening on it
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let channel = Channel::from_shared(format!("http://127.0.0.1:{}", port))
+            .unwrap()
+            .connect_lazy();
+        let mut client = CompilerGrpcClient::new(channel);
+
+        let Err(err) = build_udf(
+            &mut client,
+            "#[udf] fn my_sqr(x: i64) -> i64 { x * x }",
+            UdfLanguage::Rust,
+            None,
+            false,
+            Duration::from_secs(10),
+        )
+        .await
+        else {
+            panic!("expected building against an unavailable compiler to fail");
+        };
+
+        assert_eq!(err.status_code, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_build_udf_rejects_builtin_names() {
+        // the name is checked before building, so this never reaches the (missing) compiler
+        let channel = Channel::from_static("http://127.0.0.1:1").connect_lazy();
+        let mut client = CompilerGrpcClient::new(channel);
+
+        let resp = build_udf(
+            &mut client,
+            "#[udf] fn coalesce(x: i64) -> i64 { x }",
+            UdfLanguage::Rust,
+            None,
+            false,
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(resp.name, None);
+        assert!(
+            resp.errors[0].contains("conflicts with a built-in function"),
+            "{:?}",
+            resp.errors
+        );
+    }
+
+    #[tokio::test]
+    async fn test_build_udf_times_out() {
+        let addr = serve_compiler(HangingCompiler).await;
+        let channel = Channel::from_shared(addr).unwrap().connect_lazy();
+        let mut client = CompilerGrpcClient::new(channel);
+
+        let start = Instant::now();
+        let Err(err) = tokio::time::timeout(
+            Duration::from_secs(10),
+            build_udf(
+                &mut client,
+                "#[udf] fn my_sqr(x: i64) -> i64 { x * x }",
+                UdfLanguage::Rust,
+                None,
+                false,
+                Duration::from_millis(200),
+            ),
+        )
+        .await
+        .expect("build_udf should give up once the build timeout elapses") else {
+            panic!("expected a build against a hanging compiler to fail");
+        };
+
+        assert_eq!(err.status_code, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(err.code, Some(ErrorCode::UdfBuildTimeout));
+        assert!(err.message.contains("did not finish"), "{}", err.message);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_stream_udf_build_times_out() {
+        let (mut client, cancelled) = stalled_stream_compiler().await;
+
+        let events: Vec<_> = tokio::time::timeout(
+            Duration::from_secs(10),
+            stream_udf_build(&mut client, udf_crate(), Duration::from_millis(200))
+                .await
+                .unwrap()
+                .collect(),
+        )
+        .await
+        .expect("the stream should end once the build timeout elapses");
+
+        assert_eq!(events.len(), 2, "{:?}", events);
+        assert!(format!("{:?}", events[0]).contains("Compiling udf"));
+        assert!(format!("{:?}", events[1]).contains("did not finish"));
+
+        tokio::time::timeout(Duration::from_secs(10), cancelled)
+            .await
+            .expect("the build should be cancelled on the compiler")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_udf_build_cancelled_when_client_disconnects() {
+        let (mut client, cancelled) = stalled_stream_compiler().await;
+
+        let mut events = stream_udf_build(&mut client, udf_crate(), Duration::from_secs(600))
+            .await
+            .unwrap();
+        events.next().await.unwrap().unwrap();
+        drop(events);
+
+        tokio::time::timeout(Duration::from_secs(10), cancelled)
+            .await
+            .expect("the build should be cancelled on the compiler")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_validate_udf_uses_injected_compiler() {
+        let state = AppState {
+            controller_addr: "http://127.0.0.1:1".to_string(),
+            compiler_addr: serve_compiler(SucceedingCompiler).await,
+            database: DatabaseSource::Sqlite(Arc::new(Mutex::new(
+                rusqlite::Connection::open_in_memory().unwrap(),
+            ))),
+        };
+
+        let Json(result) = validate_udf(
+            State(state),
+            Query(ValidateUdfQueryParams {
+                include_generated: None,
+            }),
+            WithRejection(
+                Json(ValidateUdfPost {
+                    definition: "#[udf] fn my_sqr(x: i64) -> i64 { x * x }".to_string(),
+                    language: UdfLanguage::Rust,
+                    cargo_lock: None,
+                }),
+                PhantomData,
+            ),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.udf_name.as_deref(), Some("my_sqr"));
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.cargo_lock.as_deref(), Some("# lockfile for my_sqr"));
+    }
+}
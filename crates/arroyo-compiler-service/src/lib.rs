@@ -5,24 +5,25 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::net::SocketAddr;
 use std::process::Stdio;
-use std::str::from_utf8;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{path::PathBuf, str::FromStr, sync::Arc};
 
 use arroyo_rpc::grpc::rpc::{
+    build_udf_event,
     compiler_grpc_server::{CompilerGrpc, CompilerGrpcServer},
-    BuildUdfReq, BuildUdfResp, GetUdfPathReq, GetUdfPathResp, UdfCrate,
+    BuildUdfEvent, BuildUdfReq, BuildUdfResp, GetUdfPathReq, GetUdfPathResp, UdfCrate,
 };
-use arroyo_rpc::var_str::VarStr;
 
 use arroyo_rpc::config::config;
 use arroyo_server_common::wrap_start;
 use arroyo_storage::StorageProvider;
 use dlopen2::utils::PLATFORM_FILE_EXTENSION;
 use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::mpsc::{channel, Sender};
 use tokio::time::timeout;
 use tokio::{process::Command, sync::Mutex};
-use toml::{toml, Table};
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
 use tracing::{error, info};
 
@@ -54,6 +55,7 @@ pub async fn start_service() -> anyhow::Result<()> {
     .await
 }
 
+#[derive(Clone)]
 pub struct CompileService {
     build_dir: PathBuf,
     lock: Arc<Mutex<()>>,
@@ -90,28 +92,24 @@ impl CompileService {
         tokio::fs::create_dir_all(&self.build_dir.join("src")).await?;
         tokio::fs::write(self.build_dir.join("src/lib.rs"), &udf_crate.definition).await?;
 
-        let mut cargo_toml = toml! {
-            [package]
-            name = "udf"
-            version = "1.0.0"
-            edition = "2021"
-
-            [lib]
-            crate-type = ["cdylib"]
-        };
-
-        let dependencies: Table = VarStr::new(udf_crate.dependencies)
-            .sub_env_vars()
-            .and_then(|deps| {
-                toml::from_str(&deps).map_err(|e| anyhow!("Invalid dependency in RPC: {:?}", e))
-            })?;
-
-        cargo_toml.insert("dependencies".to_string(), toml::Value::Table(dependencies));
-
-        tokio::fs::write(self.build_dir.join("Cargo.toml"), &cargo_toml.to_string()).await?;
+        let cargo_toml = udf_crate.cargo_toml(true)?;
+        tokio::fs::write(self.build_dir.join("Cargo.toml"), &cargo_toml).await?;
 
         tokio::fs::create_dir_all(&self.build_dir.join("src")).await?;
         tokio::fs::write(self.build_dir.join("src/lib.rs"), &udf_crate.definition).await?;
+
+        // the build dir is shared between UDFs, so a lockfile from a previous build must not
+        // leak into this one
+        let lock_path = self.build_dir.join("Cargo.lock");
+        match &udf_crate.cargo_lock {
+            Some(lock) => tokio::fs::write(&lock_path, lock).await?,
+            None => {
+                if tokio::fs::try_exists(&lock_path).await? {
+                    tokio::fs::remove_file(&lock_path).await?;
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -224,20 +222,40 @@ impl CompileService {
     }
 }
 
-fn dylib_path(name: &str, definition: &str) -> String {
+fn dylib_path(name: &str, definition: &str, cargo_lock: Option<&str>) -> String {
     let mut hasher = DefaultHasher::new();
     definition.hash(&mut hasher);
+    if let Some(cargo_lock) = cargo_lock {
+        cargo_lock.hash(&mut hasher);
+    }
     let hash = BASE64_STANDARD_NO_PAD.encode(hasher.finish().to_le_bytes());
 
     format!("udfs/{}_{}.{}", name, hash, PLATFORM_FILE_EXTENSION)
 }
 
-#[tonic::async_trait]
-impl CompilerGrpc for CompileService {
-    async fn build_udf(
+/// Path of the Cargo.lock stored alongside a compiled UDF dylib
+fn lockfile_path(dylib_path: &str) -> String {
+    format!("{}.lock", dylib_path)
+}
+
+impl CompileService {
+    /// Reads the Cargo.lock stored with a compiled UDF, if there is one
+    async fn stored_cargo_lock(&self, dylib_path: &str) -> Option<String> {
+        let bytes = self
+            .storage
+            .get(lockfile_path(dylib_path).as_str())
+            .await
+            .ok()?;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+
+    /// Builds (or checks, if not saving) the UDF crate; if `logs` is set, cargo's progress
+    /// output is sent to it line-by-line as the build runs
+    async fn build(
         &self,
-        request: Request<BuildUdfReq>,
-    ) -> Result<Response<BuildUdfResp>, Status> {
+        req: BuildUdfReq,
+        logs: Option<Sender<String>>,
+    ) -> Result<BuildUdfResp, Status> {
         // only allow one request to be active at a given time
         let _guard = self.lock.lock().await;
 
@@ -248,23 +266,37 @@ impl CompilerGrpc for CompileService {
             .await
             .map_err(|e| Status::failed_precondition(e.to_string()))?;
 
-        let req = request.into_inner();
         let udf_crate = req
             .udf_crate
             .ok_or_else(|| Status::failed_precondition("missing udf_crate field"))?;
 
-        let path = dylib_path(&udf_crate.name, &udf_crate.definition);
+        let path = dylib_path(
+            &udf_crate.name,
+            &udf_crate.definition,
+            udf_crate.cargo_lock.as_deref(),
+        );
         let canonical_url = self.storage.canonical_url_for(&path);
 
-        // exit early if udf is already compiled
+        // exit early if udf is already compiled; unlocked builds need the lockfile stored with
+        // the dylib so callers can pin it, so they're rebuilt if it's missing
         if self.storage.exists(path.as_str()).await.is_ok_and(|x| x) {
-            info!("UDF {} already compiled, skipping", udf_crate.name);
-            return Ok(Response::new(BuildUdfResp {
-                errors: vec![],
-                udf_path: Some(canonical_url),
-            }));
+            let cargo_lock = match udf_crate.cargo_lock.clone() {
+                Some(cargo_lock) => Some(cargo_lock),
+                None => self.stored_cargo_lock(&path).await,
+            };
+
+            if cargo_lock.is_some() {
+                info!("UDF {} already compiled, skipping", udf_crate.name);
+                return Ok(BuildUdfResp {
+                    errors: vec![],
+                    udf_path: Some(canonical_url),
+                    cargo_lock,
+                });
+            }
         }
 
+        let locked = udf_crate.cargo_lock.is_some();
+
         let start = Instant::now();
 
         let name = udf_crate.name.clone();
@@ -275,28 +307,57 @@ impl CompilerGrpc for CompileService {
         let cargo_command = if req.save { "build" } else { "check" };
 
         info!("{}ing udf", cargo_command);
-        let output = Command::new(&*self.cargo_path.lock().await)
+        let mut command = Command::new(&*self.cargo_path.lock().await);
+        command
             .current_dir(&self.build_dir)
             .arg(cargo_command)
             .arg("--release")
             .arg("--message-format=json")
-            .output()
-            .await
+            // if the request is cancelled (e.g., because it timed out), stop the build as well
+            .kill_on_drop(true);
+
+        if locked {
+            command.arg("--locked");
+        }
+
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
             .map_err(|e| {
                 Status::internal(format!(
                     "Failed to run cargo, will not be able to compile UDFs: {e}"
                 ))
             })?;
 
+        // cargo writes its progress to stderr, which is forwarded to the log stream if there is
+        // one; stdout has the json compiler messages
+        let (stdout, stderr) = tokio::join!(
+            read_lines(child.stdout.take().unwrap(), None),
+            read_lines(child.stderr.take().unwrap(), logs.as_ref())
+        );
+
+        let status = child.wait().await.map_err(|e| {
+            Status::internal(format!(
+                "Failed to run cargo, will not be able to compile UDFs: {e}"
+            ))
+        })?;
+
         info!(
             "Finished running cargo {} on udfs crate {} after {:.2}s, exit code: {:?}",
             cargo_command,
             &name,
             start.elapsed().as_secs_f32(),
-            output.status.code()
+            status.code()
         );
 
-        if output.status.success() {
+        if status.success() {
+            let cargo_lock = tokio::fs::read_to_string(self.build_dir.join("Cargo.lock"))
+                .await
+                .map_err(|e| {
+                    Status::internal(format!("Failed to read Cargo.lock for UDF: {}", e))
+                })?;
+
             let udf_path = if req.save {
                 // save dylib to storage
                 let dylib = tokio::fs::read(
@@ -314,30 +375,39 @@ impl CompilerGrpc for CompileService {
                     ))
                 })?;
 
+                self.storage
+                    .put(
+                        lockfile_path(&path).as_str(),
+                        cargo_lock.clone().into_bytes(),
+                    )
+                    .await
+                    .map_err(|e| {
+                        Status::internal(format!(
+                            "Failed to write UDF Cargo.lock to artifact storage: {}",
+                            e
+                        ))
+                    })?;
+
                 info!("Wrote UDF dylib to {}", canonical_url);
                 Some(canonical_url)
             } else {
                 None
             };
 
-            return Ok(Response::new(BuildUdfResp {
+            return Ok(BuildUdfResp {
                 errors: vec![],
                 udf_path,
-            }));
+                cargo_lock: Some(cargo_lock),
+            });
         }
 
-        let stdout = from_utf8(&output.stdout)
-            .map_err(|_| Status::internal("Failed to parse cargo output"))?;
-        let stderr = from_utf8(&output.stderr)
-            .map_err(|_| Status::internal("Failed to parse cargo output"))?;
-
-        let mut lines: Vec<&str> = stdout.lines().collect();
-        lines.extend(stderr.lines());
+        let mut lines = stdout;
+        lines.extend(stderr);
 
         // parse output.stdout as json
         let mut errors = vec![];
         for line in lines {
-            let line_json: serde_json::Result<Value> = serde_json::from_str(line);
+            let line_json: serde_json::Result<Value> = serde_json::from_str(&line);
             if let Ok(line_json) = line_json {
                 if line_json["reason"] == "compiler-message"
                     && line_json["message"]["level"] == "error"
@@ -350,16 +420,103 @@ impl CompilerGrpc for CompileService {
                     );
                 }
             } else {
-                errors.push(line.to_string());
+                errors.push(line);
             }
         }
 
         info!("Cargo check on udfs crate found {} errors", errors.len());
 
-        return Ok(Response::new(BuildUdfResp {
+        Ok(BuildUdfResp {
             errors,
             udf_path: None,
-        }));
+            cargo_lock: None,
+        })
+    }
+}
+
+/// Reads the output line by line until EOF. Cargo's output isn't necessarily UTF-8 (e.g., from
+/// build scripts), so lines are decoded lossily rather than ending the read, which would leave
+/// the pipe full and cargo blocked on writing to it.
+async fn read_lines(reader: impl AsyncRead + Unpin, logs: Option<&Sender<String>>) -> Vec<String> {
+    let mut reader = BufReader::new(reader);
+    let mut buf = vec![];
+    let mut out = vec![];
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                error!("Failed to read cargo output: {}", e);
+                break;
+            }
+        }
+
+        let line = String::from_utf8_lossy(&buf)
+            .trim_end_matches(['\n', '\r'])
+            .to_string();
+
+        if let Some(logs) = logs {
+            // the receiver going away shouldn't stop the build
+            let _ = logs.send(line.clone()).await;
+        }
+        out.push(line);
+    }
+    out
+}
+
+#[tonic::async_trait]
+impl CompilerGrpc for CompileService {
+    async fn build_udf(
+        &self,
+        request: Request<BuildUdfReq>,
+    ) -> Result<Response<BuildUdfResp>, Status> {
+        Ok(Response::new(self.build(request.into_inner(), None).await?))
+    }
+
+    type BuildUdfStreamStream = ReceiverStream<Result<BuildUdfEvent, Status>>;
+
+    async fn build_udf_stream(
+        &self,
+        request: Request<BuildUdfReq>,
+    ) -> Result<Response<Self::BuildUdfStreamStream>, Status> {
+        let (tx, rx) = channel(32);
+        let service = self.clone();
+
+        tokio::spawn(async move {
+            let (log_tx, mut log_rx) = channel(32);
+
+            let log_events = tx.clone();
+            let forwarder = tokio::spawn(async move {
+                while let Some(line) = log_rx.recv().await {
+                    let _ = log_events
+                        .send(Ok(BuildUdfEvent {
+                            event: Some(build_udf_event::Event::Log(line)),
+                        }))
+                        .await;
+                }
+            });
+
+            let result = tokio::select! {
+                result = service.build(request.into_inner(), Some(log_tx)) => result,
+                // the client went away (e.g., because it timed out); dropping the build kills
+                // cargo and releases the build lock
+                _ = tx.closed() => {
+                    info!("UDF build stream closed by client; cancelling build");
+                    return;
+                }
+            };
+            // make sure all logs are sent before the result
+            let _ = forwarder.await;
+
+            let _ = tx
+                .send(result.map(|resp| BuildUdfEvent {
+                    event: Some(build_udf_event::Event::Result(resp)),
+                }))
+                .await;
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
     }
 
     async fn get_udf_path(
@@ -368,7 +525,7 @@ impl CompilerGrpc for CompileService {
     ) -> Result<Response<GetUdfPathResp>, Status> {
         let req = request.into_inner();
 
-        let path = dylib_path(&req.name, &req.definition);
+        let path = dylib_path(&req.name, &req.definition, req.cargo_lock.as_deref());
         let canonical_url = self.storage.canonical_url_for(&path);
 
         let exists =
@@ -381,3 +538,18 @@ impl CompilerGrpc for CompileService {
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::read_lines;
+
+    #[tokio::test]
+    async fn test_read_lines_past_invalid_utf8() {
+        let output: &[u8] = b"first\ninvalid \xff line\r\nlast";
+
+        assert_eq!(
+            read_lines(output, None).await,
+            vec!["first", "invalid \u{FFFD} line", "last"]
+        );
+    }
+}
@@ -99,6 +99,7 @@ impl Connector for BlackholeConnector {
             bad_data: None,
             framing: None,
             metadata_fields: vec![],
+            checkpoint_alignment: Default::default(),
         };
 
         Ok(Connection::new(
@@ -357,3 +357,215 @@ impl<V: LocalWriter + Send + 'static> TwoPhaseCommitter for LocalFileSystemWrite
         Ok((data_recovery, pre_commits))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use arrow::array::{Int64Array, TimestampNanosecondArray};
+    use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+    use arroyo_rpc::df::ArroyoSchema;
+    use arroyo_rpc::formats::JsonFormat;
+    use arroyo_rpc::TIMESTAMP_FIELD;
+    use arroyo_types::to_nanos;
+
+    use super::*;
+    use crate::filesystem::sink::json::JsonLocalWriter;
+
+    fn schema() -> ArroyoSchemaRef {
+        Arc::new(
+            ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+                Field::new("value", DataType::Int64, false),
+                Field::new(
+                    TIMESTAMP_FIELD,
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    false,
+                ),
+            ])))
+            .unwrap(),
+        )
+    }
+
+    fn batch(values: Vec<i64>) -> RecordBatch {
+        let now = to_nanos(SystemTime::now()) as i64;
+        let timestamps = vec![now; values.len()];
+        RecordBatch::try_new(
+            schema().schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(values)),
+                Arc::new(TimestampNanosecondArray::from(timestamps)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn writer(target_file_size: Option<i64>) -> (String, LocalFileSystemWriter<JsonLocalWriter>) {
+        let final_dir = std::env::temp_dir()
+            .join(format!("arroyo-local-sink-{}", Uuid::new_v4()))
+            .to_string_lossy()
+            .to_string();
+        let tmp_dir = format!("{}/__in_progress", final_dir);
+        create_dir_all(&tmp_dir).unwrap();
+
+        let file_settings = FileSettings {
+            inactivity_rollover_seconds: None,
+            max_parts: None,
+            rollover_seconds: None,
+            target_file_size,
+            target_part_size: None,
+            partitioning: None,
+            commit_style: Some(CommitStyle::Direct),
+            file_naming: None,
+        };
+
+        let table_properties = FileSystemTable {
+            table_type: TableType::Sink {
+                file_settings: Some(file_settings.clone()),
+                format_settings: None,
+                write_path: final_dir.clone(),
+                storage_options: HashMap::new(),
+                shuffle_by_partition: None,
+            },
+        };
+
+        let writer = LocalFileSystemWriter {
+            writers: HashMap::new(),
+            tmp_dir,
+            final_dir: final_dir.clone(),
+            next_file_index: 0,
+            subtask_id: 0,
+            partitioner: None,
+            finished_files: Vec::new(),
+            rolling_policy: RollingPolicy::from_file_settings(&file_settings),
+            table_properties,
+            file_settings,
+            format: Some(Format::Json(JsonFormat::default())),
+            schema: Some(schema()),
+            commit_state: Some(CommitState::VanillaParquet),
+            filenaming: FileNaming {
+                strategy: Some(FilenameStrategy::Serial),
+                prefix: None,
+                suffix: Some(JsonLocalWriter::file_suffix().to_string()),
+            },
+        };
+
+        (final_dir, writer)
+    }
+
+    fn stats(bytes_written: usize, parts_written: usize) -> MultiPartWriterStats {
+        MultiPartWriterStats {
+            bytes_written,
+            parts_written,
+            last_write_at: Instant::now(),
+            first_write_at: Instant::now(),
+            representative_timestamp: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_rolling_policy() {
+        assert!(RollingPolicy::SizeLimit(100).should_roll(&stats(100, 1), None));
+        assert!(!RollingPolicy::SizeLimit(100).should_roll(&stats(99, 1), None));
+
+        assert!(RollingPolicy::PartLimit(2).should_roll(&stats(0, 2), None));
+        assert!(!RollingPolicy::PartLimit(2).should_roll(&stats(0, 1), None));
+
+        let mut idle = stats(10, 1);
+        idle.last_write_at = Instant::now() - Duration::from_secs(10);
+        assert!(RollingPolicy::InactivityDuration(Duration::from_secs(5)).should_roll(&idle, None));
+        assert!(!RollingPolicy::InactivityDuration(Duration::from_secs(5))
+            .should_roll(&stats(10, 1), None));
+
+        let hourly = RollingPolicy::WatermarkExpiration {
+            pattern: "%Y/%m/%d/%H".to_string(),
+        };
+        let mut partitioned = stats(10, 1);
+        partitioned.representative_timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(1800);
+        assert!(!hourly.should_roll(&partitioned, None));
+        assert!(!hourly.should_roll(
+            &partitioned,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(3000))
+        ));
+        assert!(hourly.should_roll(
+            &partitioned,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(3700))
+        ));
+
+        let any = RollingPolicy::AnyPolicy(vec![
+            RollingPolicy::PartLimit(10),
+            RollingPolicy::SizeLimit(100),
+        ]);
+        assert!(any.should_roll(&stats(200, 1), None));
+        assert!(!any.should_roll(&stats(50, 1), None));
+    }
+
+    #[tokio::test]
+    async fn test_files_committed_on_checkpoint() {
+        let task_info = TaskInfo::for_test("job", "sink");
+        let (final_dir, mut writer) = writer(Some(1));
+
+        writer.insert_batch(batch(vec![1, 2, 3])).await.unwrap();
+
+        // the file exceeds the target size, so it is rolled at the checkpoint, but it isn't
+        // visible in the final directory until it's committed
+        let (recovery, pre_commits) = writer.checkpoint(&task_info, None, false).await.unwrap();
+        assert!(recovery.current_files.is_empty());
+        assert_eq!(pre_commits.len(), 1);
+
+        let pre_commit = pre_commits.values().next().unwrap().clone();
+        assert!(Path::new(&pre_commit.tmp_file).exists());
+        assert!(!Path::new(&pre_commit.destination).exists());
+        assert!(pre_commit.destination.starts_with(&final_dir));
+
+        writer
+            .commit(&task_info, pre_commits.into_values().collect())
+            .await
+            .unwrap();
+
+        assert!(!Path::new(&pre_commit.tmp_file).exists());
+        let contents = std::fs::read_to_string(&pre_commit.destination).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+
+        // subsequent writes go to a new file
+        writer.insert_batch(batch(vec![4])).await.unwrap();
+        let (_, pre_commits) = writer.checkpoint(&task_info, None, false).await.unwrap();
+        assert_eq!(pre_commits.len(), 1);
+        assert_ne!(
+            pre_commits.values().next().unwrap().destination,
+            pre_commit.destination
+        );
+
+        std::fs::remove_dir_all(final_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_open_files_recovered_until_rolled() {
+        let task_info = TaskInfo::for_test("job", "sink");
+        let (final_dir, mut writer) = writer(None);
+
+        writer.insert_batch(batch(vec![1, 2])).await.unwrap();
+
+        // no rolling policy has triggered, so the file stays open and is recorded for recovery
+        let (recovery, pre_commits) = writer.checkpoint(&task_info, None, false).await.unwrap();
+        assert!(pre_commits.is_empty());
+        assert_eq!(recovery.current_files.len(), 1);
+        assert!(recovery.current_files[0].bytes_written > 0);
+
+        writer.insert_batch(batch(vec![3])).await.unwrap();
+
+        // stopping rolls all open files
+        let (recovery, pre_commits) = writer.checkpoint(&task_info, None, true).await.unwrap();
+        assert!(recovery.current_files.is_empty());
+        assert_eq!(pre_commits.len(), 1);
+
+        let pre_commit = pre_commits.values().next().unwrap().clone();
+        writer
+            .commit(&task_info, pre_commits.into_values().collect())
+            .await
+            .unwrap();
+        let contents = std::fs::read_to_string(&pre_commit.destination).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+
+        std::fs::remove_dir_all(final_dir).unwrap();
+    }
+}
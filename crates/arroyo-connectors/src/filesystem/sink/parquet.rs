@@ -327,3 +327,120 @@ pub(crate) fn representitive_timestamp(timestamp_column: &Arc<dyn Array>) -> Res
         .value(0);
     Ok(from_nanos(time as u128))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use arrow::array::{Int64Array, StringArray, TimestampNanosecondArray};
+    use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+    use arroyo_rpc::df::ArroyoSchema;
+    use arroyo_rpc::TIMESTAMP_FIELD;
+    use bytes::Bytes;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::*;
+
+    fn schema() -> ArroyoSchemaRef {
+        Arc::new(
+            ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new("message", DataType::Utf8, false),
+                Field::new(
+                    TIMESTAMP_FIELD,
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    false,
+                ),
+            ])))
+            .unwrap(),
+        )
+    }
+
+    fn table(compression: Compression, row_group_size: Option<i64>) -> FileSystemTable {
+        FileSystemTable {
+            table_type: TableType::Sink {
+                file_settings: None,
+                format_settings: Some(FormatSettings::Parquet {
+                    compression: Some(compression),
+                    row_batch_size: None,
+                    row_group_size,
+                }),
+                write_path: "/tmp/arroyo-parquet-test".to_string(),
+                storage_options: HashMap::new(),
+                shuffle_by_partition: None,
+            },
+        }
+    }
+
+    fn write(table: &FileSystemTable, rows: usize) -> Vec<u8> {
+        let schema = schema();
+        let batch = RecordBatch::try_new(
+            schema.schema.clone(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(0..rows as i64)),
+                // highly repetitive, so it compresses well
+                Arc::new(StringArray::from_iter_values(
+                    (0..rows).map(|i| format!("the same old message, number {}", i % 10)),
+                )),
+                Arc::new(TimestampNanosecondArray::from(vec![0; rows])),
+            ],
+        )
+        .unwrap();
+
+        let mut writer = RecordBatchBufferingWriter::new(table, None, schema);
+        assert!(writer.add_batch_data(batch).is_none());
+        writer.close(None).unwrap()
+    }
+
+    #[test]
+    fn test_parquet_compression() {
+        let uncompressed = write(&table(Compression::None, None), 10_000);
+        let zstd = write(&table(Compression::Zstd, None), 10_000);
+
+        assert!(
+            zstd.len() < uncompressed.len(),
+            "zstd: {} bytes, uncompressed: {} bytes",
+            zstd.len(),
+            uncompressed.len()
+        );
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(zstd)).unwrap();
+        assert_eq!(
+            reader.metadata().row_group(0).column(1).compression(),
+            parquet::basic::Compression::ZSTD(ZstdLevel::default())
+        );
+
+        let batches: Vec<_> = reader.build().unwrap().map(|b| b.unwrap()).collect();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10_000);
+        assert_eq!(batches[0].num_columns(), 2);
+        assert_eq!(
+            batches[0]
+                .column(1)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .value(3),
+            "the same old message, number 3"
+        );
+    }
+
+    #[test]
+    fn test_parquet_row_group_size() {
+        let data = write(&table(Compression::Snappy, Some(1_000)), 2_500);
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(data)).unwrap();
+        let row_groups: Vec<_> = reader
+            .metadata()
+            .row_groups()
+            .iter()
+            .map(|rg| rg.num_rows())
+            .collect();
+        assert_eq!(row_groups, vec![1_000, 1_000, 500]);
+    }
+
+    #[test]
+    fn test_invalid_parquet_compression() {
+        assert!(Compression::try_from("zstd").is_ok());
+        assert!(Compression::try_from("brotli").is_err());
+    }
+}
@@ -156,6 +156,7 @@ impl Connector for ImpulseConnector {
             bad_data: None,
             framing: None,
             metadata_fields: vec![],
+            checkpoint_alignment: Default::default(),
         };
 
         Ok(Connection::new(
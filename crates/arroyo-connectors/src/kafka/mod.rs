@@ -1,11 +1,13 @@
 use anyhow::{anyhow, bail};
 use arrow::datatypes::DataType;
 use arroyo_formats::de::ArrowDeserializer;
+use arroyo_formats::schema::{infer_from_sample, InferenceConfidence};
 use arroyo_formats::ser::ArrowSerializer;
 use arroyo_operator::connector::{Connection, MetadataDef};
 use arroyo_rpc::api_types::connections::{ConnectionProfile, ConnectionSchema, TestSourceMessage};
 use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::formats::{BadData, Format, JsonFormat};
+use arroyo_rpc::grpc::api::CheckpointAlignment;
 use arroyo_rpc::schema_resolver::{
     ConfluentSchemaRegistry, ConfluentSchemaRegistryClient, SchemaResolver,
 };
@@ -230,6 +232,7 @@ impl Connector for KafkaConnector {
             bad_data: schema.bad_data.clone(),
             framing: schema.framing.clone(),
             metadata_fields: schema.metadata_fields(),
+            checkpoint_alignment: Default::default(),
         };
 
         Ok(Connection::new(
@@ -434,24 +437,40 @@ impl Connector for KafkaConnector {
                 commit_mode,
                 key_field,
                 timestamp_field,
-            } => Ok(ConstructedOperator::from_operator(Box::new(
-                KafkaSinkFunc {
-                    bootstrap_servers: profile.bootstrap_servers.to_string(),
-                    producer: None,
-                    consistency_mode: (*commit_mode).into(),
-                    timestamp_field: timestamp_field.clone(),
-                    timestamp_col: None,
-                    key_field: key_field.clone(),
-                    key_col: None,
-                    write_futures: vec![],
-                    client_config: client_configs(&profile, Some(table.clone()))?,
-                    context: Context::new(Some(profile.clone())),
-                    topic: table.topic,
-                    serializer: ArrowSerializer::new(
-                        config.format.expect("Format must be defined for KafkaSink"),
-                    ),
-                },
-            ))),
+            } => {
+                // records after a barrier may already have been written when the checkpoint is
+                // taken and are then written again on restore, so transactions can't make the
+                // sink exactly-once
+                let commit_mode = match (commit_mode, config.checkpoint_alignment) {
+                    (SinkCommitMode::ExactlyOnce, CheckpointAlignment::AtLeastOnce) => {
+                        warn!(
+                            "kafka sink for topic {} is exactly-once, but checkpoints are at-least-once; falling back to at-least-once",
+                            table.topic
+                        );
+                        SinkCommitMode::AtLeastOnce
+                    }
+                    (commit_mode, _) => *commit_mode,
+                };
+
+                Ok(ConstructedOperator::from_operator(Box::new(
+                    KafkaSinkFunc {
+                        bootstrap_servers: profile.bootstrap_servers.to_string(),
+                        producer: None,
+                        consistency_mode: commit_mode.into(),
+                        timestamp_field: timestamp_field.clone(),
+                        timestamp_col: None,
+                        key_field: key_field.clone(),
+                        key_col: None,
+                        write_futures: vec![],
+                        client_config: client_configs(&profile, Some(table.clone()))?,
+                        context: Context::new(Some(profile.clone())),
+                        topic: table.topic,
+                        serializer: ArrowSerializer::new(
+                            config.format.expect("Format must be defined for KafkaSink"),
+                        ),
+                    },
+                )))
+            }
         }
     }
 }
@@ -705,7 +724,7 @@ impl KafkaTester {
             Format::RawBytes(_) => {
                 // all bytes are valid
             }
-            Format::Protobuf(_) => {
+            Format::Protobuf(_) | Format::ArrowIpc(_) => {
                 let aschema: ArroyoSchema = schema.clone().into();
                 let mut deserializer = ArrowDeserializer::new(
                     format.clone(),
@@ -726,7 +745,12 @@ impl KafkaTester {
 
                 if let Some(error) = error {
                     bail!(
-                        "Failed to parse message according to the provided Protobuf schema: {}",
+                        "Failed to parse message according to the provided {} schema: {}",
+                        if matches!(format, Format::Protobuf(_)) {
+                            "Protobuf"
+                        } else {
+                            "Arrow IPC"
+                        },
                         error.details()
                     );
                 }
@@ -742,10 +766,11 @@ impl KafkaTester {
         schema: Option<ConnectionSchema>,
         mut tx: Sender<TestSourceMessage>,
     ) -> anyhow::Result<()> {
-        let format = schema
-            .as_ref()
-            .and_then(|s| s.format.clone())
-            .ok_or_else(|| anyhow!("No format defined for Kafka connection"))?;
+        // without a format, the test guesses one from a sample message instead of validating
+        let format = schema.as_ref().and_then(|s| s.format.clone());
+        if format.is_none() && !matches!(table.type_, TableType::Source { .. }) {
+            bail!("No format defined for Kafka connection");
+        }
 
         let client = self
             .connect(Some(table.clone()))
@@ -810,17 +835,35 @@ impl KafkaTester {
                 match client.poll(Duration::ZERO) {
                     Some(Ok(message)) => {
                         self.info(&mut tx, "Received message from Kafka").await;
-                        self.validate_schema(
-                            &table,
-                            schema.as_ref().unwrap(),
-                            &format,
-                            message
-                                .detach()
-                                .payload()
-                                .ok_or_else(|| anyhow!("received message with empty payload"))?
-                                .to_vec(),
-                        )
-                        .await?;
+                        let payload = message
+                            .detach()
+                            .payload()
+                            .ok_or_else(|| anyhow!("received message with empty payload"))?
+                            .to_vec();
+
+                        let Some(format) = &format else {
+                            let inferred = infer_from_sample(&payload)?;
+                            let confidence = match inferred.confidence {
+                                InferenceConfidence::High => "",
+                                InferenceConfidence::Low => {
+                                    " (low confidence; check that this is correct)"
+                                }
+                            };
+                            self.info(
+                                &mut tx,
+                                format!(
+                                    "Guessed format {} from a sample message: {}{}",
+                                    inferred.schema.format.as_ref().unwrap().name(),
+                                    inferred.reason,
+                                    confidence
+                                ),
+                            )
+                            .await;
+                            return Ok(());
+                        };
+
+                        self.validate_schema(&table, schema.as_ref().unwrap(), format, payload)
+                            .await?;
 
                         self.info(&mut tx, "Successfully validated message schema")
                             .await;
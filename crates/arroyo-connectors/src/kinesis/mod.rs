@@ -112,6 +112,7 @@ impl Connector for KinesisConnector {
             bad_data: schema.bad_data.clone(),
             framing: schema.framing.clone(),
             metadata_fields: schema.metadata_fields(),
+            checkpoint_alignment: Default::default(),
         };
 
         Ok(Connection::new(
@@ -1,13 +1,13 @@
+use std::collections::HashMap;
 use std::num::NonZeroU32;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::mqtt::sink::MqttSinkFunc;
+use crate::mqtt::sink::{Envelope, MessageKey, MqttSinkFunc, TopicExpression, TopicQos};
 use crate::mqtt::source::MqttSourceFunc;
 use anyhow::{anyhow, bail};
 use arrow::datatypes::DataType;
-use arroyo_formats::ser::ArrowSerializer;
 use arroyo_operator::connector::{Connection, Connector, MetadataDef};
 use arroyo_operator::operator::ConstructedOperator;
 use arroyo_rpc::api_types::connections::{
@@ -16,7 +16,9 @@ use arroyo_rpc::api_types::connections::{
 use arroyo_rpc::var_str::VarStr;
 use arroyo_rpc::{ConnectorOptions, OperatorConfig};
 use rumqttc::v5::mqttbytes::QoS;
-use rumqttc::v5::{AsyncClient, Event as MqttEvent, EventLoop, Incoming, MqttOptions};
+use rumqttc::v5::{
+    AsyncClient, ConnectionError, Event as MqttEvent, EventLoop, Incoming, MqttOptions,
+};
 use rumqttc::Outgoing;
 use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer};
 use rustls_native_certs::load_native_certs;
@@ -30,6 +32,8 @@ const CONFIG_SCHEMA: &str = include_str!("./profile.json");
 const TABLE_SCHEMA: &str = include_str!("./table.json");
 const ICON: &str = include_str!("./mqtt.svg");
 
+const PROFILE_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub mod sink;
 pub mod source;
 
@@ -43,15 +47,19 @@ import_types!(
 import_types!(schema = "src/mqtt/table.json");
 pub struct MqttConnector {}
 
+impl From<QualityOfService> for QoS {
+    fn from(qos: QualityOfService) -> Self {
+        match qos {
+            QualityOfService::AtMostOnce => QoS::AtMostOnce,
+            QualityOfService::AtLeastOnce => QoS::AtLeastOnce,
+            QualityOfService::ExactlyOnce => QoS::ExactlyOnce,
+        }
+    }
+}
+
 impl MqttTable {
     pub fn qos(&self) -> QoS {
-        self.qos
-            .map(|qos| match qos {
-                QualityOfService::AtMostOnce => QoS::AtMostOnce,
-                QualityOfService::AtLeastOnce => QoS::AtLeastOnce,
-                QualityOfService::ExactlyOnce => QoS::ExactlyOnce,
-            })
-            .unwrap_or(QoS::AtMostOnce)
+        self.qos.map(QoS::from).unwrap_or(QoS::AtMostOnce)
     }
 }
 
@@ -73,13 +81,25 @@ impl MqttConnector {
             None
         };
 
-        Ok(MqttConfig {
+        let config = MqttConfig {
             url,
             username,
             password,
             tls,
             client_prefix: options.pull_opt_str("client_prefix")?,
-        })
+            client_id: options.pull_opt_str("client_id")?,
+            keep_alive_secs: options.pull_opt_u64("keep_alive_secs")?,
+            clean_session: options.pull_opt_bool("clean_session")?,
+            skip_retained: options.pull_opt_bool("skip_retained")?,
+            max_publishes_per_second: options.pull_opt_u64("max_publishes_per_second")?,
+            max_bytes_per_second: options.pull_opt_u64("max_bytes_per_second")?,
+            user_properties: pull_map(options, "user_properties.")?,
+            user_property_columns: pull_map(options, "user_property_columns.")?,
+            envelope: options.pull_opt_str("envelope")?,
+        };
+
+        client_id(&config, 0)?;
+        Ok(config)
     }
 
     pub fn table_from_options(options: &mut ConnectorOptions) -> anyhow::Result<MqttTable> {
@@ -102,6 +122,17 @@ impl MqttConnector {
                     })
                     .transpose()?
                     .unwrap_or(false),
+                topic_expression: options.pull_opt_str("sink.topic_expression")?,
+                qos_overrides: pull_map(options, "sink.qos_overrides.")?,
+                dedup_key_columns: options
+                    .pull_opt_str("sink.dedup_key_columns")?
+                    .map(|s| s.split(',').map(|c| c.trim().to_string()).collect())
+                    .unwrap_or_default(),
+                key_property: options.pull_opt_str("sink.key_property")?,
+                key_columns: options
+                    .pull_opt_str("sink.key_columns")?
+                    .map(|s| s.split(',').map(|c| c.trim().to_string()).collect())
+                    .unwrap_or_default(),
             },
             _ => {
                 bail!("type must be one of 'source' or 'sink")
@@ -116,6 +147,22 @@ impl MqttConnector {
     }
 }
 
+fn pull_map(
+    options: &mut ConnectorOptions,
+    prefix: &str,
+) -> anyhow::Result<HashMap<String, String>> {
+    let keys: Vec<_> = options.keys_with_prefix(prefix).cloned().collect();
+
+    keys.iter()
+        .map(|k| {
+            Ok((
+                k.trim_start_matches(prefix).to_string(),
+                options.pull_str(k)?,
+            ))
+        })
+        .collect()
+}
+
 impl Connector for MqttConnector {
     type ProfileT = MqttConfig;
     type TableT = MqttTable;
@@ -171,6 +218,67 @@ impl Connector for MqttConnector {
             .map(|t| t.to_owned())
             .ok_or_else(|| anyhow!("'format' must be set for Mqtt connection"))?;
 
+        if let TableType::Sink {
+            topic_expression: Some(topic_expression),
+            ..
+        } = &table.type_
+        {
+            // validate the expression against the sink's columns so that mistakes are reported
+            // when the pipeline is created rather than when it starts
+            TopicExpression::new(topic_expression, &schema.arroyo_schema().schema)?;
+        }
+
+        if let TableType::Sink { qos_overrides, .. } = &table.type_ {
+            TopicQos::new(table.qos(), qos_overrides)?;
+        }
+
+        if let TableType::Sink {
+            key_property: Some(property),
+            key_columns,
+            ..
+        } = &table.type_
+        {
+            // without explicit columns the key comes from the sink's input, which is only known
+            // once the query is planned
+            if !key_columns.is_empty() {
+                MessageKey::new(property.clone(), key_columns, &schema.arroyo_schema())?;
+            }
+        }
+
+        if let (TableType::Sink { .. }, Some(envelope)) = (&table.type_, &config.envelope) {
+            Envelope::new(envelope, &schema.arroyo_schema().schema)?.validate_format(&format)?;
+        }
+
+        let dedup_key_columns = match &table.type_ {
+            TableType::Sink {
+                dedup_key_columns, ..
+            } if !dedup_key_columns.is_empty() => {
+                if format.is_updating() {
+                    bail!("dedup key columns are not supported for updating sinks");
+                }
+
+                // the sink's input is partitioned by the key columns, and its dedup state by
+                // their hashes; restricting them to TEXT lets the state be declared before the
+                // sink sees its input
+                let schema = schema.arroyo_schema();
+                for c in dedup_key_columns {
+                    let field = schema
+                        .schema
+                        .field_with_name(c)
+                        .map_err(|_| anyhow!("dedup key column '{}' not found", c))?;
+                    if field.data_type() != &DataType::Utf8 {
+                        bail!(
+                            "dedup key column '{}' has type {}, but must be TEXT",
+                            c,
+                            field.data_type()
+                        );
+                    }
+                }
+                Some(dedup_key_columns.clone())
+            }
+            _ => None,
+        };
+
         let config = OperatorConfig {
             connection: serde_json::to_value(config).unwrap(),
             table: serde_json::to_value(table).unwrap(),
@@ -179,6 +287,7 @@ impl Connector for MqttConnector {
             bad_data: schema.bad_data.clone(),
             framing: schema.framing.clone(),
             metadata_fields: schema.metadata_fields(),
+            checkpoint_alignment: Default::default(),
         };
 
         Ok(Connection::new(
@@ -189,17 +298,27 @@ impl Connector for MqttConnector {
             schema,
             &config,
             desc,
-        ))
+        )
+        .with_partition_fields(dedup_key_columns))
     }
 
     fn test_profile(&self, profile: Self::ProfileT) -> Option<Receiver<TestSourceMessage>> {
         let (tx, rx) = tokio::sync::oneshot::channel();
 
         tokio::spawn(async move {
-            let (itx, _rx) = tokio::sync::mpsc::channel(8);
-            let message = match test_inner(profile, None, itx).await {
-                Ok(_) => TestSourceMessage::done("Successfully connected to Mqtt"),
-                Err(e) => TestSourceMessage::fail(format!("Failed to connect to Mqtt: {:?}", e)),
+            let message = match tokio::time::timeout(
+                PROFILE_TEST_TIMEOUT,
+                test_connectivity(profile.clone()),
+            )
+            .await
+            {
+                Ok(Ok(_)) => TestSourceMessage::done("Successfully connected to Mqtt"),
+                Ok(Err(e)) => TestSourceMessage::fail(format!("Failed to connect to Mqtt: {}", e)),
+                Err(_) => TestSourceMessage::fail(format!(
+                    "Timed out after {}s connecting to Mqtt broker at {}",
+                    PROFILE_TEST_TIMEOUT.as_secs(),
+                    profile.url
+                )),
             };
 
             tx.send(message).unwrap();
@@ -287,25 +406,79 @@ impl Connector for MqttConnector {
                 subscribed: Arc::new(AtomicBool::new(false)),
                 metadata_fields: config.metadata_fields,
             })),
-            TableType::Sink { retain } => {
-                ConstructedOperator::from_operator(Box::new(MqttSinkFunc {
-                    config: profile,
-                    qos,
-                    topic: table.topic,
+            TableType::Sink {
+                retain,
+                topic_expression,
+                qos_overrides,
+                dedup_key_columns,
+                key_property,
+                key_columns,
+            } => {
+                let format = config
+                    .format
+                    .ok_or_else(|| anyhow!("format is required for mqtt sink"))?;
+                let mut sink = MqttSinkFunc::new(
+                    profile,
+                    TopicQos::new(qos, &qos_overrides)?,
+                    table.topic,
+                    topic_expression,
                     retain,
-                    serializer: ArrowSerializer::new(
-                        config
-                            .format
-                            .ok_or_else(|| anyhow!("format is required for mqtt sink"))?,
-                    ),
-                    stopped: Arc::new(AtomicBool::new(false)),
-                    client: None,
-                }))
+                    format,
+                    dedup_key_columns,
+                )
+                .with_checkpoint_alignment(config.checkpoint_alignment);
+                if let Some(property) = key_property {
+                    sink = sink.with_key(property, key_columns);
+                }
+                ConstructedOperator::from_operator(Box::new(sink))
             }
         })
     }
 }
 
+/// Checks that a connection can be established with the profile's broker and credentials, then
+/// disconnects; unlike `test_inner`, no messages are published
+async fn test_connectivity(c: MqttConfig) -> anyhow::Result<()> {
+    // as in `test_inner`, don't take over the session of a running pipeline
+    client_id(&c, 0)?;
+    let c = MqttConfig {
+        client_id: None,
+        clean_session: None,
+        ..c
+    };
+    let (client, mut eventloop) = create_connection(&c, 0)?;
+
+    let mut connected = false;
+    loop {
+        match eventloop.poll().await {
+            Ok(MqttEvent::Incoming(Incoming::ConnAck(_))) => {
+                connected = true;
+                client.disconnect().await?;
+            }
+            Ok(MqttEvent::Outgoing(Outgoing::Disconnect)) if connected => {
+                return Ok(());
+            }
+            Ok(MqttEvent::Incoming(Incoming::Disconnect(disconnect))) => {
+                bail!("broker closed the connection: {:?}", disconnect.reason_code);
+            }
+            Ok(_) => (),
+            Err(ConnectionError::ConnectionRefused(code)) => {
+                bail!(
+                    "broker refused the connection ({:?}); check the username and password",
+                    code
+                );
+            }
+            Err(ConnectionError::Tls(e)) => {
+                bail!("TLS error connecting to {}: {}", c.url, e);
+            }
+            Err(ConnectionError::Io(e)) => {
+                bail!("could not reach broker at {}: {}", c.url, e);
+            }
+            Err(e) => bail!("error connecting to {}: {}", c.url, e),
+        }
+    }
+}
+
 async fn test_inner(
     c: MqttConfig,
     t: Option<MqttTable>,
@@ -315,6 +488,14 @@ async fn test_inner(
         .await
         .unwrap();
 
+    // validate the session settings, but test with a fresh session so that we don't take over
+    // the session of a running pipeline
+    client_id(&c, 0)?;
+    let c = MqttConfig {
+        client_id: None,
+        clean_session: None,
+        ..c
+    };
     let (client, mut eventloop) = create_connection(&c, 0)?;
 
     let wait_for_incomming = match t {
@@ -393,23 +574,39 @@ fn load_private_key<'a>(certificate: &str) -> anyhow::Result<PrivatePkcs8KeyDer<
     Ok(certs)
 }
 
+/// Returns the client id for a subtask. The broker only keeps one active connection per client id,
+/// so ids must be unique for each subtask: explicit ids are suffixed with the subtask index, and
+/// otherwise an id is generated as <client_prefix>_<task_id><current_time_in_millis>. Resuming a
+/// session (i.e., disabling clean sessions) requires an explicit id that is stable across restarts.
+pub(crate) fn client_id(c: &MqttConfig, task_id: usize) -> anyhow::Result<String> {
+    match &c.client_id {
+        Some(client_id) => {
+            if client_id.is_empty() {
+                bail!("client_id must not be empty");
+            }
+            Ok(format!("{}_{}", client_id, task_id))
+        }
+        None if c.clean_session == Some(false) => {
+            bail!("clean_session = false requires a client_id, so that sessions can be resumed")
+        }
+        None => Ok(format!(
+            "{}_{}{}",
+            c.client_prefix.as_deref().unwrap_or("arroyo-mqtt"),
+            task_id,
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis()
+                % 100000,
+        )),
+    }
+}
+
 pub(crate) fn create_connection(
     c: &MqttConfig,
     task_id: usize,
 ) -> anyhow::Result<(AsyncClient, EventLoop)> {
-    // It creates a client id with the format: <client_prefix>_<task_id><current_time_in_millis>
-    // because the client id must be unique for each connection. Otherwise, the broker will only keep one active connection
-    // per client id
-    let client_id = format!(
-        "{}_{}{}",
-        c.client_prefix.as_deref().unwrap_or("arroyo-mqtt"),
-        task_id,
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis()
-            % 100000,
-    );
+    let client_id = client_id(c, task_id)?;
 
     let mut url = url::Url::parse(&c.url)?;
     let ssl = matches!(url.scheme(), "mqtts" | "ssl");
@@ -417,7 +614,12 @@ pub(crate) fn create_connection(
 
     let mut options = MqttOptions::try_from(url)?;
 
-    options.set_keep_alive(Duration::from_secs(10));
+    options.set_keep_alive(Duration::from_secs(c.keep_alive_secs.unwrap_or(10)));
+    options.set_clean_start(c.clean_session.unwrap_or(true));
+    if c.clean_session == Some(false) {
+        // in MQTT 5, the session is dropped on disconnect unless it has an expiry interval
+        options.set_session_expiry_interval(Some(u32::MAX));
+    }
     if ssl {
         let mut root_cert_store = RootCertStore::empty();
 
@@ -470,3 +672,55 @@ pub(crate) fn create_connection(
 
     Ok(AsyncClient::new(options, 100))
 }
+
+/// A config for connecting to the broker at `url` in tests, with every other option unset
+#[cfg(test)]
+pub(crate) fn test_config(url: impl Into<String>) -> MqttConfig {
+    MqttConfig {
+        url: url.into(),
+        client_prefix: None,
+        client_id: None,
+        keep_alive_secs: None,
+        clean_session: None,
+        skip_retained: None,
+        max_publishes_per_second: None,
+        max_bytes_per_second: None,
+        user_properties: HashMap::new(),
+        user_property_columns: HashMap::new(),
+        envelope: None,
+        username: None,
+        password: None,
+        tls: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_profile_unreachable_broker() {
+        // bind and immediately release a port so that nothing is listening on it
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let config = test_config(format!("tcp://127.0.0.1:{}", port));
+
+        let message = MqttConnector {}
+            .test_profile(config)
+            .unwrap()
+            .await
+            .unwrap();
+
+        assert!(message.error, "{:?}", message);
+        assert!(message.done);
+        assert!(
+            message.message.contains("could not reach broker"),
+            "{}",
+            message.message
+        );
+    }
+}
@@ -1,41 +1,686 @@
+use anyhow::{anyhow, bail};
+use arrow::array::{
+    Array, ArrayRef, AsArray, BooleanArray, StringArray, TimestampNanosecondArray, UInt32Array,
+};
+use arrow::compute::cast;
+use arrow::compute::kernels::cmp::eq;
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit, UInt32Type};
 use arrow::record_batch::RecordBatch;
+use arrow::row::{RowConverter, Rows, SortField};
 use async_trait::async_trait;
-use std::sync::atomic::AtomicBool;
+use datafusion::common::DFSchema;
+use datafusion::execution::context::SessionContext;
+use datafusion::logical_expr::ExprSchemable;
+use datafusion::physical_expr::PhysicalExpr;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter as GovernorRateLimiter};
+use prometheus::Histogram;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::num::NonZeroU32;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::mqtt::MqttConfig;
+use crate::mqtt::{MqttConfig, QualityOfService};
 use arroyo_formats::ser::ArrowSerializer;
+use arroyo_metrics::histogram_for_task;
 use arroyo_operator::context::{Collector, OperatorContext};
 use arroyo_operator::operator::ArrowOperator;
+use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::formats::Format;
+use arroyo_rpc::grpc::api::CheckpointAlignment;
+use arroyo_rpc::grpc::rpc::{
+    GlobalKeyedTableConfig, TableConfig, TableEnum, TaskCheckpointEventType,
+};
+use arroyo_rpc::{CheckpointEvent, ControlResp, TIMESTAMP_FIELD};
+use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
+use arroyo_state::timestamp_table_config;
+use arroyo_types::{to_nanos, Backoff, ChainInfo, CheckpointBarrier, SignalMessage};
+use futures::StreamExt;
+use prost::Message;
+use rumqttc::v5::mqttbytes::v5::PublishProperties;
 use rumqttc::v5::mqttbytes::QoS;
 use rumqttc::v5::AsyncClient;
 use rumqttc::v5::ConnectionError;
+use rumqttc::v5::Event;
+use rumqttc::v5::EventLoop;
+use rumqttc::Outgoing;
+use tokio::select;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// How long to wait on close for the DISCONNECT to be sent before abandoning the connection
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[cfg(test)]
 mod test;
 
 pub struct MqttSinkFunc {
     pub config: MqttConfig,
-    pub qos: QoS,
+    pub qos: TopicQos,
     pub topic: String,
+    pub topic_expression: Option<String>,
     pub retain: bool,
+    pub updating: bool,
     pub serializer: ArrowSerializer,
     pub client: Option<AsyncClient>,
-    pub stopped: Arc<AtomicBool>,
+    // cancelled when the sink stops, so the eventloop task exits without waiting for its poll
+    pub shutdown: CancellationToken,
+    // polls the client's eventloop; exits once the client has disconnected
+    pub eventloop_task: Option<JoinHandle<()>>,
+    pub rate_limiter: Option<PublishRateLimiter>,
+    pub throttle_wait: Option<Histogram>,
+    pub deduplicator: Option<Deduplicator>,
+    // with at-least-once checkpoints, records published before a barrier may be replayed after a
+    // restore, so the dedup keys are checkpointed and kept until the checkpoint commits
+    pub checkpoint_alignment: CheckpointAlignment,
+    // planned from `topic_expression` against the input schema on start
+    pub topics: Option<TopicExpression>,
+    // parsed from the config's envelope template against the input schema on start
+    pub envelope: Option<Envelope>,
+    // the user property to publish each record's key as, and the columns forming the key (or
+    // empty to use the input's key columns)
+    pub key_property: Option<(String, Vec<String>)>,
+    // resolved from `key_property` against the input schema on start
+    pub key: Option<MessageKey>,
 }
 
 impl MqttSinkFunc {
-    pub fn new(config: MqttConfig, qos: QoS, topic: String, retain: bool, format: Format) -> Self {
+    pub fn new(
+        config: MqttConfig,
+        qos: TopicQos,
+        topic: String,
+        topic_expression: Option<String>,
+        retain: bool,
+        format: Format,
+        dedup_key_columns: Vec<String>,
+    ) -> Self {
         Self {
+            deduplicator: (!dedup_key_columns.is_empty())
+                .then(|| Deduplicator::new(dedup_key_columns)),
+            checkpoint_alignment: CheckpointAlignment::Aligned,
+            rate_limiter: PublishRateLimiter::new(&config),
             config,
             qos,
             topic,
+            topic_expression,
             retain,
+            updating: format.is_updating(),
             serializer: ArrowSerializer::new(format),
             client: None,
-            stopped: Arc::new(AtomicBool::new(false)),
+            shutdown: CancellationToken::new(),
+            eventloop_task: None,
+            throttle_wait: None,
+            topics: None,
+            envelope: None,
+            key_property: None,
+            key: None,
+        }
+    }
+
+    /// Publishes each record's key as the user property `property`, computed from `columns`, or
+    /// from the input's key columns if `columns` is empty
+    pub fn with_key(mut self, property: String, columns: Vec<String>) -> Self {
+        self.key_property = Some((property, columns));
+        self
+    }
+
+    /// Sets how the pipeline's checkpoints are aligned, which determines whether dedup keys need
+    /// to be checkpointed
+    pub fn with_checkpoint_alignment(mut self, alignment: CheckpointAlignment) -> Self {
+        self.checkpoint_alignment = alignment;
+        self
+    }
+
+    /// Whether records published before a checkpoint barrier can be replayed after restoring
+    /// from that checkpoint, in which case their dedup keys are checkpointed
+    fn checkpoints_dedup_keys(&self) -> bool {
+        self.deduplicator.is_some() && self.checkpoint_alignment == CheckpointAlignment::AtLeastOnce
+    }
+}
+
+/// The QoS to publish each topic with: the sink's QoS, unless overridden for topics matching an
+/// MQTT topic filter
+pub struct TopicQos {
+    default: QoS,
+    // ordered from most to least specific
+    overrides: Vec<(String, QoS)>,
+}
+
+impl From<QoS> for TopicQos {
+    fn from(default: QoS) -> Self {
+        Self {
+            default,
+            overrides: vec![],
+        }
+    }
+}
+
+impl TopicQos {
+    pub fn new(default: QoS, overrides: &HashMap<String, String>) -> anyhow::Result<Self> {
+        let mut overrides = overrides
+            .iter()
+            .map(|(filter, qos)| {
+                validate_topic_filter(filter)?;
+                let qos = QualityOfService::try_from(qos.as_str()).map_err(|_| {
+                    anyhow!(
+                        "invalid QoS '{}' for topic filter '{}'; expected one of AtMostOnce, AtLeastOnce or ExactlyOnce",
+                        qos,
+                        filter
+                    )
+                })?;
+                Ok((filter.clone(), qos.into()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // filters without wildcards are the most specific, followed by those with the most levels
+        overrides.sort_by_cached_key(|(filter, _)| {
+            (
+                filter.contains(['+', '#']),
+                std::cmp::Reverse(filter.split('/').count()),
+                filter.contains('#'),
+                filter.clone(),
+            )
+        });
+
+        Ok(Self { default, overrides })
+    }
+
+    pub fn qos(&self, topic: &str) -> QoS {
+        self.overrides
+            .iter()
+            .find(|(filter, _)| topic_matches(filter, topic))
+            .map(|(_, qos)| *qos)
+            .unwrap_or(self.default)
+    }
+}
+
+fn validate_topic_filter(filter: &str) -> anyhow::Result<()> {
+    let levels: Vec<_> = filter.split('/').collect();
+    for (i, level) in levels.iter().enumerate() {
+        if (level.contains('+') && *level != "+")
+            || (level.contains('#') && (*level != "#" || i != levels.len() - 1))
+        {
+            bail!(
+                "invalid topic filter '{}': wildcards must occupy a whole level, and # must be the last level",
+                filter
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Whether `topic` matches the MQTT topic `filter`, where + matches a single level and a trailing
+/// # matches any number of levels, including none
+fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut topic_levels = topic.split('/');
+    for level in filter.split('/') {
+        match (level, topic_levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (level, Some(t)) if level == t => {}
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+/// A SQL expression computing the topic to publish each record to
+pub struct TopicExpression {
+    expr: Arc<dyn PhysicalExpr>,
+}
+
+impl TopicExpression {
+    /// Plans `sql` against `schema`, casting its result to a string
+    pub fn new(sql: &str, schema: &Schema) -> anyhow::Result<Self> {
+        let plan = || -> datafusion::common::Result<_> {
+            let ctx = SessionContext::new();
+            let df_schema = DFSchema::try_from(schema.clone())?;
+            let expr = ctx
+                .parse_sql_expr(sql, &df_schema)?
+                .cast_to(&DataType::Utf8, &df_schema)?;
+            ctx.create_physical_expr(expr, &df_schema)
+        };
+
+        Ok(Self {
+            expr: plan().map_err(|e| anyhow!("invalid topic expression '{}': {}", sql, e))?,
+        })
+    }
+
+    /// Returns the topic for each row of the batch; rows with null topics should be published to
+    /// the sink's default topic
+    pub fn evaluate(&self, batch: &RecordBatch) -> anyhow::Result<StringArray> {
+        let topics = self.expr.evaluate(batch)?.into_array(batch.num_rows())?;
+        Ok(topics.as_string::<i32>().clone())
+    }
+}
+
+/// A JSON template that each serialized record is wrapped in before it is published. The
+/// template refers to the serialized record as `<payload>` and to columns of the input as
+/// `{column}`. Within a JSON string, placeholders are escaped and null values render as an empty
+/// string; elsewhere numeric and boolean values are inserted as-is, other values as JSON strings,
+/// and null values as `null`. An unquoted `<payload>` is inserted as-is, so it requires a format
+/// that serializes records as JSON.
+pub struct Envelope {
+    segments: Vec<EnvelopeSegment>,
+    columns: Vec<String>,
+}
+
+enum EnvelopeSegment {
+    Literal(String),
+    Payload { in_string: bool },
+    Column { index: usize, in_string: bool },
+}
+
+impl Envelope {
+    /// Parses `template`, checking that every column it refers to exists in `schema` and that
+    /// it renders to valid JSON
+    pub fn new(template: &str, schema: &Schema) -> anyhow::Result<Self> {
+        let mut segments = vec![];
+        let mut columns: Vec<String> = vec![];
+        let mut literal = String::new();
+        let mut in_string = false;
+        let mut escaped = false;
+        let mut rest = template;
+
+        while let Some(c) = rest.chars().next() {
+            if !escaped {
+                let placeholder = if rest.starts_with("<payload>") {
+                    Some((EnvelopeSegment::Payload { in_string }, "<payload>".len()))
+                } else if let Some(name) = placeholder_name(rest) {
+                    schema.index_of(name).map_err(|_| {
+                        anyhow!("column '{}' in the mqtt envelope does not exist", name)
+                    })?;
+                    let index = match columns.iter().position(|c| c == name) {
+                        Some(index) => index,
+                        None => {
+                            columns.push(name.to_string());
+                            columns.len() - 1
+                        }
+                    };
+                    Some((EnvelopeSegment::Column { index, in_string }, name.len() + 2))
+                } else {
+                    None
+                };
+
+                if let Some((segment, len)) = placeholder {
+                    if !literal.is_empty() {
+                        segments.push(EnvelopeSegment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(segment);
+                    rest = &rest[len..];
+                    continue;
+                }
+            }
+
+            if in_string && escaped {
+                escaped = false;
+            } else if in_string && c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = !in_string;
+            }
+
+            literal.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+
+        if !literal.is_empty() {
+            segments.push(EnvelopeSegment::Literal(literal));
+        }
+
+        let envelope = Self { segments, columns };
+
+        let mut rendered = vec![];
+        envelope.render(&mut rendered, b"null", |_| None);
+        serde_json::from_slice::<serde_json::Value>(&rendered)
+            .map_err(|e| anyhow!("mqtt envelope '{}' is not valid JSON: {}", template, e))?;
+
+        Ok(envelope)
+    }
+
+    /// Checks that records serialized with `format` can be inserted into the template; an
+    /// unquoted `<payload>` is written as-is, so it has to be plain JSON
+    pub fn validate_format(&self, format: &Format) -> anyhow::Result<()> {
+        let raw_payload = self
+            .segments
+            .iter()
+            .any(|s| matches!(s, EnvelopeSegment::Payload { in_string: false }));
+
+        match format {
+            _ if !raw_payload => Ok(()),
+            Format::Json(json) if !json.confluent_schema_registry => Ok(()),
+            Format::Json(_) => bail!(
+                "an unquoted <payload> in the mqtt envelope can't be used with the confluent schema \
+                registry, which prefixes the JSON with a schema id; quote it as \"<payload>\" to \
+                embed the payload as a string"
+            ),
+            _ => bail!(
+                "an unquoted <payload> in the mqtt envelope requires the json format; quote it as \
+                \"<payload>\" to embed the payload as a string"
+            ),
+        }
+    }
+
+    /// Returns the columns referenced by the template, cast to strings, along with whether
+    /// their values can be written into the JSON unquoted
+    pub fn columns(&self, batch: &RecordBatch) -> anyhow::Result<Vec<(ArrayRef, bool)>> {
+        self.columns
+            .iter()
+            .map(|name| {
+                let array = batch
+                    .column_by_name(name)
+                    .ok_or_else(|| anyhow!("column '{}' in the mqtt envelope not found", name))?;
+                let raw = array.data_type().is_numeric() || array.data_type() == &DataType::Boolean;
+                Ok((cast(array, &DataType::Utf8)?, raw))
+            })
+            .collect()
+    }
+
+    /// Wraps the payload for `row` of a batch whose envelope columns are `columns`
+    pub fn wrap(&self, columns: &[(ArrayRef, bool)], row: usize, payload: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(payload.len() + 64);
+        self.render(&mut out, payload, |i| {
+            let (array, raw) = &columns[i];
+            let array = array.as_string::<i32>();
+            array.is_valid(row).then(|| (array.value(row), *raw))
+        });
+        out
+    }
+
+    fn render<'a>(
+        &self,
+        out: &mut Vec<u8>,
+        payload: &[u8],
+        value: impl Fn(usize) -> Option<(&'a str, bool)>,
+    ) {
+        for segment in &self.segments {
+            match segment {
+                EnvelopeSegment::Literal(s) => out.extend_from_slice(s.as_bytes()),
+                EnvelopeSegment::Payload { in_string: false } => out.extend_from_slice(payload),
+                EnvelopeSegment::Payload { in_string: true } => {
+                    write_escaped(out, &String::from_utf8_lossy(payload))
+                }
+                EnvelopeSegment::Column { index, in_string } => match (value(*index), *in_string) {
+                    (Some((v, _)), true) => write_escaped(out, v),
+                    (None, true) => {}
+                    // NaN and infinite floats have no JSON representation
+                    (Some((v, true)), false) if !v.parse::<f64>().is_ok_and(|f| !f.is_finite()) => {
+                        out.extend_from_slice(v.as_bytes())
+                    }
+                    (Some((v, false)), false) => {
+                        out.push(b'"');
+                        write_escaped(out, v);
+                        out.push(b'"');
+                    }
+                    (_, false) => out.extend_from_slice(b"null"),
+                },
+            }
+        }
+    }
+}
+
+/// Returns the column name if `s` starts with a `{column}` placeholder
+fn placeholder_name(s: &str) -> Option<&str> {
+    let end = s.find('}')?;
+    let name = s.strip_prefix('{')?.get(..end - 1)?;
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    ((first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_'))
+    .then_some(name)
+}
+
+/// Writes `s` escaped as the contents of a JSON string
+fn write_escaped(out: &mut Vec<u8>, s: &str) {
+    let quoted = serde_json::to_string(s).expect("strings can always be serialized");
+    out.extend_from_slice(&quoted.as_bytes()[1..quoted.len() - 1]);
+}
+
+/// Computes a key for each record from its key columns, which is published as a user property so
+/// that keyed semantics survive into MQTT. Key column values are cast to strings and joined with
+/// `:`; null values are empty.
+pub struct MessageKey {
+    property: String,
+    indices: Vec<usize>,
+}
+
+impl MessageKey {
+    pub fn new(
+        property: String,
+        columns: &[String],
+        schema: &ArroyoSchema,
+    ) -> anyhow::Result<Self> {
+        let indices = if columns.is_empty() {
+            match schema.storage_keys() {
+                Some(keys) if !keys.is_empty() => keys.clone(),
+                _ => bail!(
+                    "the input to the mqtt sink is not keyed, so key columns must be set to \
+                    publish the '{}' property",
+                    property
+                ),
+            }
+        } else {
+            columns
+                .iter()
+                .map(|c| {
+                    schema
+                        .schema
+                        .index_of(c)
+                        .map_err(|_| anyhow!("key column '{}' not found", c))
+                })
+                .collect::<anyhow::Result<_>>()?
+        };
+
+        Ok(Self { property, indices })
+    }
+
+    /// Returns the user property holding the key of each row of the batch
+    pub fn properties(&self, batch: &RecordBatch) -> anyhow::Result<Vec<(String, String)>> {
+        let columns = self
+            .indices
+            .iter()
+            .map(|i| cast(batch.column(*i), &DataType::Utf8))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((0..batch.num_rows())
+            .map(|row| {
+                let key = columns
+                    .iter()
+                    .map(|c| {
+                        let c = c.as_string::<i32>();
+                        if c.is_valid(row) {
+                            c.value(row)
+                        } else {
+                            ""
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(":");
+                (self.property.clone(), key)
+            })
+            .collect())
+    }
+}
+
+/// Tracks the keys of records published since the last committed checkpoint, so that records
+/// replayed after a restore aren't published again. A key is recorded once its record has been
+/// published, belongs to the epoch of the next checkpoint barrier, and is forgotten once that
+/// checkpoint commits.
+///
+/// Keys are checkpointed as keyed state, partitioned by the hash of the key columns; the sink's
+/// input is partitioned by the same columns, so each subtask restores exactly the keys it will
+/// see replayed.
+pub struct Deduplicator {
+    columns: Vec<String>,
+    converter: RowConverter,
+    // keys published since the last checkpoint barrier
+    pending: HashSet<Vec<u8>>,
+    // keys published before each checkpoint barrier whose checkpoint hasn't committed yet
+    sealed: BTreeMap<u32, HashSet<Vec<u8>>>,
+}
+
+impl Deduplicator {
+    /// Creates a deduplicator keyed on `columns`, which must be TEXT columns
+    pub fn new(columns: Vec<String>) -> Self {
+        let converter = RowConverter::new(
+            columns
+                .iter()
+                .map(|_| SortField::new(DataType::Utf8))
+                .collect(),
+        )
+        .expect("TEXT columns can be row-encoded");
+
+        Self {
+            columns,
+            converter,
+            pending: HashSet::new(),
+            sealed: BTreeMap::new(),
+        }
+    }
+
+    /// The schema the published keys are checkpointed with: the key columns, followed by the
+    /// epoch each key was published in
+    pub fn state_schema(&self) -> ArroyoSchema {
+        let mut fields: Vec<_> = self
+            .columns
+            .iter()
+            .map(|c| Field::new(c, DataType::Utf8, true))
+            .collect();
+        fields.push(Field::new("epoch", DataType::UInt32, false));
+        fields.push(Field::new(
+            TIMESTAMP_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ));
+
+        ArroyoSchema::new_keyed(
+            Arc::new(Schema::new(fields)),
+            self.columns.len() + 1,
+            (0..self.columns.len()).collect(),
+        )
+    }
+
+    /// Returns the encoded key of each row of the batch
+    pub fn keys(&self, batch: &RecordBatch) -> anyhow::Result<Rows> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|c| {
+                batch
+                    .column_by_name(c)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("dedup key column '{}' not found", c))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(self.converter.convert_columns(&columns)?)
+    }
+
+    /// Whether a record with this key has been published since the last committed checkpoint
+    pub fn is_published(&self, key: &[u8]) -> bool {
+        self.pending.contains(key) || self.sealed.values().any(|keys| keys.contains(key))
+    }
+
+    /// Records that a record with this key has been published
+    pub fn record(&mut self, key: &[u8]) {
+        self.pending.insert(key.to_vec());
+    }
+
+    /// Assigns the keys published since the last barrier to `epoch`, returning the columns of
+    /// the state rows to checkpoint for them, if there are any
+    pub fn checkpoint(
+        &mut self,
+        epoch: u32,
+        timestamp: SystemTime,
+    ) -> anyhow::Result<Option<Vec<ArrayRef>>> {
+        let keys = std::mem::take(&mut self.pending);
+        if keys.is_empty() {
+            return Ok(None);
+        }
+
+        let parser = self.converter.parser();
+        let mut columns = self
+            .converter
+            .convert_rows(keys.iter().map(|k| parser.parse(k)))?;
+        let timestamp = to_nanos(timestamp) as i64;
+        columns.push(Arc::new(UInt32Array::from(vec![epoch; keys.len()])));
+        columns.push(Arc::new(TimestampNanosecondArray::from(vec![
+            timestamp;
+            keys.len()
+        ])));
+
+        self.sealed.entry(epoch).or_default().extend(keys);
+        Ok(Some(columns))
+    }
+
+    /// Forgets all published keys
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.sealed.clear();
+    }
+
+    /// Forgets the keys published before the barrier of the committed checkpoint `epoch`
+    pub fn commit(&mut self, epoch: u32) {
+        self.sealed.retain(|e, _| *e > epoch);
+    }
+
+    /// Restores the keys from a batch of checkpointed state. Keys checkpointed before the
+    /// restored epoch belong to checkpoints that had already committed, so are skipped; the
+    /// rest are treated as published since the last barrier, so that they're kept until the
+    /// records replayed after the restore have passed through.
+    pub fn restore(&mut self, batch: &RecordBatch, restored_epoch: u32) -> anyhow::Result<()> {
+        let epochs = batch
+            .column(self.columns.len())
+            .as_primitive_opt::<UInt32Type>()
+            .ok_or_else(|| anyhow!("invalid mqtt dedup state"))?;
+        let keys = self
+            .converter
+            .convert_columns(&batch.columns()[..self.columns.len()])?;
+
+        for (key, epoch) in keys.iter().zip(epochs.values().iter()) {
+            if *epoch >= restored_epoch {
+                self.pending.insert(key.as_ref().to_vec());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Token buckets enforcing the sink's configured publish and byte rate limits
+pub struct PublishRateLimiter {
+    publishes: Option<DefaultDirectRateLimiter>,
+    bytes: Option<(DefaultDirectRateLimiter, NonZeroU32)>,
+}
+
+impl PublishRateLimiter {
+    /// Returns None if no limits are configured
+    pub fn new(config: &MqttConfig) -> Option<Self> {
+        let limit = |l: Option<u64>| NonZeroU32::new(l?.min(u32::MAX as u64) as u32);
+
+        let publishes = limit(config.max_publishes_per_second)
+            .map(|l| GovernorRateLimiter::direct(Quota::per_second(l)));
+        let bytes = limit(config.max_bytes_per_second)
+            .map(|l| (GovernorRateLimiter::direct(Quota::per_second(l)), l));
+
+        (publishes.is_some() || bytes.is_some()).then_some(Self { publishes, bytes })
+    }
+
+    /// Waits until a message with a payload of `size` bytes may be published
+    pub async fn until_ready(&self, size: usize) {
+        if let Some(publishes) = &self.publishes {
+            publishes.until_ready().await;
+        }
+
+        if let Some((bytes, burst)) = &self.bytes {
+            // payloads larger than the per-second limit wait for a full bucket
+            let size = size.min(u32::MAX as usize) as u32;
+            if let Some(n) = NonZeroU32::new(size) {
+                bytes
+                    .until_n_ready(n.min(*burst))
+                    .await
+                    .expect("size is clamped to the bucket capacity");
+            }
         }
     }
 }
@@ -45,37 +690,126 @@ impl ArrowOperator for MqttSinkFunc {
     fn name(&self) -> String {
         format!("mqtt-producer-{}", self.topic)
     }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        let Some(deduplicator) = self
+            .deduplicator
+            .as_ref()
+            .filter(|_| self.checkpoints_dedup_keys())
+        else {
+            return HashMap::new();
+        };
+
+        [
+            (
+                "d".to_string(),
+                timestamp_table_config(
+                    "d",
+                    "mqtt sink published keys",
+                    Duration::ZERO,
+                    false,
+                    deduplicator.state_schema(),
+                ),
+            ),
+            (
+                "c".to_string(),
+                TableConfig {
+                    table_type: TableEnum::GlobalKeyValue.into(),
+                    config: GlobalKeyedTableConfig {
+                        table_name: "c".to_string(),
+                        description: "mqtt sink checkpointed epochs".to_string(),
+                        uses_two_phase_commit: true,
+                    }
+                    .encode_to_vec(),
+                },
+            ),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn is_committing(&self) -> bool {
+        self.checkpoints_dedup_keys()
+    }
+
     async fn on_start(&mut self, ctx: &mut OperatorContext) {
-        let mut attempts = 0;
-        while attempts < 20 {
+        let restored_epoch = ctx.table_manager.restored_epoch();
+        if let (true, Some(deduplicator), Some(restored_epoch)) = (
+            self.checkpoints_dedup_keys(),
+            &mut self.deduplicator,
+            restored_epoch,
+        ) {
+            let table = ctx
+                .table_manager
+                .get_uncached_key_value_view("d")
+                .await
+                .expect("should be able to get mqtt dedup state");
+            let mut batches = Box::pin(table.get_all());
+            while let Some(batch) = batches.next().await {
+                batch
+                    .and_then(|batch| deduplicator.restore(&batch, restored_epoch))
+                    .expect("should be able to restore mqtt dedup state");
+            }
+        }
+
+        if self.rate_limiter.is_some() {
+            let chain_info = ChainInfo {
+                job_id: ctx.task_info.job_id.clone(),
+                node_id: ctx.task_info.node_id,
+                description: ctx.task_info.operator_name.clone(),
+                task_index: ctx.task_info.task_index,
+            };
+            self.throttle_wait = histogram_for_task(
+                &chain_info,
+                "arroyo_worker_mqtt_sink_throttle_wait_seconds",
+                "Time the mqtt sink spent waiting on its rate limit before publishing",
+                HashMap::new(),
+                vec![0.0, 0.001, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0],
+            );
+        }
+
+        if let Some(topic_expression) = &self.topic_expression {
+            match TopicExpression::new(topic_expression, &ctx.in_schemas[0].schema) {
+                Ok(topics) => self.topics = Some(topics),
+                Err(e) => {
+                    ctx.report_error("Invalid mqtt topic expression", e.to_string())
+                        .await;
+                    panic!("Invalid mqtt topic expression: {}", e);
+                }
+            }
+        }
+
+        if let Some(envelope) = &self.config.envelope {
+            match Envelope::new(envelope, &ctx.in_schemas[0].schema) {
+                Ok(envelope) => self.envelope = Some(envelope),
+                Err(e) => {
+                    ctx.report_error("Invalid mqtt envelope", e.to_string())
+                        .await;
+                    panic!("Invalid mqtt envelope: {}", e);
+                }
+            }
+        }
+
+        if let Some((property, columns)) = &self.key_property {
+            match MessageKey::new(property.clone(), columns, &ctx.in_schemas[0]) {
+                Ok(key) => self.key = Some(key),
+                Err(e) => {
+                    ctx.report_error("Invalid mqtt key", e.to_string()).await;
+                    panic!("Invalid mqtt key: {}", e);
+                }
+            }
+        }
+
+        let mut backoff = Backoff::new(Duration::from_millis(50), Duration::from_secs(5), 2.0)
+            .with_max_attempts(20);
+        loop {
             match super::create_connection(&self.config, ctx.task_info.task_index as usize) {
-                Ok((client, mut eventloop)) => {
+                Ok((client, eventloop)) => {
                     self.client = Some(client);
-                    let stopped = self.stopped.clone();
-                    tokio::spawn(async move {
-                        while !stopped.load(std::sync::atomic::Ordering::Relaxed) {
-                            match eventloop.poll().await {
-                                Ok(_) => (),
-                                Err(err) => match err {
-                                    ConnectionError::Timeout(_) => (),
-                                    ConnectionError::MqttState(rumqttc::v5::StateError::Io(
-                                        err,
-                                    ))
-                                    | ConnectionError::Io(err)
-                                        if err.kind() == std::io::ErrorKind::ConnectionAborted
-                                            || err.kind()
-                                                == std::io::ErrorKind::ConnectionReset =>
-                                    {
-                                        continue;
-                                    }
-                                    err => {
-                                        tracing::error!("Failed to poll mqtt eventloop: {:?}", err);
-                                        tokio::time::sleep(Duration::from_secs(1)).await;
-                                    }
-                                },
-                            }
-                        }
-                    });
+                    self.eventloop_task = Some(tokio::spawn(poll_eventloop(
+                        eventloop,
+                        self.shutdown.clone(),
+                    )));
                     return;
                 }
                 Err(e) => {
@@ -83,8 +817,10 @@ impl ArrowOperator for MqttSinkFunc {
                 }
             };
 
-            tokio::time::sleep(Duration::from_millis((50 * (1 << attempts)).min(5_000))).await;
-            attempts -= 1;
+            let Some(delay) = backoff.next_delay() else {
+                break;
+            };
+            tokio::time::sleep(delay).await;
         }
 
         panic!("Failed to establish connection to mqtt after 20 retries");
@@ -96,15 +832,135 @@ impl ArrowOperator for MqttSinkFunc {
         ctx: &mut OperatorContext,
         _: &mut dyn Collector,
     ) {
-        for v in self.serializer.serialize(&batch) {
-            match self
-                .client
-                .as_mut()
-                .unwrap()
-                .publish(&self.topic, self.qos, self.retain, v)
-                .await
-            {
-                Ok(_) => (),
+        let deletes = if self.updating {
+            retraction_mask(&batch)
+        } else {
+            None
+        };
+
+        let dedup_keys = match self.deduplicator.as_ref().map(|d| d.keys(&batch)) {
+            Some(Ok(keys)) => Some(keys),
+            Some(Err(e)) => {
+                ctx.report_error("Invalid mqtt dedup key", e.to_string())
+                    .await;
+                panic!("Invalid mqtt dedup key: {}", e);
+            }
+            None => None,
+        };
+
+        let topics = match self.topics.as_ref().map(|t| t.evaluate(&batch)) {
+            Some(Ok(topics)) => Some(topics),
+            Some(Err(e)) => {
+                ctx.report_error("Failed to compute mqtt topic", e.to_string())
+                    .await;
+                panic!("Failed to compute mqtt topic: {}", e);
+            }
+            None => None,
+        };
+
+        let envelope_columns = match self.envelope.as_ref().map(|e| e.columns(&batch)) {
+            Some(Ok(columns)) => Some(columns),
+            Some(Err(e)) => {
+                ctx.report_error("Invalid mqtt envelope", e.to_string())
+                    .await;
+                panic!("Invalid mqtt envelope: {}", e);
+            }
+            None => None,
+        };
+
+        let mut properties = match user_properties(&self.config, &batch) {
+            Ok(properties) => properties,
+            Err(e) => {
+                ctx.report_error("Invalid mqtt user properties", e.to_string())
+                    .await;
+                panic!("Invalid mqtt user properties: {}", e);
+            }
+        };
+
+        match self.key.as_ref().map(|k| k.properties(&batch)) {
+            Some(Ok(keys)) => {
+                let properties = properties.get_or_insert_with(|| vec![vec![]; batch.num_rows()]);
+                for (p, key) in properties.iter_mut().zip(keys) {
+                    p.push(key);
+                }
+            }
+            Some(Err(e)) => {
+                ctx.report_error("Failed to compute mqtt key", e.to_string())
+                    .await;
+                panic!("Failed to compute mqtt key: {}", e);
+            }
+            None => {}
+        }
+
+        for (i, v) in self.serializer.serialize(&batch).enumerate() {
+            let is_delete = deletes.as_ref().is_some_and(|d| d.value(i));
+
+            // deletes are always published, so that a replayed retraction still clears the
+            // topic; other records are skipped if their key has already been published
+            let dedup_key = dedup_keys.as_ref().filter(|_| !is_delete).map(|k| k.row(i));
+            if dedup_key.is_some_and(|key| {
+                self.deduplicator
+                    .as_ref()
+                    .unwrap()
+                    .is_published(key.as_ref())
+            }) {
+                continue;
+            }
+
+            // deletes are published as an empty retained message, which clears any retained
+            // value for the topic on the broker
+            let (retain, payload) = match &deletes {
+                Some(deletes) if deletes.value(i) => (true, vec![]),
+                _ => match (&self.envelope, &envelope_columns) {
+                    (Some(envelope), Some(columns)) => (self.retain, envelope.wrap(columns, i, &v)),
+                    _ => (self.retain, v),
+                },
+            };
+
+            // wait rather than drop, so that backpressure slows down the rest of the pipeline
+            if let Some(rate_limiter) = &self.rate_limiter {
+                let start = Instant::now();
+                rate_limiter.until_ready(payload.len()).await;
+                if let Some(throttle_wait) = &self.throttle_wait {
+                    throttle_wait.observe(start.elapsed().as_secs_f64());
+                }
+            }
+
+            let topic = match &topics {
+                Some(topics) if topics.is_valid(i) => topics.value(i),
+                _ => self.topic.as_str(),
+            };
+
+            let qos = self.qos.qos(topic);
+
+            let client = self.client.as_mut().unwrap();
+            let start = Instant::now();
+            let result = match &properties {
+                Some(properties) => {
+                    client
+                        .publish_with_properties(
+                            topic,
+                            qos,
+                            retain,
+                            payload,
+                            PublishProperties {
+                                user_properties: properties[i].clone(),
+                                ..Default::default()
+                            },
+                        )
+                        .await
+                }
+                None => client.publish(topic, qos, retain, payload).await,
+            };
+            // publish blocks when the client's request queue to the broker is full
+            ctx.record_backpressure(start.elapsed());
+
+            match result {
+                Ok(_) => {
+                    if let Some(key) = dedup_key {
+                        self.deduplicator.as_mut().unwrap().record(key.as_ref());
+                    }
+                }
                 Err(e) => {
                     ctx.report_error("Could not write to mqtt", format!("{:?}", e))
                         .await;
@@ -113,11 +969,201 @@ impl ArrowOperator for MqttSinkFunc {
             }
         }
     }
+
+    async fn handle_checkpoint(
+        &mut self,
+        barrier: CheckpointBarrier,
+        ctx: &mut OperatorContext,
+        _: &mut dyn Collector,
+    ) {
+        let checkpoints_dedup_keys = self.checkpoints_dedup_keys();
+        let Some(deduplicator) = &mut self.deduplicator else {
+            return;
+        };
+
+        // with aligned checkpoints every record published before the barrier is covered by the
+        // checkpoint, so none of them can be replayed
+        if !checkpoints_dedup_keys {
+            deduplicator.clear();
+            return;
+        }
+
+        // the keys' state expires with the watermark, by which point the checkpoint that
+        // wrote them has long since committed
+        let timestamp = ctx.last_present_watermark().unwrap_or(barrier.timestamp);
+        let columns = deduplicator
+            .checkpoint(barrier.epoch, timestamp)
+            .expect("should be able to encode mqtt dedup keys");
+
+        if let Some(columns) = columns {
+            ctx.table_manager
+                .get_uncached_key_value_view("d")
+                .await
+                .expect("should be able to get mqtt dedup state")
+                .insert_batch(columns)
+                .await
+                .expect("should be able to write mqtt dedup state");
+        }
+
+        // written with two-phase commit, so that the sink is told when the checkpoint commits
+        let epochs: &mut GlobalKeyedView<u32, u32> = ctx
+            .table_manager
+            .get_global_keyed_state("c")
+            .await
+            .expect("should be able to get mqtt commit state");
+        epochs.insert(ctx.task_info.task_index, barrier.epoch).await;
+    }
+
+    async fn handle_commit(
+        &mut self,
+        epoch: u32,
+        _: &HashMap<String, HashMap<u32, Vec<u8>>>,
+        ctx: &mut OperatorContext,
+    ) {
+        if let Some(deduplicator) = &mut self.deduplicator {
+            deduplicator.commit(epoch);
+        }
+
+        ctx.control_tx
+            .send(ControlResp::CheckpointEvent(CheckpointEvent {
+                checkpoint_epoch: epoch,
+                node_id: ctx.task_info.node_id,
+                operator_id: ctx.task_info.operator_id.clone(),
+                subtask_index: ctx.task_info.task_index,
+                time: SystemTime::now(),
+                event_type: TaskCheckpointEventType::FinishedCommit,
+            }))
+            .await
+            .expect("sent commit event");
+    }
+
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        _: &mut OperatorContext,
+        _: &mut dyn Collector,
+    ) {
+        self.disconnect().await;
+    }
+}
+
+impl MqttSinkFunc {
+    /// Sends an MQTT DISCONNECT so the broker treats this as a clean shutdown (and doesn't
+    /// publish the last will), then waits for the eventloop task to write it and exit
+    async fn disconnect(&mut self) {
+        let Some(task) = self.eventloop_task.take() else {
+            return;
+        };
+
+        if let Some(client) = &self.client {
+            if let Err(e) = client.disconnect().await {
+                warn!("Failed to send mqtt disconnect: {:?}", e);
+            }
+        }
+
+        let abort = task.abort_handle();
+        if tokio::time::timeout(DISCONNECT_TIMEOUT, task)
+            .await
+            .is_err()
+        {
+            warn!(
+                "mqtt eventloop did not exit within {:?} of disconnecting; aborting it",
+                DISCONNECT_TIMEOUT
+            );
+            abort.abort();
+        }
+
+        self.shutdown.cancel();
+    }
+}
+
+/// Polls the client's eventloop, which is what actually sends publishes to the broker, until the
+/// DISCONNECT has been written or `shutdown` is cancelled
+async fn poll_eventloop(mut eventloop: EventLoop, shutdown: CancellationToken) {
+    loop {
+        let event = select! {
+            _ = shutdown.cancelled() => return,
+            event = eventloop.poll() => event,
+        };
+
+        match event {
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => {
+                // the DISCONNECT packet has been written to the broker
+                return;
+            }
+            Ok(_) => (),
+            Err(err) => match err {
+                ConnectionError::Timeout(_) => (),
+                ConnectionError::MqttState(rumqttc::v5::StateError::Io(err))
+                | ConnectionError::Io(err)
+                    if err.kind() == std::io::ErrorKind::ConnectionAborted
+                        || err.kind() == std::io::ErrorKind::ConnectionReset => {}
+                err => {
+                    tracing::error!("Failed to poll mqtt eventloop: {:?}", err);
+                    select! {
+                        _ = shutdown.cancelled() => return,
+                        _ = tokio::time::sleep(Duration::from_secs(1)) => (),
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// For a changelog (debezium-encoded) batch, returns a mask that is true for every row
+/// whose `op` is a delete; returns None if the batch has no `op` column.
+pub(crate) fn retraction_mask(batch: &RecordBatch) -> Option<BooleanArray> {
+    let op = batch.column_by_name("op")?.as_string_opt::<i32>()?;
+    eq(op, &StringArray::new_scalar("d")).ok()
+}
+
+/// Resolves the configured user properties for each row of the batch, or None if no user
+/// properties are configured. Column values are cast to strings; null values omit the property.
+pub(crate) fn user_properties(
+    config: &MqttConfig,
+    batch: &RecordBatch,
+) -> anyhow::Result<Option<Vec<Vec<(String, String)>>>> {
+    if config.user_properties.is_empty() && config.user_property_columns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut constants: Vec<_> = config
+        .user_properties
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    constants.sort();
+
+    let mut columns = config
+        .user_property_columns
+        .iter()
+        .map(|(key, column)| {
+            let array = batch.column_by_name(column).ok_or_else(|| {
+                anyhow!("column '{}' for user property '{}' not found", column, key)
+            })?;
+            Ok((key, cast(array, &DataType::Utf8)?))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    columns.sort_by(|a, b| a.0.cmp(b.0));
+
+    Ok(Some(
+        (0..batch.num_rows())
+            .map(|i| {
+                let mut properties = constants.clone();
+                for (key, array) in &columns {
+                    let array = array.as_string::<i32>();
+                    if array.is_valid(i) {
+                        properties.push((key.to_string(), array.value(i).to_string()));
+                    }
+                }
+                properties
+            })
+            .collect(),
+    ))
 }
 
 impl Drop for MqttSinkFunc {
     fn drop(&mut self) {
-        self.stopped
-            .store(true, std::sync::atomic::Ordering::Relaxed);
+        self.shutdown.cancel();
     }
 }
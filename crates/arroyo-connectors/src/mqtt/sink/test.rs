@@ -1,16 +1,20 @@
-use arrow::array::{RecordBatch, StringArray};
+use arrow::array::{Array, Int64Array, RecordBatch, StringArray};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use super::MqttSinkFunc;
-use crate::mqtt::{create_connection, MqttConfig, Tls};
+use super::{
+    poll_eventloop, retraction_mask, user_properties, Deduplicator, Envelope, MessageKey,
+    MqttSinkFunc, PublishRateLimiter, TopicExpression, TopicQos, DISCONNECT_TIMEOUT,
+};
+use crate::mqtt::{client_id, create_connection, test_config, MqttConfig, Tls};
 use crate::test::DummyCollector;
 use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 use arroyo_operator::context::OperatorContext;
 use arroyo_operator::operator::ArrowOperator;
 use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::{
-    formats::{Format, JsonFormat},
+    formats::{Format, JsonFormat, RawStringFormat},
     var_str::VarStr,
 };
 use arroyo_types::get_test_task_info;
@@ -21,6 +25,7 @@ use rumqttc::{
 };
 use serde::Deserialize;
 use tokio::sync::mpsc::channel;
+use tokio_util::sync::CancellationToken;
 
 fn schema() -> SchemaRef {
     Arc::new(Schema::new(vec![Field::new(
@@ -48,7 +53,6 @@ pub struct MqttTopicTester {
 impl MqttTopicTester {
     fn get_config(&self) -> MqttConfig {
         MqttConfig {
-            url: format!("tcp://localhost:{}", self.port),
             client_prefix: Some("test".to_string()),
             username: self.username.as_ref().map(|u| VarStr::new(u.clone())),
             password: self.password.as_ref().map(|p| VarStr::new(p.clone())),
@@ -57,6 +61,7 @@ impl MqttTopicTester {
                 cert: self.cert.as_ref().map(|ca| VarStr::new(ca.clone())),
                 key: self.key.as_ref().map(|ca| VarStr::new(ca.clone())),
             }),
+            ..test_config(format!("tcp://localhost:{}", self.port))
         }
     }
 
@@ -69,10 +74,12 @@ impl MqttTopicTester {
         let config = self.get_config();
         let mut mqtt = MqttSinkFunc::new(
             config,
-            QoS::AtLeastOnce,
+            QoS::AtLeastOnce.into(),
             self.topic.clone(),
+            None,
             false,
             Format::Json(JsonFormat::default()),
+            vec![],
         );
 
         let (command_tx, _) = channel(128);
@@ -169,4 +176,447 @@ async fn test_mqtt() {
             }
         }
     }
+
+    // closing disconnects cleanly, so the eventloop exits without needing to be aborted
+    let start = std::time::Instant::now();
+    sink_with_writes
+        .sink
+        .on_close(&None, &mut sink_with_writes.ctx, &mut DummyCollector {})
+        .await;
+    assert!(start.elapsed() < DISCONNECT_TIMEOUT);
+    assert!(sink_with_writes.sink.eventloop_task.is_none());
+}
+
+#[test]
+fn test_retraction_mask() {
+    let changelog_schema = Arc::new(Schema::new(vec![
+        Field::new("after", DataType::Utf8, true),
+        Field::new("op", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        changelog_schema,
+        vec![
+            Arc::new(StringArray::from(vec![Some("a"), None, Some("b")])),
+            Arc::new(StringArray::from(vec!["c", "d", "u"])),
+        ],
+    )
+    .unwrap();
+
+    let mask = retraction_mask(&batch).unwrap();
+    assert_eq!(
+        mask.iter().collect::<Vec<_>>(),
+        vec![Some(false), Some(true), Some(false)]
+    );
+
+    let data = StringArray::from_iter_values(vec!["x".to_string()]);
+    let batch = RecordBatch::try_new(schema(), vec![Arc::new(data)]).unwrap();
+    assert!(retraction_mask(&batch).is_none());
+}
+
+#[test]
+fn test_user_properties() {
+    let mut config = test_config("tcp://localhost:1883");
+
+    let batch_schema = Arc::new(Schema::new(vec![
+        Field::new("value", DataType::Utf8, false),
+        Field::new("tenant_id", DataType::Int64, true),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        batch_schema,
+        vec![
+            Arc::new(StringArray::from(vec!["a", "b"])),
+            Arc::new(Int64Array::from(vec![Some(7), None])),
+        ],
+    )
+    .unwrap();
+
+    assert!(user_properties(&config, &batch).unwrap().is_none());
+
+    config
+        .user_properties
+        .insert("producer".to_string(), "arroyo".to_string());
+    config
+        .user_property_columns
+        .insert("tenant".to_string(), "tenant_id".to_string());
+
+    let properties = user_properties(&config, &batch).unwrap().unwrap();
+    assert_eq!(
+        properties,
+        vec![
+            vec![
+                ("producer".to_string(), "arroyo".to_string()),
+                ("tenant".to_string(), "7".to_string())
+            ],
+            vec![("producer".to_string(), "arroyo".to_string())],
+        ]
+    );
+
+    config
+        .user_property_columns
+        .insert("missing".to_string(), "not_a_column".to_string());
+    assert!(user_properties(&config, &batch).is_err());
+}
+
+#[tokio::test]
+async fn test_publish_rate_limiter() {
+    let mut config = test_config("tcp://localhost:1883");
+
+    assert!(PublishRateLimiter::new(&config).is_none());
+
+    config.max_publishes_per_second = Some(0);
+    assert!(PublishRateLimiter::new(&config).is_none());
+
+    // the first two publishes fit in the bucket; the third must wait for a token
+    config.max_publishes_per_second = Some(2);
+    let limiter = PublishRateLimiter::new(&config).unwrap();
+    let start = std::time::Instant::now();
+    for _ in 0..3 {
+        limiter.until_ready(10).await;
+    }
+    assert!(start.elapsed() >= std::time::Duration::from_millis(400));
+
+    // payloads larger than the byte limit wait for a full bucket rather than failing
+    config.max_publishes_per_second = None;
+    config.max_bytes_per_second = Some(100);
+    let limiter = PublishRateLimiter::new(&config).unwrap();
+    let start = std::time::Instant::now();
+    limiter.until_ready(1000).await;
+    limiter.until_ready(50).await;
+    assert!(start.elapsed() >= std::time::Duration::from_millis(400));
+
+    // empty payloads (e.g., deletes) don't consume bytes
+    limiter.until_ready(0).await;
+}
+
+#[test]
+fn test_topic_expression() {
+    let batch_schema = Arc::new(Schema::new(vec![
+        Field::new("region", DataType::Utf8, true),
+        Field::new("shard", DataType::Int64, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        batch_schema.clone(),
+        vec![
+            Arc::new(StringArray::from(vec![Some("US-East"), Some("EU"), None])),
+            Arc::new(Int64Array::from(vec![1, 2, 3])),
+        ],
+    )
+    .unwrap();
+
+    let topics = TopicExpression::new("lower(region) || '/' || cast(shard as text)", &batch_schema)
+        .unwrap()
+        .evaluate(&batch)
+        .unwrap();
+    assert_eq!(topics.value(0), "us-east/1");
+    assert_eq!(topics.value(1), "eu/2");
+    // null topics fall back to the sink's topic
+    assert!(topics.is_null(2));
+
+    // non-string results are cast
+    let topics = TopicExpression::new("shard * 10", &batch_schema)
+        .unwrap()
+        .evaluate(&batch)
+        .unwrap();
+    assert_eq!(topics.value(2), "30");
+
+    assert!(TopicExpression::new("lower(missing)", &batch_schema).is_err());
+}
+
+#[test]
+fn test_topic_qos() {
+    let overrides: HashMap<_, _> = [
+        ("alerts/#", "ExactlyOnce"),
+        ("alerts/+/debug", "AtMostOnce"),
+        ("alerts/eu/debug", "AtLeastOnce"),
+        ("metrics/+", "AtLeastOnce"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+
+    let qos = TopicQos::new(QoS::AtMostOnce, &overrides).unwrap();
+
+    assert_eq!(qos.qos("alerts"), QoS::ExactlyOnce);
+    assert_eq!(qos.qos("alerts/us/critical"), QoS::ExactlyOnce);
+    // the most specific filter wins
+    assert_eq!(qos.qos("alerts/us/debug"), QoS::AtMostOnce);
+    assert_eq!(qos.qos("alerts/eu/debug"), QoS::AtLeastOnce);
+    assert_eq!(qos.qos("metrics/cpu"), QoS::AtLeastOnce);
+    // + matches exactly one level
+    assert_eq!(qos.qos("metrics/cpu/0"), QoS::AtMostOnce);
+    assert_eq!(qos.qos("metrics"), QoS::AtMostOnce);
+    assert_eq!(qos.qos("other"), QoS::AtMostOnce);
+
+    let invalid = |filter: &str, qos: &str| {
+        TopicQos::new(
+            QoS::AtMostOnce,
+            &[(filter.to_string(), qos.to_string())]
+                .into_iter()
+                .collect(),
+        )
+        .is_err()
+    };
+    assert!(invalid("alerts/#", "Sometimes"));
+    assert!(invalid("alerts/#/debug", "AtMostOnce"));
+    assert!(invalid("alerts+/debug", "AtMostOnce"));
+}
+
+#[test]
+fn test_envelope() {
+    let batch_schema = Arc::new(Schema::new(vec![
+        Field::new("source", DataType::Utf8, true),
+        Field::new("version", DataType::Int64, true),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        batch_schema.clone(),
+        vec![
+            Arc::new(StringArray::from(vec![Some("sensor \"a\""), None])),
+            Arc::new(Int64Array::from(vec![Some(2), None])),
+        ],
+    )
+    .unwrap();
+
+    let envelope = Envelope::new(
+        r#"{"meta": {"source": "{source}", "v": {version}, "src": {source}}, "data": <payload>}"#,
+        &batch_schema,
+    )
+    .unwrap();
+    let columns = envelope.columns(&batch).unwrap();
+
+    let wrapped = envelope.wrap(&columns, 0, br#"{"x":1}"#);
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&wrapped).unwrap(),
+        serde_json::json!({
+            "meta": {"source": "sensor \"a\"", "v": 2, "src": "sensor \"a\""},
+            "data": {"x": 1}
+        })
+    );
+
+    let wrapped = envelope.wrap(&columns, 1, br#"{"x":2}"#);
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&wrapped).unwrap(),
+        serde_json::json!({
+            "meta": {"source": "", "v": null, "src": null},
+            "data": {"x": 2}
+        })
+    );
+
+    // payloads within a string are escaped
+    let envelope = Envelope::new(r#"{"raw": "<payload>"}"#, &batch_schema).unwrap();
+    let wrapped = envelope.wrap(&[], 0, br#"{"x":1}"#);
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&wrapped).unwrap(),
+        serde_json::json!({"raw": "{\"x\":1}"})
+    );
+
+    // only JSON payloads can be inserted unquoted
+    let raw_string = Format::RawString(RawStringFormat {});
+    assert!(envelope.validate_format(&raw_string).is_ok());
+    let envelope = Envelope::new(r#"{"data": <payload>}"#, &batch_schema).unwrap();
+    assert!(envelope
+        .validate_format(&Format::Json(JsonFormat::default()))
+        .is_ok());
+    assert!(envelope.validate_format(&raw_string).is_err());
+    assert!(envelope
+        .validate_format(&Format::Json(JsonFormat {
+            confluent_schema_registry: true,
+            ..Default::default()
+        }))
+        .is_err());
+
+    assert!(Envelope::new(r#"{"v": {missing}}"#, &batch_schema).is_err());
+    assert!(Envelope::new(r#"{"data": <payload>"#, &batch_schema).is_err());
+}
+
+#[test]
+fn test_message_key() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("region", DataType::Utf8, true),
+        Field::new("id", DataType::Int64, false),
+        Field::new("value", DataType::Utf8, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(StringArray::from(vec![Some("us"), None])),
+            Arc::new(Int64Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec!["a", "b"])),
+        ],
+    )
+    .unwrap();
+
+    // explicit key columns
+    let key = MessageKey::new(
+        "key".to_string(),
+        &["region".to_string(), "id".to_string()],
+        &ArroyoSchema::new_unkeyed(schema.clone(), 0),
+    )
+    .unwrap();
+    assert_eq!(
+        key.properties(&batch).unwrap(),
+        vec![
+            ("key".to_string(), "us:1".to_string()),
+            ("key".to_string(), ":2".to_string())
+        ]
+    );
+
+    // the input's key columns
+    let key = MessageKey::new(
+        "key".to_string(),
+        &[],
+        &ArroyoSchema::new_keyed(schema.clone(), 0, vec![1]),
+    )
+    .unwrap();
+    assert_eq!(
+        key.properties(&batch).unwrap(),
+        vec![
+            ("key".to_string(), "1".to_string()),
+            ("key".to_string(), "2".to_string())
+        ]
+    );
+
+    assert!(MessageKey::new(
+        "key".to_string(),
+        &[],
+        &ArroyoSchema::new_unkeyed(schema.clone(), 0)
+    )
+    .is_err());
+    assert!(MessageKey::new(
+        "key".to_string(),
+        &["missing".to_string()],
+        &ArroyoSchema::new_unkeyed(schema, 0)
+    )
+    .is_err());
+}
+
+#[test]
+fn test_deduplicator() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("value", DataType::Utf8, false),
+    ]));
+    let batch = |ids: Vec<&str>| {
+        let values: Vec<_> = ids.iter().map(|id| format!("value-{id}")).collect();
+        RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(ids)),
+                Arc::new(StringArray::from(values)),
+            ],
+        )
+        .unwrap()
+    };
+    // publishes the records whose keys haven't been published, as the sink does
+    let publish = |deduplicator: &mut Deduplicator, batch: &RecordBatch| {
+        let keys = deduplicator.keys(batch).unwrap();
+        keys.iter()
+            .map(|key| {
+                let published = !deduplicator.is_published(key.as_ref());
+                if published {
+                    deduplicator.record(key.as_ref());
+                }
+                published
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let mut deduplicator = Deduplicator::new(vec!["id".to_string()]);
+
+    // keys are only remembered once they've been published
+    let keys = deduplicator.keys(&batch(vec!["1"])).unwrap();
+    assert!(!deduplicator.is_published(keys.row(0).as_ref()));
+    assert_eq!(
+        publish(&mut deduplicator, &batch(vec!["1", "2", "1"])),
+        vec![true, true, false]
+    );
+
+    // "3" reaches the sink before the barrier for checkpoint 1, but after the sources'
+    // barriers, so it will be replayed if the pipeline is restored from checkpoint 1
+    assert_eq!(publish(&mut deduplicator, &batch(vec!["3"])), vec![true]);
+    let columns = deduplicator.checkpoint(1, timestamp).unwrap().unwrap();
+    assert!(deduplicator.checkpoint(1, timestamp).unwrap().is_none());
+
+    let state = RecordBatch::try_new(deduplicator.state_schema().schema, columns).unwrap();
+    assert_eq!(state.num_rows(), 3);
+
+    // restoring from checkpoint 1 suppresses the replayed record, but not records that hadn't
+    // been published before the failure
+    let mut restored = Deduplicator::new(vec!["id".to_string()]);
+    restored.restore(&state, 1).unwrap();
+    assert_eq!(
+        publish(&mut restored, &batch(vec!["3", "4"])),
+        vec![false, true]
+    );
+
+    // keys checkpointed in epochs before the restored one had already been committed
+    let mut restored = Deduplicator::new(vec!["id".to_string()]);
+    restored.restore(&state, 2).unwrap();
+    assert_eq!(publish(&mut restored, &batch(vec!["3"])), vec![true]);
+
+    // the keys are kept until their epoch's checkpoint commits
+    assert_eq!(publish(&mut deduplicator, &batch(vec!["4"])), vec![true]);
+    deduplicator.checkpoint(2, timestamp).unwrap().unwrap();
+    deduplicator.commit(1);
+    assert_eq!(
+        publish(&mut deduplicator, &batch(vec!["1", "4"])),
+        vec![true, false]
+    );
+    deduplicator.commit(2);
+    assert_eq!(publish(&mut deduplicator, &batch(vec!["4"])), vec![true]);
+
+    let deduplicator = Deduplicator::new(vec!["missing".to_string()]);
+    assert!(deduplicator.keys(&batch(vec!["1"])).is_err());
+}
+
+#[test]
+fn test_client_id() {
+    let mut config = MqttConfig {
+        client_prefix: Some("prefix".to_string()),
+        ..test_config("tcp://localhost:1883")
+    };
+
+    assert!(client_id(&config, 3).unwrap().starts_with("prefix_3"));
+
+    // persistent sessions need a stable client id
+    config.clean_session = Some(false);
+    assert!(client_id(&config, 3).is_err());
+
+    config.client_id = Some("pipeline".to_string());
+    assert_eq!(client_id(&config, 3).unwrap(), "pipeline_3");
+    assert_eq!(client_id(&config, 4).unwrap(), "pipeline_4");
+
+    config.client_id = Some("".to_string());
+    assert!(client_id(&config, 3).is_err());
+}
+
+#[tokio::test]
+async fn test_eventloop_exits_on_shutdown() {
+    // a "broker" that accepts the connection but never responds, so the eventloop stays parked
+    // waiting for the CONNACK
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let (_socket, _) = listener.accept().await.unwrap();
+        std::future::pending::<()>().await;
+    });
+
+    let config = test_config(format!("tcp://127.0.0.1:{}", port));
+    let (_client, eventloop) = create_connection(&config, 0).unwrap();
+
+    let shutdown = CancellationToken::new();
+    let task = tokio::spawn(poll_eventloop(eventloop, shutdown.clone()));
+
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert!(!task.is_finished());
+
+    shutdown.cancel();
+    tokio::time::timeout(std::time::Duration::from_millis(500), task)
+        .await
+        .expect("eventloop task should exit promptly once shut down")
+        .unwrap();
 }
@@ -92,6 +92,12 @@ impl MqttSourceFunc {
         self.subscribed.clone()
     }
 
+    /// Retained messages are replayed by the broker when we subscribe and may be arbitrarily
+    /// old; if they're kept they are timestamped with ingestion time like every other message
+    fn skip_message(&self, retained: bool) -> bool {
+        retained && self.config.skip_retained.unwrap_or(false)
+    }
+
     async fn run_int(
         &mut self,
         ctx: &mut SourceContext,
@@ -148,6 +154,10 @@ impl MqttSourceFunc {
                 event = eventloop.poll() => {
                     match event {
                         Ok(MqttEvent::Incoming(Incoming::Publish(p))) => {
+                            if self.skip_message(p.retain) {
+                                continue;
+                            }
+
                             let topic = String::from_utf8_lossy(&p.topic).to_string();
 
                             let connector_metadata = if !self.metadata_fields.is_empty() {
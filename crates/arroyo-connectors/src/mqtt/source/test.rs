@@ -3,7 +3,7 @@ use std::collections::VecDeque;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use crate::mqtt::{create_connection, MqttConfig, Tls};
+use crate::mqtt::{create_connection, test_config, MqttConfig, Tls};
 use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
 use arroyo_operator::context::{
     batch_bounded, ArrowCollector, BatchReceiver, OperatorContext, SourceCollector, SourceContext,
@@ -46,28 +46,30 @@ impl MqttSourceWithReads {
     }
 
     async fn assert_next_message_record_value(&mut self, mut expected_values: VecDeque<u64>) {
-        match self.data_recv.recv().await {
-            Some(item) => {
-                if let ArrowMessage::Data(record) = item {
-                    let a = record.columns()[1]
-                        .as_any()
-                        .downcast_ref::<UInt64Array>()
-                        .unwrap();
-
-                    for v in a {
-                        assert_eq!(
-                            expected_values
-                                .pop_front()
-                                .expect("found more elements than expected"),
-                            v.unwrap()
-                        );
+        while !expected_values.is_empty() {
+            match self.data_recv.recv().await {
+                Some(item) => {
+                    if let ArrowMessage::Data(record) = item {
+                        let a = record.columns()[1]
+                            .as_any()
+                            .downcast_ref::<UInt64Array>()
+                            .unwrap();
+
+                        for v in a {
+                            assert_eq!(
+                                expected_values
+                                    .pop_front()
+                                    .expect("found more elements than expected"),
+                                v.unwrap()
+                            );
+                        }
+                    } else {
+                        unreachable!("expected data, got {:?}", item);
                     }
-                } else {
-                    unreachable!("expected data, got {:?}", item);
                 }
-            }
-            None => {
-                unreachable!("option shouldn't be missing")
+                None => {
+                    unreachable!("option shouldn't be missing")
+                }
             }
         }
     }
@@ -81,13 +83,14 @@ pub struct MqttTopicTester {
     key: Option<String>,
     username: Option<String>,
     password: Option<String>,
+    skip_retained: bool,
 }
 
 impl MqttTopicTester {
     fn get_config(&self) -> MqttConfig {
         MqttConfig {
-            url: format!("tcp://localhost:{}", self.port),
             client_prefix: Some("test".to_string()),
+            skip_retained: Some(self.skip_retained),
             username: self.username.as_ref().map(|u| VarStr::new(u.clone())),
             password: self.password.as_ref().map(|p| VarStr::new(p.clone())),
             tls: Some(Tls {
@@ -95,6 +98,7 @@ impl MqttTopicTester {
                 cert: self.cert.as_ref().map(|ca| VarStr::new(ca.clone())),
                 key: self.key.as_ref().map(|ca| VarStr::new(ca.clone())),
             }),
+            ..test_config(format!("tcp://localhost:{}", self.port))
         }
     }
 
@@ -202,6 +206,7 @@ async fn test_mqtt() {
         key: None,
         username: None,
         password: None,
+        skip_retained: false,
     };
 
     let mut task_info = arroyo_types::get_test_task_info();
@@ -242,3 +247,79 @@ async fn test_mqtt() {
         .await
         .unwrap();
 }
+
+async fn run_retained_test(skip_retained: bool, expected: Vec<u64>) {
+    let mqtt_tester = MqttTopicTester {
+        topic: format!("mqtt-arroyo-retained-test-{}", random::<u64>()),
+        port: 1883,
+        ca: None,
+        cert: None,
+        key: None,
+        username: None,
+        password: None,
+        skip_retained,
+    };
+
+    let client = mqtt_tester.get_client().await;
+
+    // published before the source subscribes, so it's only seen as a retained message
+    client
+        .publish(
+            &mqtt_tester.topic,
+            QoS::AtLeastOnce,
+            true,
+            serde_json::to_vec(&TestData { value: 100 }).unwrap(),
+        )
+        .await
+        .expect("Failed to publish message");
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    let mut task_info = arroyo_types::get_test_task_info();
+    task_info.job_id = format!("mqtt-job-{}", random::<u64>());
+
+    let mut reader = mqtt_tester.get_source_with_reader(task_info).await;
+
+    reader
+        .wait_for_subscription(std::time::Duration::from_secs(5))
+        .await;
+
+    for message in 1u64..4 {
+        client
+            .publish(
+                &mqtt_tester.topic,
+                QoS::AtLeastOnce,
+                false,
+                serde_json::to_vec(&TestData { value: message }).unwrap(),
+            )
+            .await
+            .expect("Failed to publish message");
+    }
+
+    reader
+        .assert_next_message_record_value(expected.into())
+        .await;
+
+    // clear the retained message from the broker
+    client
+        .publish(&mqtt_tester.topic, QoS::AtLeastOnce, true, vec![])
+        .await
+        .expect("Failed to publish message");
+
+    reader
+        .to_control_tx
+        .send(ControlMessage::Stop {
+            mode: arroyo_rpc::grpc::rpc::StopMode::Graceful,
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_mqtt_skip_retained() {
+    run_retained_test(true, vec![1, 2, 3]).await;
+}
+
+#[tokio::test]
+async fn test_mqtt_keep_retained() {
+    run_retained_test(false, vec![100, 1, 2, 3]).await;
+}
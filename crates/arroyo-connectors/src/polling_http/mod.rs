@@ -222,6 +222,7 @@ impl Connector for PollingHTTPConnector {
             bad_data: schema.bad_data.clone(),
             framing: schema.framing.clone(),
             metadata_fields: vec![],
+            checkpoint_alignment: Default::default(),
         };
 
         Ok(Connection::new(
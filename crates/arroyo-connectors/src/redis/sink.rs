@@ -246,13 +246,8 @@ impl ArrowOperator for RedisSinkFunc {
                     ctx.in_schemas
                         .first()
                         .expect("no in-schema for redis sink!")
-                        .schema
-                        .index_of(key)
-                        .unwrap_or_else(|_| {
-                            panic!(
-                                "key column ({key}) does not exist in input schema for redis sink"
-                            )
-                        }),
+                        .field_index(key)
+                        .unwrap_or_else(|e| panic!("invalid key column for redis sink: {e}")),
                 );
             }
             _ => {}
@@ -262,10 +257,13 @@ impl ArrowOperator for RedisSinkFunc {
             hash_field_column, ..
         } = &self.target
         {
-            self.hash_index = Some(ctx.in_schemas.first().expect("no in-schema for redis sink!")
-                .schema
-                .index_of(hash_field_column)
-                .unwrap_or_else(|_| panic!("hash field column ({hash_field_column}) does not exist in input schema for redis sink")));
+            self.hash_index = Some(
+                ctx.in_schemas
+                    .first()
+                    .expect("no in-schema for redis sink!")
+                    .field_index(hash_field_column)
+                    .unwrap_or_else(|e| panic!("invalid hash field column for redis sink: {e}")),
+            );
         }
 
         let mut attempts = 0;
@@ -103,6 +103,7 @@ impl Connector for SingleFileConnector {
             bad_data: schema.bad_data.clone(),
             framing: schema.framing.clone(),
             metadata_fields: vec![],
+            checkpoint_alignment: Default::default(),
         };
 
         Ok(Connection::new(
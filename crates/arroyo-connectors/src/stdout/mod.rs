@@ -9,7 +9,9 @@ use arroyo_operator::connector::Connection;
 use arroyo_rpc::api_types::connections::{
     ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
 };
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Sender;
+use typify::import_types;
 
 use crate::EmptyConfig;
 
@@ -21,10 +23,13 @@ use arroyo_rpc::formats::{Format, JsonFormat};
 pub struct StdoutConnector {}
 
 const ICON: &str = include_str!("./stdout.svg");
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(schema = "src/stdout/table.json");
 
 impl Connector for StdoutConnector {
     type ProfileT = EmptyConfig;
-    type TableT = EmptyConfig;
+    type TableT = StdoutTable;
     fn name(&self) -> &'static str {
         "stdout"
     }
@@ -42,7 +47,7 @@ impl Connector for StdoutConnector {
             hidden: false,
             custom_schemas: true,
             connection_config: None,
-            table_config: "{}".to_string(),
+            table_config: TABLE_SCHEMA.to_string(),
         }
     }
 
@@ -71,11 +76,12 @@ impl Connector for StdoutConnector {
     fn from_options(
         &self,
         name: &str,
-        _options: &mut ConnectorOptions,
+        options: &mut ConnectorOptions,
         schema: Option<&ConnectionSchema>,
         _profile: Option<&ConnectionProfile>,
     ) -> anyhow::Result<Connection> {
-        self.from_config(None, name, EmptyConfig {}, EmptyConfig {}, schema)
+        let max_rows = options.pull_opt_u64("max_rows")?;
+        self.from_config(None, name, EmptyConfig {}, StdoutTable { max_rows }, schema)
     }
 
     fn from_config(
@@ -106,6 +112,7 @@ impl Connector for StdoutConnector {
             bad_data: schema.bad_data.clone(),
             framing: schema.framing.clone(),
             metadata_fields: vec![],
+            checkpoint_alignment: Default::default(),
         };
 
         Ok(Connection::new(
@@ -122,15 +129,18 @@ impl Connector for StdoutConnector {
     fn make_operator(
         &self,
         _: Self::ProfileT,
-        _: Self::TableT,
+        table: Self::TableT,
         c: OperatorConfig,
     ) -> anyhow::Result<ConstructedOperator> {
         let format = c
             .format
             .unwrap_or_else(|| Format::Json(JsonFormat::default()));
-        Ok(ConstructedOperator::from_operator(Box::new(StdoutSink {
-            stdout: BufWriter::new(tokio::io::stdout()),
-            serializer: ArrowSerializer::new(format),
-        })))
+        Ok(ConstructedOperator::from_operator(Box::new(
+            StdoutSink::new(
+                BufWriter::new(tokio::io::stdout()),
+                ArrowSerializer::new(format),
+                table.max_rows,
+            ),
+        )))
     }
 }
@@ -1,18 +1,53 @@
 use arrow::array::RecordBatch;
 use arroyo_formats::ser::ArrowSerializer;
-use tokio::io::{AsyncWriteExt, BufWriter, Stdout};
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter, Stdout};
+use tracing::info;
 
 use arroyo_operator::context::{Collector, OperatorContext};
 use arroyo_operator::operator::ArrowOperator;
 use arroyo_types::SignalMessage;
 
-pub struct StdoutSink {
-    pub stdout: BufWriter<Stdout>,
+pub struct StdoutSink<W = Stdout> {
+    pub stdout: BufWriter<W>,
     pub serializer: ArrowSerializer,
+    max_rows: Option<u64>,
+    rows_written: u64,
+}
+
+impl<W: AsyncWrite + Unpin + Send> StdoutSink<W> {
+    pub fn new(stdout: BufWriter<W>, serializer: ArrowSerializer, max_rows: Option<u64>) -> Self {
+        Self {
+            stdout,
+            serializer,
+            max_rows,
+            rows_written: 0,
+        }
+    }
+
+    async fn write_batch(&mut self, batch: &RecordBatch) -> std::io::Result<()> {
+        for value in self.serializer.serialize(batch) {
+            if self.max_rows.is_some_and(|max| self.rows_written >= max) {
+                break;
+            }
+
+            self.stdout.write_all(&value).await?;
+            self.stdout.write_u8(b'\n').await?;
+            self.rows_written += 1;
+
+            // logged as soon as the limit is reached, even if the batch ends with this row
+            if self.max_rows == Some(self.rows_written) {
+                info!(
+                    "stdout sink reached its limit of {} rows; discarding further output",
+                    self.rows_written
+                );
+            }
+        }
+        self.stdout.flush().await
+    }
 }
 
 #[async_trait::async_trait]
-impl ArrowOperator for StdoutSink {
+impl<W: AsyncWrite + Unpin + Send + 'static> ArrowOperator for StdoutSink<W> {
     fn name(&self) -> String {
         "Stdout".to_string()
     }
@@ -23,11 +58,11 @@ impl ArrowOperator for StdoutSink {
         _: &mut OperatorContext,
         _: &mut dyn Collector,
     ) {
-        for value in self.serializer.serialize(&batch) {
-            self.stdout.write_all(&value).await.unwrap();
-            self.stdout.write_u8(b'\n').await.unwrap();
+        if self.max_rows.is_some_and(|max| self.rows_written >= max) {
+            return;
         }
-        self.stdout.flush().await.unwrap();
+
+        self.write_batch(&batch).await.unwrap();
     }
 
     async fn on_close(
@@ -39,3 +74,67 @@ impl ArrowOperator for StdoutSink {
         self.stdout.flush().await.unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arroyo_rpc::formats::{Format, JsonFormat};
+    use std::sync::Arc;
+
+    fn batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn sink(max_rows: Option<u64>) -> StdoutSink<Vec<u8>> {
+        StdoutSink::new(
+            BufWriter::new(vec![]),
+            ArrowSerializer::new(Format::Json(JsonFormat::default())),
+            max_rows,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_writes_serialized_rows() {
+        let mut sink = sink(None);
+        sink.write_batch(&batch()).await.unwrap();
+
+        let output = String::from_utf8(sink.stdout.into_inner()).unwrap();
+        let rows: Vec<serde_json::Value> = output
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        assert_eq!(
+            rows,
+            vec![
+                serde_json::json!({"id": 1, "name": "a"}),
+                serde_json::json!({"id": 2, "name": "b"}),
+                serde_json::json!({"id": 3, "name": "c"}),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_rows() {
+        let mut sink = sink(Some(4));
+        sink.write_batch(&batch()).await.unwrap();
+        sink.write_batch(&batch()).await.unwrap();
+
+        let output = String::from_utf8(sink.stdout.into_inner()).unwrap();
+        assert_eq!(output.lines().count(), 4);
+    }
+}
@@ -7,9 +7,11 @@ use arrow_schema::DataType;
 use arroyo_rpc::api_types::pipelines::{PipelineEdge, PipelineGraph, PipelineNode};
 use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::grpc::api;
-use arroyo_rpc::grpc::api::{ArrowProgram, ArrowProgramConfig, ConnectorOp, EdgeType};
+use arroyo_rpc::grpc::api::{
+    ArrowProgram, ArrowProgramConfig, CheckpointAlignment, ConnectorOp, EdgeType,
+};
 use petgraph::dot::Dot;
-use petgraph::graph::DiGraph;
+use petgraph::graph::{DiGraph, NodeIndex};
 use petgraph::prelude::EdgeRef;
 use petgraph::Direction;
 use prost::Message;
@@ -17,8 +19,8 @@ use rand::distributions::Alphanumeric;
 use rand::prelude::SmallRng;
 use rand::{Rng, SeedableRng};
 use std::collections::hash_map::DefaultHasher;
-use std::collections::{HashMap, HashSet};
-use std::fmt::{Debug, Display, Formatter};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter, Write};
 use std::hash::Hasher;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -34,6 +36,7 @@ pub enum OperatorName {
     InstantJoin,
     LookupJoin,
     WindowFunction,
+    TopN,
     TumblingWindowAggregate,
     SlidingWindowAggregate,
     SessionWindowAggregate,
@@ -61,6 +64,17 @@ impl Display for LogicalEdgeType {
     }
 }
 
+impl LogicalEdgeType {
+    fn name(&self) -> &'static str {
+        match self {
+            LogicalEdgeType::Forward => "forward",
+            LogicalEdgeType::Shuffle => "shuffle",
+            LogicalEdgeType::LeftJoin => "left join",
+            LogicalEdgeType::RightJoin => "right join",
+        }
+    }
+}
+
 impl From<arroyo_rpc::grpc::api::EdgeType> for LogicalEdgeType {
     fn from(value: EdgeType) -> Self {
         match value {
@@ -105,6 +119,7 @@ impl TryFrom<LogicalProgram> for PipelineGraph {
                 },
                 description: node.description.clone(),
                 parallelism: node.parallelism as u32,
+                metadata: node.metadata.clone(),
             }))
             .collect();
 
@@ -221,6 +236,9 @@ pub struct LogicalNode {
     pub description: String,
     pub operator_chain: OperatorChain,
     pub parallelism: usize,
+    /// Display-only details about the node (like its output schema and window parameters)
+    /// for rendering the plan; not used for execution
+    pub metadata: BTreeMap<String, String>,
 }
 
 impl LogicalNode {
@@ -244,8 +262,14 @@ impl LogicalNode {
                 edges: vec![],
             },
             parallelism,
+            metadata: BTreeMap::new(),
         }
     }
+
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
 }
 
 impl Display for LogicalNode {
@@ -293,6 +317,7 @@ pub struct PythonUdfConfig {
 pub struct ProgramConfig {
     pub udf_dylibs: HashMap<String, DylibUdfConfig>,
     pub python_udfs: HashMap<String, PythonUdfConfig>,
+    pub checkpoint_alignment: CheckpointAlignment,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -325,6 +350,70 @@ impl LogicalProgram {
         format!("{:?}", Dot::with_config(&self.graph, &[]))
     }
 
+    /// Renders the graph as an indented tree in the style of `EXPLAIN`: each sink is followed
+    /// by its inputs, which are labeled with the type of the edge that connects them. Nodes that
+    /// feed multiple outputs are only expanded the first time they appear.
+    pub fn explain(&self) -> String {
+        let mut out = String::new();
+        let mut visited = HashSet::new();
+
+        let mut sinks: Vec<_> = self.graph.externals(Direction::Outgoing).collect();
+        sinks.sort_by_key(|idx| self.graph[*idx].node_id);
+        for sink in sinks {
+            self.explain_node(sink, None, 0, &mut visited, &mut out);
+        }
+
+        out
+    }
+
+    fn explain_node(
+        &self,
+        idx: NodeIndex,
+        edge_type: Option<LogicalEdgeType>,
+        depth: usize,
+        visited: &mut HashSet<NodeIndex>,
+        out: &mut String,
+    ) {
+        let node = &self.graph[idx];
+        let indent = "  ".repeat(depth);
+        let edge = edge_type
+            .map(|t| format!("<-[{}]- ", t.name()))
+            .unwrap_or_default();
+
+        if !visited.insert(idx) {
+            writeln!(out, "{indent}{edge}node {} (see above)", node.node_id).unwrap();
+            return;
+        }
+
+        let operators = node
+            .operator_chain
+            .operators
+            .iter()
+            .map(|op| op.operator_name.to_string())
+            .join(" -> ");
+        writeln!(
+            out,
+            "{indent}{edge}{operators} (node {}, parallelism {}): {}",
+            node.node_id, node.parallelism, node.description
+        )
+        .unwrap();
+
+        let mut inputs: Vec<_> = self
+            .graph
+            .edges_directed(idx, Direction::Incoming)
+            .collect();
+        inputs.sort_by_key(|e| self.graph[e.source()].node_id);
+        for e in inputs {
+            self.explain_node(
+                e.source(),
+                Some(e.weight().edge_type),
+                depth + 1,
+                visited,
+                out,
+            );
+        }
+    }
+
     pub fn task_count(&self) -> usize {
         // TODO: this can be cached
         self.graph.node_weights().map(|nw| nw.parallelism).sum()
@@ -385,6 +474,7 @@ impl LogicalProgram {
                     OperatorName::Join => "join-with-expiration".to_string(),
                     OperatorName::InstantJoin => "windowed-join".to_string(),
                     OperatorName::WindowFunction => "sql-window-function".to_string(),
+                    OperatorName::TopN => "sql-top-n".to_string(),
                     OperatorName::LookupJoin => "lookup-join".to_string(),
                     OperatorName::TumblingWindowAggregate => {
                         "sql-tumbling-window-aggregate".to_string()
@@ -450,6 +540,7 @@ impl TryFrom<ArrowProgram> for LogicalProgram {
                             .collect::<anyhow::Result<Vec<_>>>()?,
                     },
                     parallelism: node.parallelism as usize,
+                    metadata: node.metadata,
                 }),
             );
         }
@@ -474,6 +565,7 @@ impl TryFrom<ArrowProgram> for LogicalProgram {
             .unwrap_or_else(|| ArrowProgramConfig {
                 udf_dylibs: HashMap::new(),
                 python_udfs: HashMap::new(),
+                checkpoint_alignment: CheckpointAlignment::Aligned.into(),
             })
             .into();
 
@@ -584,12 +676,14 @@ impl From<ProgramConfig> for ArrowProgramConfig {
                 .into_iter()
                 .map(|(k, v)| (k, v.into()))
                 .collect(),
+            checkpoint_alignment: from.checkpoint_alignment.into(),
         }
     }
 }
 
 impl From<ArrowProgramConfig> for ProgramConfig {
     fn from(from: ArrowProgramConfig) -> Self {
+        let checkpoint_alignment = from.checkpoint_alignment();
         ProgramConfig {
             udf_dylibs: from
                 .udf_dylibs
@@ -601,6 +695,7 @@ impl From<ArrowProgramConfig> for ProgramConfig {
                 .into_iter()
                 .map(|(k, v)| (k, v.into()))
                 .collect(),
+            checkpoint_alignment,
         }
     }
 }
@@ -633,6 +728,7 @@ impl From<LogicalProgram> for ArrowProgram {
                         .iter()
                         .map(|edge| (**edge).clone().into())
                         .collect(),
+                    metadata: node.metadata.clone(),
                 }
             })
             .collect();
@@ -82,6 +82,7 @@ impl Optimizer for ChainingOptimizer {
             let mut new_cur = cur.clone();
 
             new_cur.description = format!("{} -> {}", cur.description, successor_node.description);
+            new_cur.metadata.extend(successor_node.metadata.clone());
 
             new_cur
                 .operator_chain
@@ -3,12 +3,13 @@ use crate::proto::schema::get_pool;
 use crate::{proto, should_flush};
 use arrow::array::{Int32Builder, Int64Builder};
 use arrow::compute::kernels;
+use arrow::ipc::reader::{FileReader, StreamReader};
 use arrow_array::builder::{
     make_builder, ArrayBuilder, BinaryBuilder, GenericByteBuilder, StringBuilder,
     TimestampNanosecondBuilder, UInt64Builder,
 };
 use arrow_array::types::GenericBinaryType;
-use arrow_array::{ArrayRef, BooleanArray, RecordBatch};
+use arrow_array::{new_null_array, ArrayRef, BooleanArray, RecordBatch};
 use arrow_schema::{DataType, Schema, SchemaRef};
 use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::formats::{
@@ -20,6 +21,7 @@ use arroyo_types::{to_nanos, SourceError, LOOKUP_KEY_INDEX_FIELD};
 use prost_reflect::DescriptorPool;
 use serde_json::Value;
 use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
 use std::sync::Arc;
 use std::time::{Instant, SystemTime};
 use tokio::sync::Mutex;
@@ -123,6 +125,13 @@ enum BufferDecoder {
         buffered_count: usize,
         buffered_since: Instant,
     },
+    /// Holds already-decoded columns (one vec per decoded batch), for formats like Arrow IPC
+    /// that produce whole batches rather than individual rows
+    Arrays {
+        columns: Vec<Vec<ArrayRef>>,
+        buffered_count: usize,
+        buffered_since: Instant,
+    },
 }
 
 impl BufferDecoder {
@@ -133,6 +142,11 @@ impl BufferDecoder {
                 buffered_count,
                 buffered_since,
                 ..
+            }
+            | BufferDecoder::Arrays {
+                buffered_count,
+                buffered_since,
+                ..
             } => should_flush(*buffered_count, *buffered_since),
         }
     }
@@ -177,9 +191,73 @@ impl BufferDecoder {
                         .map(|(batch, mask, _)| (batch.columns().to_vec(), Some(mask))),
                 })
             }
+            BufferDecoder::Arrays {
+                columns,
+                buffered_count,
+                buffered_since,
+            } => {
+                if *buffered_count == 0 {
+                    return None;
+                }
+
+                *buffered_since = Instant::now();
+                *buffered_count = 0;
+                let batches = std::mem::take(columns);
+                let num_columns = batches.first().map(|b| b.len()).unwrap_or_default();
+                Some(
+                    (0..num_columns)
+                        .map(|i| {
+                            let arrays: Vec<_> = batches.iter().map(|b| b[i].as_ref()).collect();
+                            kernels::concat::concat(&arrays)
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                        .map(|arrays| (arrays, None))
+                        .map_err(|e| {
+                            SourceError::bad_data(format!("failed to combine Arrow data: {:?}", e))
+                        }),
+                )
+            }
         }
     }
 
+    fn decode_arrow(&mut self, schema: &Schema, batch: &RecordBatch) -> Result<(), SourceError> {
+        let BufferDecoder::Arrays {
+            columns,
+            buffered_count,
+            ..
+        } = self
+        else {
+            unreachable!("Tried to decode Arrow data for non-Arrow deserializer");
+        };
+
+        let arrays = schema
+            .fields
+            .iter()
+            .map(|f| match batch.column_by_name(f.name()) {
+                Some(c) if c.data_type() == f.data_type() => Ok(c.clone()),
+                Some(c) => kernels::cast::cast(c, f.data_type()).map_err(|e| {
+                    SourceError::bad_data(format!(
+                        "field '{}' has type {}, which cannot be converted to {}: {:?}",
+                        f.name(),
+                        c.data_type(),
+                        f.data_type(),
+                        e
+                    ))
+                }),
+                None if f.is_nullable() => Ok(new_null_array(f.data_type(), batch.num_rows())),
+                None => Err(SourceError::bad_data(format!(
+                    "Arrow data is missing required field '{}'",
+                    f.name()
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        columns.push(arrays);
+        *buffered_count += batch.num_rows();
+
+        Ok(())
+    }
+
     fn decode_json(&mut self, msg: &[u8]) -> Result<(), SourceError> {
         match self {
             BufferDecoder::Buffer(_) => {
@@ -204,8 +282,8 @@ impl BufferDecoder {
     fn get_buffer(&mut self) -> &mut ContextBuffer {
         match self {
             BufferDecoder::Buffer(buffer) => buffer,
-            BufferDecoder::JsonDecoder { .. } => {
-                panic!("tried to get a raw buffer from a JSON deserializer");
+            BufferDecoder::JsonDecoder { .. } | BufferDecoder::Arrays { .. } => {
+                panic!("tried to get a raw buffer from a non-raw deserializer");
             }
         }
     }
@@ -240,6 +318,21 @@ impl BufferDecoder {
             } => {
                 decoder.decode("{}".as_bytes()).unwrap();
 
+                *buffered_count += 1;
+            }
+            BufferDecoder::Arrays {
+                columns,
+                buffered_count,
+                ..
+            } => {
+                columns.push(
+                    schema
+                        .fields
+                        .iter()
+                        .map(|f| new_null_array(f.data_type(), 1))
+                        .collect(),
+                );
+
                 *buffered_count += 1;
             }
         }
@@ -379,6 +472,11 @@ impl ArrowDeserializer {
                 buffered_count: 0,
                 buffered_since: Instant::now(),
             },
+            Format::ArrowIpc(_) => BufferDecoder::Arrays {
+                columns: vec![],
+                buffered_count: 0,
+                buffered_since: Instant::now(),
+            },
             _ => BufferDecoder::Buffer(ContextBuffer::new(schema_without_additional.clone())),
         };
 
@@ -435,6 +533,7 @@ impl ArrowDeserializer {
     ) -> Vec<SourceError> {
         let (count, errors) = match &*self.format {
             Format::Avro(_) => self.deserialize_slice_avro(msg).await,
+            Format::ArrowIpc(_) => self.deserialize_slice_arrow_ipc(msg),
             _ => {
                 let mut count = 0;
                 let errors = FramingIterator::new(self.framing.clone(), msg)
@@ -576,6 +675,7 @@ impl ArrowDeserializer {
                 }
             }
             Format::Avro(_) => unreachable!("this should not be called for avro"),
+            Format::ArrowIpc(_) => unreachable!("this should not be called for arrow ipc"),
             Format::Parquet(_) => todo!("parquet is not supported as an input format"),
         }
 
@@ -646,6 +746,52 @@ impl ArrowDeserializer {
         (count, errors)
     }
 
+    /// Arrow IPC messages are self-describing and may contain many rows, so framing is not
+    /// applied and the returned count is the number of rows decoded
+    fn deserialize_slice_arrow_ipc(&mut self, msg: &[u8]) -> (usize, Vec<SourceError>) {
+        let Format::ArrowIpc(format) = &*self.format else {
+            unreachable!("not arrow ipc");
+        };
+
+        let batches: Result<Vec<RecordBatch>, _> = if format.file {
+            FileReader::try_new(Cursor::new(msg), None).and_then(|reader| reader.collect())
+        } else {
+            StreamReader::try_new(msg, None).and_then(|reader| reader.collect())
+        };
+
+        let batches = match batches {
+            Ok(batches) => batches,
+            Err(e) => {
+                return (
+                    0,
+                    vec![SourceError::bad_data(format!(
+                        "failed to read Arrow IPC message: {:?}",
+                        e
+                    ))],
+                );
+            }
+        };
+
+        let mut count = 0;
+        let errors = batches
+            .iter()
+            .filter_map(|batch| {
+                match self
+                    .buffer_decoder
+                    .decode_arrow(&self.decoder_schema, batch)
+                {
+                    Ok(()) => {
+                        count += batch.num_rows();
+                        None
+                    }
+                    Err(e) => Some(e),
+                }
+            })
+            .collect();
+
+        (count, errors)
+    }
+
     fn deserialize_raw_string(&mut self, msg: &[u8]) {
         let (col, _) = self
             .decoder_schema
@@ -726,14 +872,16 @@ fn add_additional_fields(
 #[cfg(test)]
 mod tests {
     use crate::de::{ArrowDeserializer, FieldValueType, FramingIterator};
+    use crate::ser::ArrowSerializer;
     use arrow::datatypes::Int32Type;
     use arrow_array::cast::AsArray;
     use arrow_array::types::{GenericBinaryType, Int64Type, TimestampNanosecondType};
+    use arrow_array::{Int64Array, RecordBatch, StringArray, TimestampNanosecondArray};
     use arrow_schema::{DataType, Schema, TimeUnit};
     use arroyo_rpc::df::ArroyoSchema;
     use arroyo_rpc::formats::{
-        BadData, Format, Framing, FramingMethod, JsonFormat, NewlineDelimitedFraming,
-        RawBytesFormat,
+        ArrowIpcFormat, BadData, Format, Framing, FramingMethod, JsonFormat,
+        NewlineDelimitedFraming, RawBytesFormat,
     };
     use arroyo_rpc::MetadataField;
     use arroyo_types::{to_nanos, SourceError};
@@ -943,6 +1091,58 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_arrow_ipc_round_trip() {
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("x", arrow_schema::DataType::Int64, true),
+            arrow_schema::Field::new("y", arrow_schema::DataType::Utf8, true),
+            arrow_schema::Field::new(
+                "_timestamp",
+                arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+
+        let input = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![Some(1), None, Some(3)])),
+                Arc::new(StringArray::from(vec![Some("a"), Some("b"), None])),
+                Arc::new(TimestampNanosecondArray::from(vec![0, 1, 2])),
+            ],
+        )
+        .unwrap();
+
+        for file in [false, true] {
+            let format = Format::ArrowIpc(ArrowIpcFormat { file });
+            let messages: Vec<_> = ArrowSerializer::new(format.clone())
+                .serialize(&input)
+                .collect();
+            assert_eq!(messages.len(), 1);
+
+            let arroyo_schema =
+                Arc::new(ArroyoSchema::from_schema_unkeyed(schema.clone()).unwrap());
+            let mut deserializer =
+                ArrowDeserializer::new(format, arroyo_schema, &[], None, BadData::Fail {});
+
+            let time = SystemTime::now();
+            for message in &messages {
+                let result = deserializer.deserialize_slice(message, time, None).await;
+                assert!(result.is_empty());
+            }
+
+            let batch = deserializer.flush_buffer().unwrap().unwrap();
+            assert_eq!(batch.num_rows(), 3);
+            assert_eq!(batch.column(0), input.column(0));
+            assert_eq!(batch.column(1), input.column(1));
+            assert!(batch
+                .column(2)
+                .as_primitive::<TimestampNanosecondType>()
+                .iter()
+                .all(|t| t == Some(to_nanos(time) as i64)));
+        }
+    }
+
     #[tokio::test]
     async fn test_additional_fields_deserialization() {
         let schema = Arc::new(Schema::new(vec![
@@ -1,7 +1,10 @@
 use anyhow::{anyhow, bail};
 use arrow_schema::{DataType, Field, TimeUnit};
+use arroyo_rpc::api_types::connections::{ConnectionSchema, SchemaDefinition, SourceField};
+use arroyo_rpc::formats::{Format, JsonFormat};
 use arroyo_types::ArroyoExtensionType;
 use schemars::schema::{RootSchema, Schema};
+use serde_json::{Map, Value};
 use std::sync::Arc;
 use tracing::warn;
 use typify::{Type, TypeDetails, TypeSpace, TypeSpaceSettings};
@@ -60,6 +63,203 @@ pub fn to_arrow(name: &str, schema: &str) -> anyhow::Result<arrow_schema::Schema
     Ok(arrow_schema::Schema::new(fields))
 }
 
+/// Converts a json schema into connection fields; non-required properties become nullable,
+/// nested objects become structs, and arrays become lists
+pub fn to_source_fields(name: &str, schema: &str) -> anyhow::Result<Vec<SourceField>> {
+    to_arrow(name, schema)?
+        .fields
+        .into_iter()
+        .map(|f| (**f).clone().try_into())
+        .collect::<Result<_, String>>()
+        .map_err(|e| anyhow!("failed to convert json schema {}: {}", name, e))
+}
+
+/// Builds an inferred json [`ConnectionSchema`] from a json schema document
+pub fn infer_connection_schema(name: &str, schema: &str) -> anyhow::Result<ConnectionSchema> {
+    ConnectionSchema::try_new(
+        Some(Format::Json(JsonFormat::default())),
+        None,
+        None,
+        None,
+        to_source_fields(name, schema)?,
+        Some(SchemaDefinition::JsonSchema(schema.to_string())),
+        Some(true),
+        Default::default(),
+    )
+}
+
+/// Infers connection fields from a sample json object. As a single sample can't show whether a
+/// field is optional, every field is nullable; nulls, empty containers, and arrays with mixed
+/// element types become JSON fields
+pub fn infer_fields_from_sample(sample: &Map<String, Value>) -> anyhow::Result<Vec<SourceField>> {
+    sample
+        .iter()
+        .map(|(name, v)| sample_field(name, v).try_into())
+        .collect::<Result<_, String>>()
+        .map_err(|e| anyhow!("failed to infer fields from sample: {}", e))
+}
+
+fn sample_field(name: &str, v: &Value) -> Field {
+    let (data_type, extension) = sample_datatype(v);
+    ArroyoExtensionType::add_metadata(extension, Field::new(name, data_type, true))
+}
+
+fn sample_datatype(v: &Value) -> (DataType, Option<ArroyoExtensionType>) {
+    match v {
+        Value::Bool(_) => (DataType::Boolean, None),
+        Value::Number(n) if n.is_i64() => (DataType::Int64, None),
+        Value::Number(n) if n.is_u64() => (DataType::UInt64, None),
+        Value::Number(_) => (DataType::Float64, None),
+        Value::String(_) => (DataType::Utf8, None),
+        Value::Object(o) if !o.is_empty() => (
+            DataType::Struct(o.iter().map(|(k, v)| sample_field(k, v)).collect()),
+            None,
+        ),
+        Value::Array(items) => {
+            let mut types = items.iter().map(sample_datatype);
+            match types.next() {
+                Some(first) if types.all(|t| t == first) => {
+                    let (data_type, extension) = first;
+                    (
+                        DataType::List(Arc::new(ArroyoExtensionType::add_metadata(
+                            extension,
+                            Field::new("item", data_type, true),
+                        ))),
+                        None,
+                    )
+                }
+                _ => (DataType::Utf8, Some(ArroyoExtensionType::JSON)),
+            }
+        }
+        Value::Null | Value::Object(_) => (DataType::Utf8, Some(ArroyoExtensionType::JSON)),
+    }
+}
+
+/// Infers connection fields from several sample json objects, merging their types. Unlike
+/// [`infer_fields_from_sample`], a field is only nullable if it is null or missing in at least one
+/// sample; integers and floats widen to floats, and otherwise conflicting types become JSON fields
+pub fn infer_fields_from_samples(
+    samples: &[Map<String, Value>],
+) -> anyhow::Result<Vec<SourceField>> {
+    if samples.is_empty() {
+        bail!("at least one sample is required to infer a schema");
+    }
+
+    if samples.len() == 1 {
+        return infer_fields_from_sample(&samples[0]);
+    }
+
+    let fields = samples
+        .iter()
+        .map(strict_sample_fields)
+        .reduce(|a, b| merge_fields(&a, &b))
+        .unwrap();
+
+    fields
+        .iter()
+        .map(|f| resolve_nulls(f).try_into())
+        .collect::<Result<_, String>>()
+        .map_err(|e| anyhow!("failed to infer fields from samples: {}", e))
+}
+
+/// Like [`sample_field`], but only nullable if the value is null, which is typed as
+/// [`DataType::Null`] until it's merged with the other samples
+fn strict_sample_fields(sample: &Map<String, Value>) -> Vec<Field> {
+    sample
+        .iter()
+        .map(|(name, v)| strict_sample_field(name, v))
+        .collect()
+}
+
+fn strict_sample_field(name: &str, v: &Value) -> Field {
+    match v {
+        Value::Null => Field::new(name, DataType::Null, true),
+        Value::Object(o) if !o.is_empty() => Field::new(
+            name,
+            DataType::Struct(strict_sample_fields(o).into()),
+            false,
+        ),
+        Value::Array(items) if !items.is_empty() => {
+            let item = items
+                .iter()
+                .map(|v| strict_sample_field("item", v))
+                .reduce(|a, b| merge_field(&a, &b))
+                .unwrap();
+            Field::new(name, DataType::List(Arc::new(item)), false)
+        }
+        v => sample_field(name, v).with_nullable(false),
+    }
+}
+
+fn merge_fields(a: &[Field], b: &[Field]) -> Vec<Field> {
+    let mut merged: Vec<Field> = a
+        .iter()
+        .map(|fa| match b.iter().find(|fb| fb.name() == fa.name()) {
+            Some(fb) => merge_field(fa, fb),
+            None => fa.clone().with_nullable(true),
+        })
+        .collect();
+
+    merged.extend(
+        b.iter()
+            .filter(|fb| !a.iter().any(|fa| fa.name() == fb.name()))
+            .map(|fb| fb.clone().with_nullable(true)),
+    );
+
+    merged
+}
+
+fn merge_field(a: &Field, b: &Field) -> Field {
+    let nullable = a.is_nullable() || b.is_nullable();
+
+    let merged = match (a.data_type(), b.data_type()) {
+        (DataType::Null, _) => b.clone(),
+        (_, DataType::Null) => a.clone(),
+        _ if a.data_type() == b.data_type() && a.metadata() == b.metadata() => a.clone(),
+        (
+            DataType::Int64 | DataType::UInt64 | DataType::Float64,
+            DataType::Int64 | DataType::UInt64 | DataType::Float64,
+        ) => Field::new(a.name(), DataType::Float64, nullable),
+        (DataType::Struct(fa), DataType::Struct(fb)) => {
+            let fa: Vec<_> = fa.iter().map(|f| (**f).clone()).collect();
+            let fb: Vec<_> = fb.iter().map(|f| (**f).clone()).collect();
+            Field::new(
+                a.name(),
+                DataType::Struct(merge_fields(&fa, &fb).into()),
+                nullable,
+            )
+        }
+        (DataType::List(ia), DataType::List(ib)) => Field::new(
+            a.name(),
+            DataType::List(Arc::new(merge_field(ia, ib))),
+            nullable,
+        ),
+        _ => ArroyoExtensionType::add_metadata(
+            Some(ArroyoExtensionType::JSON),
+            Field::new(a.name(), DataType::Utf8, nullable),
+        ),
+    };
+
+    merged.with_nullable(nullable)
+}
+
+/// Fields that were null in every sample have no known type, so they become JSON fields
+fn resolve_nulls(f: &Field) -> Field {
+    match f.data_type() {
+        DataType::Null => ArroyoExtensionType::add_metadata(
+            Some(ArroyoExtensionType::JSON),
+            Field::new(f.name(), DataType::Utf8, true),
+        ),
+        DataType::Struct(fields) => f.clone().with_data_type(DataType::Struct(
+            fields.iter().map(|f| resolve_nulls(f)).collect(),
+        )),
+        DataType::List(item) => f
+            .clone()
+            .with_data_type(DataType::List(Arc::new(resolve_nulls(item)))),
+        _ => f.clone(),
+    }
+}
+
 fn to_arrow_datatype(
     type_space: &TypeSpace,
     t: &Type,
@@ -135,7 +335,8 @@ fn to_arrow_datatype(
 
 #[cfg(test)]
 mod test {
-    use super::to_arrow;
+    use super::{infer_connection_schema, to_arrow};
+    use arroyo_rpc::api_types::connections::{FieldType, PrimitiveType, SourceField};
 
     #[test]
     fn test() {
@@ -196,4 +397,84 @@ mod test {
 
         let _ = to_arrow("nexmark", json_schema).unwrap();
     }
+
+    fn field<'a>(fields: &'a [SourceField], name: &str) -> &'a SourceField {
+        fields.iter().find(|f| f.field_name == name).unwrap()
+    }
+
+    #[test]
+    fn test_infer_connection_schema() {
+        let json_schema = r##"
+{
+  "type": "object",
+  "required": ["id", "customer", "created_at"],
+  "properties": {
+    "id": { "type": "integer", "format": "int64" },
+    "created_at": { "type": "string", "format": "date-time" },
+    "note": { "type": "string" },
+    "customer": {
+      "type": "object",
+      "required": ["name"],
+      "properties": {
+        "name": { "type": "string" },
+        "address": {
+          "type": "object",
+          "properties": {
+            "city": { "type": "string" }
+          }
+        }
+      }
+    },
+    "tags": {
+      "type": "array",
+      "items": { "type": "string" }
+    }
+  }
+}"##;
+
+        let schema = infer_connection_schema("orders", json_schema).unwrap();
+        assert_eq!(schema.inferred, Some(true));
+
+        let id = field(&schema.fields, "id");
+        assert_eq!(
+            id.field_type.r#type,
+            FieldType::Primitive(PrimitiveType::Int64)
+        );
+        assert!(!id.nullable);
+
+        let created_at = field(&schema.fields, "created_at");
+        assert_eq!(
+            created_at.field_type.r#type,
+            FieldType::Primitive(PrimitiveType::UnixNanos)
+        );
+        assert!(!created_at.nullable);
+
+        assert!(field(&schema.fields, "note").nullable);
+
+        let customer = field(&schema.fields, "customer");
+        assert!(!customer.nullable);
+        let FieldType::Struct(customer) = &customer.field_type.r#type else {
+            panic!("expected struct, got {:?}", customer.field_type);
+        };
+        assert!(!field(&customer.fields, "name").nullable);
+
+        let address = field(&customer.fields, "address");
+        assert!(address.nullable);
+        let FieldType::Struct(address) = &address.field_type.r#type else {
+            panic!("expected struct, got {:?}", address.field_type);
+        };
+        assert_eq!(
+            field(&address.fields, "city").field_type.r#type,
+            FieldType::Primitive(PrimitiveType::String)
+        );
+
+        let tags = field(&schema.fields, "tags");
+        let FieldType::List(item) = &tags.field_type.r#type else {
+            panic!("expected list, got {:?}", tags.field_type);
+        };
+        assert_eq!(
+            item.field_type.r#type,
+            FieldType::Primitive(PrimitiveType::String)
+        );
+    }
 }
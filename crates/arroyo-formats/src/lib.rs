@@ -7,6 +7,7 @@ pub mod json;
 
 pub mod de;
 pub mod proto;
+pub mod schema;
 pub mod ser;
 
 pub fn should_flush(size: usize, time: Instant) -> bool {
@@ -1,4 +1,5 @@
 use crate::float_to_json;
+use crate::proto::schema::well_known_datatype;
 use anyhow::anyhow;
 use arroyo_rpc::formats::ProtobufFormat;
 use arroyo_types::SourceError;
@@ -56,7 +57,13 @@ fn proto_value_to_json(field: &FieldDescriptor, value: &Value) -> JsonValue {
             })
             .unwrap_or_default(),
         ),
-        Value::Message(m) => proto_to_json(m),
+        Value::Message(m) => {
+            if well_known_datatype(&m.descriptor()).is_some() {
+                well_known_to_json(m)
+            } else {
+                proto_to_json(m)
+            }
+        }
         Value::List(l) => {
             JsonValue::Array(l.iter().map(|v| proto_value_to_json(field, v)).collect())
         }
@@ -90,6 +97,25 @@ fn proto_value_to_json(field: &FieldDescriptor, value: &Value) -> JsonValue {
     }
 }
 
+/// Converts a `google.protobuf.Timestamp` or `google.protobuf.Duration` into nanoseconds, which the
+/// json decoder reads directly into the corresponding arrow column
+fn well_known_to_json(message: &DynamicMessage) -> JsonValue {
+    let seconds = message
+        .get_field_by_name("seconds")
+        .and_then(|v| v.as_i64())
+        .unwrap_or_default();
+    let nanos = message
+        .get_field_by_name("nanos")
+        .and_then(|v| v.as_i32())
+        .unwrap_or_default();
+
+    seconds
+        .checked_mul(1_000_000_000)
+        .and_then(|s| s.checked_add(nanos as i64))
+        .map(|n| JsonValue::Number(n.into()))
+        .unwrap_or(JsonValue::Null)
+}
+
 // see: https://docs.confluent.io/platform/current/schema-registry/fundamentals/serdes-develop/index.html#wire-format
 fn skip_confluent_header(msg: &mut &[u8]) -> anyhow::Result<()> {
     // skip magic byte + schema ID
@@ -1,5 +1,5 @@
 use anyhow::{anyhow, bail, Context};
-use arrow_schema::{DataType, Field, Schema};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
 use arroyo_types::ArroyoExtensionType;
 use prost_reflect::{Cardinality, DescriptorPool, FieldDescriptor, Kind, MessageDescriptor};
 use regex::Regex;
@@ -38,6 +38,8 @@ fn protobuf_to_arrow_datatype(
                 if field.is_map() {
                     // we don't currently support maps so treat maps as raw json
                     return (DataType::Utf8, Some(ArroyoExtensionType::JSON));
+                } else if let Some(dt) = well_known_datatype(&message) {
+                    dt
                 } else {
                     DataType::Struct(fields_for_message(&message).into())
                 }
@@ -48,6 +50,16 @@ fn protobuf_to_arrow_datatype(
     )
 }
 
+/// Returns the native arrow type for protobuf well-known types that we don't want to expose as
+/// plain structs; these are decoded into nanoseconds in `proto_to_json`
+pub(crate) fn well_known_datatype(message: &MessageDescriptor) -> Option<DataType> {
+    match message.full_name() {
+        "google.protobuf.Timestamp" => Some(DataType::Timestamp(TimeUnit::Nanosecond, None)),
+        "google.protobuf.Duration" => Some(DataType::Duration(TimeUnit::Nanosecond)),
+        _ => None,
+    }
+}
+
 fn fields_for_message(message: &MessageDescriptor) -> Vec<Arc<Field>> {
     message
         .fields()
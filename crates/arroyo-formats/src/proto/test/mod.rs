@@ -1,12 +1,19 @@
+use crate::de::ArrowDeserializer;
 use crate::proto::schema::{
     protobuf_to_arrow, schema_file_to_descriptor, schema_file_to_descriptor_with_resolver,
     ProtoSchemaResolver,
 };
-use arrow_schema::{DataType, Field, Schema};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{DurationNanosecondType, TimestampNanosecondType};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::formats::{BadData, Format, ProtobufFormat};
 use arroyo_types::ArroyoExtensionType;
-use prost_reflect::DescriptorPool;
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, Value};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 #[tokio::test]
 async fn test_basic_types() {
@@ -207,3 +214,96 @@ async fn test_imports() {
     .await
     .unwrap();
 }
+
+#[tokio::test]
+async fn test_well_known_types() {
+    let bytes = schema_file_to_descriptor(
+        include_str!("protos/well_known_types.proto"),
+        &HashMap::default(),
+    )
+    .await
+    .unwrap();
+
+    let pool = DescriptorPool::decode(bytes.as_ref()).unwrap();
+    let descriptor = pool.get_message_by_name("TestWellKnownTypes").unwrap();
+    let arrow_schema = protobuf_to_arrow(&descriptor).unwrap();
+
+    assert_eq!(arrow_schema.fields().len(), 3);
+    assert_field(
+        &arrow_schema,
+        "created_at",
+        DataType::Timestamp(TimeUnit::Nanosecond, None),
+        true,
+    );
+    assert_field(
+        &arrow_schema,
+        "elapsed",
+        DataType::Duration(TimeUnit::Nanosecond),
+        true,
+    );
+
+    let mut fields = arrow_schema.fields().to_vec();
+    fields.push(Arc::new(Field::new(
+        "_timestamp",
+        DataType::Timestamp(TimeUnit::Nanosecond, None),
+        false,
+    )));
+    let schema =
+        Arc::new(ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(fields))).unwrap());
+
+    let mut deserializer = ArrowDeserializer::new(
+        Format::Protobuf(ProtobufFormat {
+            into_unstructured_json: false,
+            message_name: Some("TestWellKnownTypes".to_string()),
+            compiled_schema: Some(bytes),
+            confluent_schema_registry: false,
+        }),
+        schema,
+        &[],
+        None,
+        BadData::Fail {},
+    );
+
+    let timestamp = pool
+        .get_message_by_name("google.protobuf.Timestamp")
+        .unwrap();
+    let mut created_at = DynamicMessage::new(timestamp);
+    created_at.set_field_by_name("seconds", Value::I64(1_700_000_000));
+    created_at.set_field_by_name("nanos", Value::I32(123_456_789));
+
+    let duration = pool
+        .get_message_by_name("google.protobuf.Duration")
+        .unwrap();
+    let mut elapsed = DynamicMessage::new(duration);
+    elapsed.set_field_by_name("seconds", Value::I64(90));
+    elapsed.set_field_by_name("nanos", Value::I32(500));
+
+    let mut message = DynamicMessage::new(descriptor);
+    message.set_field_by_name("name", Value::String("event".to_string()));
+    message.set_field_by_name("created_at", Value::Message(created_at));
+    message.set_field_by_name("elapsed", Value::Message(elapsed));
+
+    let errors = deserializer
+        .deserialize_slice(&message.encode_to_vec(), SystemTime::now(), None)
+        .await;
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let batch = deserializer.flush_buffer().unwrap().unwrap();
+    assert_eq!(batch.num_rows(), 1);
+    assert_eq!(
+        batch
+            .column_by_name("created_at")
+            .unwrap()
+            .as_primitive::<TimestampNanosecondType>()
+            .value(0),
+        1_700_000_000_123_456_789
+    );
+    assert_eq!(
+        batch
+            .column_by_name("elapsed")
+            .unwrap()
+            .as_primitive::<DurationNanosecondType>()
+            .value(0),
+        90_000_000_500
+    );
+}
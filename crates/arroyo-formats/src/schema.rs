@@ -0,0 +1,627 @@
+use crate::proto::schema::{get_pool, protobuf_to_arrow, schema_file_to_descriptor};
+use crate::{avro, json};
+use anyhow::{anyhow, bail, Result};
+use arrow_schema::{DataType, Schema};
+use arroyo_rpc::api_types::connections::{
+    ConfluentSchemaQueryParams, ConnectionSchema, SchemaDefinition, SourceField,
+};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::formats::{
+    AvroFormat, Format, JsonFormat, ProtobufFormat, RawBytesFormat, RawStringFormat,
+};
+use arroyo_rpc::schema_resolver::{ConfluentSchemaRegistry, ConfluentSchemaType};
+use arroyo_rpc::var_str::VarStr;
+use parquet::arrow::arrow_to_parquet_schema;
+use parquet::schema::types::SchemaDescriptor;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Conversions of an [`ArroyoSchema`] into the schema languages of external systems, for sinks
+/// that need to declare their schema up front (e.g., to a schema registry or in a Parquet footer)
+pub trait ArroyoSchemaExt {
+    /// Returns the JSON-encoded avro schema for this schema
+    fn to_avro_schema(&self, include_timestamp: bool) -> Result<String>;
+
+    /// Returns the parquet schema for this schema
+    fn to_parquet_schema(&self, include_timestamp: bool) -> Result<SchemaDescriptor>;
+}
+
+impl ArroyoSchemaExt for ArroyoSchema {
+    fn to_avro_schema(&self, include_timestamp: bool) -> Result<String> {
+        let schema = export_schema(self, include_timestamp);
+        for f in schema.fields() {
+            check_avro_type(f.data_type())?;
+        }
+
+        let avro = avro::schema::to_avro("ArroyoAvro", schema.fields());
+        Ok(serde_json::to_string(&avro)?)
+    }
+
+    fn to_parquet_schema(&self, include_timestamp: bool) -> Result<SchemaDescriptor> {
+        let schema = export_schema(self, include_timestamp);
+        Ok(arrow_to_parquet_schema(&schema)?)
+    }
+}
+
+fn export_schema(schema: &ArroyoSchema, include_timestamp: bool) -> Schema {
+    if include_timestamp {
+        schema.schema.as_ref().clone()
+    } else {
+        schema.schema_without_timestamp()
+    }
+}
+
+/// Fetches the latest value schema registered for the topic (under the subject `{topic}-value`)
+/// and converts it into an inferred [`ConnectionSchema`]
+pub async fn infer_from_schema_registry(
+    params: &ConfluentSchemaQueryParams,
+    api_key: Option<VarStr>,
+    api_secret: Option<VarStr>,
+) -> Result<ConnectionSchema> {
+    let subject = format!("{}-value", params.topic);
+    let registry = ConfluentSchemaRegistry::new(&params.endpoint, &subject, api_key, api_secret)?;
+
+    let resp = registry
+        .get_schema_for_version(None)
+        .await?
+        .ok_or_else(|| anyhow!("no schema found for subject '{}'", subject))?;
+
+    let (format, definition, arrow) = match resp.schema_type {
+        ConfluentSchemaType::Avro => {
+            let arrow = avro::schema::to_arrow(&resp.schema)?;
+            (
+                Format::Avro(AvroFormat::new(true, false, false)),
+                SchemaDefinition::AvroSchema(resp.schema),
+                arrow,
+            )
+        }
+        ConfluentSchemaType::Json => {
+            let arrow = json::schema::to_arrow(&params.topic, &resp.schema)?;
+            (
+                Format::Json(JsonFormat {
+                    confluent_schema_registry: true,
+                    ..Default::default()
+                }),
+                SchemaDefinition::JsonSchema(resp.schema),
+                arrow,
+            )
+        }
+        ConfluentSchemaType::Protobuf => {
+            let dependencies: HashMap<String, String> = registry
+                .resolve_references(&resp.references)
+                .await?
+                .into_iter()
+                .map(|(name, s)| (name, s.schema))
+                .collect();
+
+            let encoded = schema_file_to_descriptor(&resp.schema, &dependencies).await?;
+            let pool = get_pool(&encoded)?;
+
+            // the registry doesn't tell us which message is the value, so we take the first
+            // one defined in the schema
+            let message = pool
+                .all_messages()
+                .find(|m| !m.full_name().starts_with("google.protobuf."))
+                .ok_or_else(|| anyhow!("protobuf schema does not define any messages"))?;
+
+            let arrow = protobuf_to_arrow(&message)?;
+            (
+                Format::Protobuf(ProtobufFormat {
+                    into_unstructured_json: false,
+                    message_name: Some(message.full_name().to_string()),
+                    compiled_schema: Some(encoded),
+                    confluent_schema_registry: true,
+                }),
+                SchemaDefinition::ProtobufSchema {
+                    schema: resp.schema,
+                    dependencies,
+                },
+                arrow,
+            )
+        }
+    };
+
+    let fields: Vec<SourceField> = arrow
+        .fields
+        .iter()
+        .map(|f| (**f).clone().try_into())
+        .collect::<Result<_, String>>()
+        .map_err(|e| anyhow!("failed to convert schema: {}", e))?;
+
+    ConnectionSchema::try_new(
+        Some(format),
+        None,
+        None,
+        None,
+        fields,
+        Some(definition),
+        Some(true),
+        Default::default(),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InferenceConfidence {
+    /// The sample parsed in the guessed format
+    High,
+    /// The guess is based on framing alone or is a fallback; the user should confirm it
+    Low,
+}
+
+/// A [`ConnectionSchema`] guessed from a sample message
+#[derive(Debug, Clone)]
+pub struct SampleInference {
+    pub schema: ConnectionSchema,
+    pub confidence: InferenceConfidence,
+    /// A human-readable explanation of the guess
+    pub reason: String,
+}
+
+/// Guesses the format of a sample message: a JSON object (optionally in the Confluent schema
+/// registry framing) is inferred as JSON along with its fields; other messages starting with the
+/// Confluent magic byte are guessed to be schema-registry Avro; anything else falls back to raw
+/// strings, or raw bytes if the message isn't valid UTF-8.
+pub fn infer_from_sample(msg: &[u8]) -> Result<SampleInference> {
+    if let Ok(Value::Object(sample)) = serde_json::from_slice::<Value>(msg) {
+        return sample_inference(
+            Format::Json(JsonFormat::default()),
+            json::schema::infer_fields_from_sample(&sample)?,
+            InferenceConfidence::High,
+            "message is a JSON object",
+        );
+    }
+
+    // the confluent wire format prefixes messages with a 0 byte followed by a 4-byte schema id
+    if msg.len() > 5 && msg[0] == 0 {
+        if let Ok(Value::Object(sample)) = serde_json::from_slice::<Value>(&msg[5..]) {
+            return sample_inference(
+                Format::Json(JsonFormat {
+                    confluent_schema_registry: true,
+                    ..Default::default()
+                }),
+                json::schema::infer_fields_from_sample(&sample)?,
+                InferenceConfidence::High,
+                "message is a JSON object in the schema registry wire format",
+            );
+        }
+
+        return sample_inference(
+            Format::Avro(AvroFormat::new(true, false, false)),
+            vec![],
+            InferenceConfidence::Low,
+            "message starts with the schema registry magic byte, so it may be Avro; the schema \
+            must be fetched from the schema registry",
+        );
+    }
+
+    match std::str::from_utf8(msg) {
+        Ok(_) => sample_inference(
+            Format::RawString(RawStringFormat {}),
+            vec![raw_value_field(DataType::Utf8)?],
+            InferenceConfidence::Low,
+            "message is not JSON or Avro, but is valid UTF-8",
+        ),
+        Err(_) => sample_inference(
+            Format::RawBytes(RawBytesFormat {}),
+            vec![raw_value_field(DataType::Binary)?],
+            InferenceConfidence::Low,
+            "message is not JSON, Avro, or valid UTF-8",
+        ),
+    }
+}
+
+/// Infers a [`ConnectionSchema`] in the given format from one or more sample messages. Fields
+/// that are missing or null in any of the samples are nullable.
+pub fn infer_from_samples(format: Format, samples: &[&[u8]]) -> Result<ConnectionSchema> {
+    if samples.is_empty() {
+        bail!("at least one sample message is required to infer a schema");
+    }
+
+    let fields = match &format {
+        Format::Json(json) => {
+            let samples = samples
+                .iter()
+                .enumerate()
+                .map(|(i, msg)| {
+                    // skip the magic byte and schema id of the confluent wire format
+                    let msg = if json.confluent_schema_registry && msg.len() > 5 {
+                        &msg[5..]
+                    } else {
+                        msg
+                    };
+
+                    match serde_json::from_slice(msg) {
+                        Ok(Value::Object(sample)) => Ok(sample),
+                        Ok(_) => bail!("sample {} is not a JSON object", i + 1),
+                        Err(e) => bail!("sample {} is not valid JSON: {}", i + 1, e),
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            json::schema::infer_fields_from_samples(&samples)?
+        }
+        Format::RawString(_) => {
+            if let Some(i) = samples.iter().position(|s| std::str::from_utf8(s).is_err()) {
+                bail!("sample {} is not valid UTF-8", i + 1);
+            }
+            vec![raw_value_field(DataType::Utf8)?]
+        }
+        Format::RawBytes(_) => vec![raw_value_field(DataType::Binary)?],
+        f => bail!(
+            "schemas cannot be inferred from samples in the {} format; provide a schema definition instead",
+            f.name()
+        ),
+    };
+
+    ConnectionSchema::try_new(
+        Some(format),
+        None,
+        None,
+        None,
+        fields,
+        None,
+        Some(true),
+        Default::default(),
+    )
+}
+
+fn raw_value_field(data_type: DataType) -> Result<SourceField> {
+    SourceField::try_from(arrow_schema::Field::new("value", data_type, false))
+        .map_err(|e| anyhow!(e))
+}
+
+fn sample_inference(
+    format: Format,
+    fields: Vec<SourceField>,
+    confidence: InferenceConfidence,
+    reason: &str,
+) -> Result<SampleInference> {
+    Ok(SampleInference {
+        schema: ConnectionSchema::try_new(
+            Some(format),
+            None,
+            None,
+            None,
+            fields,
+            None,
+            Some(true),
+            Default::default(),
+        )?,
+        confidence,
+        reason: reason.to_string(),
+    })
+}
+
+fn check_avro_type(dt: &DataType) -> Result<()> {
+    match dt {
+        DataType::List(f) | DataType::FixedSizeList(f, _) | DataType::LargeList(f) => {
+            check_avro_type(f.data_type())
+        }
+        DataType::Struct(fields) => fields
+            .iter()
+            .try_for_each(|f| check_avro_type(f.data_type())),
+        DataType::Null
+        | DataType::Time32(_)
+        | DataType::Time64(_)
+        | DataType::Duration(_)
+        | DataType::Interval(_)
+        | DataType::Union(_, _)
+        | DataType::Dictionary(_, _)
+        | DataType::Decimal128(_, _)
+        | DataType::Decimal256(_, _)
+        | DataType::Map(_, _)
+        | DataType::RunEndEncoded(_, _)
+        | DataType::BinaryView
+        | DataType::Utf8View
+        | DataType::ListView(_)
+        | DataType::LargeListView(_) => {
+            bail!("data type {} cannot be represented in avro", dt)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        infer_from_sample, infer_from_samples, infer_from_schema_registry, ArroyoSchemaExt,
+        InferenceConfidence,
+    };
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+    use arroyo_rpc::api_types::connections::{
+        ConfluentSchemaQueryParams, FieldType, PrimitiveType, SchemaDefinition,
+    };
+    use arroyo_rpc::df::ArroyoSchema;
+    use arroyo_rpc::formats::{AvroFormat, Format, JsonFormat};
+    use arroyo_rpc::var_str::VarStr;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    fn schema() -> ArroyoSchema {
+        let address_fields = vec![
+            Field::new("street", DataType::Utf8, false),
+            Field::new("zip", DataType::Int32, true),
+        ];
+
+        ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("score", DataType::Float64, false),
+            Field::new("active", DataType::Boolean, false),
+            Field::new("address", DataType::Struct(address_fields.into()), false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_avro_schema_export() {
+        let avro: Value = serde_json::from_str(&schema().to_avro_schema(false).unwrap()).unwrap();
+
+        let fields = avro.get("fields").unwrap().as_array().unwrap();
+        assert_eq!(fields.len(), 5);
+
+        assert_eq!(fields[0], json!({"name": "id", "type": "long"}));
+        assert_eq!(
+            fields[1],
+            json!({"name": "name", "type": ["null", "string"]})
+        );
+        assert_eq!(fields[2], json!({"name": "score", "type": "double"}));
+        assert_eq!(fields[3], json!({"name": "active", "type": "boolean"}));
+
+        let address = fields[4].get("type").unwrap();
+        assert_eq!(address.get("type").unwrap(), "record");
+        assert_eq!(
+            address.get("fields").unwrap(),
+            &json!([
+                {"name": "street", "type": "string"},
+                {"name": "zip", "type": ["null", "int"]},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_avro_schema_export_with_timestamp() {
+        let avro: Value = serde_json::from_str(&schema().to_avro_schema(true).unwrap()).unwrap();
+
+        let fields = avro.get("fields").unwrap().as_array().unwrap();
+        assert_eq!(
+            fields.last().unwrap(),
+            &json!({"name": "_timestamp", "type": {"type": "long", "logicalType": "timestamp-micros"}})
+        );
+    }
+
+    #[test]
+    fn test_avro_schema_export_unsupported() {
+        let schema = ArroyoSchema::from_fields(vec![Field::new(
+            "elapsed",
+            DataType::Duration(TimeUnit::Second),
+            false,
+        )]);
+
+        assert!(schema.to_avro_schema(false).is_err());
+    }
+
+    async fn mock_registry(
+        status: &'static str,
+        body: &'static str,
+    ) -> (ConfluentSchemaQueryParams, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        (
+            ConfluentSchemaQueryParams {
+                endpoint: format!("http://{}/", addr),
+                topic: "orders".to_string(),
+            },
+            handle,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_infer_from_schema_registry() {
+        let (params, request) = mock_registry(
+            "200 OK",
+            r#"{"subject":"orders-value","version":3,"id":12,"schema":"{\"type\":\"record\",\"name\":\"Order\",\"fields\":[{\"name\":\"id\",\"type\":\"long\"},{\"name\":\"note\",\"type\":[\"null\",\"string\"]}]}"}"#,
+        )
+        .await;
+
+        let schema = infer_from_schema_registry(
+            &params,
+            Some(VarStr::new("key".to_string())),
+            Some(VarStr::new("secret".to_string())),
+        )
+        .await
+        .unwrap();
+
+        let request = request.await.unwrap().to_lowercase();
+        assert!(request.starts_with("get /subjects/orders%2dvalue/versions/latest "));
+        assert!(request.contains("authorization: basic "));
+
+        assert_eq!(schema.inferred, Some(true));
+        assert!(matches!(schema.format, Some(Format::Avro(_))));
+        assert!(matches!(
+            schema.definition,
+            Some(SchemaDefinition::AvroSchema(_))
+        ));
+
+        assert_eq!(schema.fields.len(), 2);
+        assert_eq!(schema.fields[0].field_name, "id");
+        assert!(!schema.fields[0].nullable);
+        assert_eq!(schema.fields[1].field_name, "note");
+        assert!(schema.fields[1].nullable);
+    }
+
+    #[tokio::test]
+    async fn test_infer_from_schema_registry_error() {
+        let (params, _) = mock_registry(
+            "500 Internal Server Error",
+            r#"{"error_code":50001,"message":"oops"}"#,
+        )
+        .await;
+
+        let err = infer_from_schema_registry(&params, None, None)
+            .await
+            .unwrap_err();
+
+        assert!(format!("{:?}", err).contains("500"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_parquet_schema_export() {
+        let parquet = schema().to_parquet_schema(false).unwrap();
+        // the address struct is flattened into two leaf columns
+        assert_eq!(parquet.num_columns(), 6);
+        assert_eq!(parquet.column(4).path().string(), "address.street");
+
+        let parquet = schema().to_parquet_schema(true).unwrap();
+        assert_eq!(parquet.num_columns(), 7);
+    }
+
+    #[test]
+    fn test_infer_from_sample() {
+        let inferred = infer_from_sample(
+            br#"{"id": 5, "name": "x", "price": 1.5, "tags": ["a"], "extra": null}"#,
+        )
+        .unwrap();
+        assert_eq!(inferred.confidence, InferenceConfidence::High);
+        assert!(matches!(
+            inferred.schema.format,
+            Some(Format::Json(ref json)) if !json.confluent_schema_registry
+        ));
+        assert_eq!(inferred.schema.inferred, Some(true));
+
+        let types: Vec<_> = inferred
+            .schema
+            .fields
+            .iter()
+            .map(|f| {
+                (
+                    f.field_name.as_str(),
+                    f.field_type.r#type.clone(),
+                    f.nullable,
+                )
+            })
+            .collect();
+        assert_eq!(types.len(), 5);
+        assert!(types.contains(&("id", FieldType::Primitive(PrimitiveType::Int64), true)));
+        assert!(types.contains(&("name", FieldType::Primitive(PrimitiveType::String), true)));
+        assert!(types.contains(&("price", FieldType::Primitive(PrimitiveType::F64), true)));
+        assert!(types.contains(&("extra", FieldType::Primitive(PrimitiveType::Json), true)));
+        assert!(matches!(
+            types.iter().find(|(n, _, _)| *n == "tags").unwrap().1,
+            FieldType::List(_)
+        ));
+
+        let mut framed = vec![0, 0, 0, 0, 1];
+        framed.extend_from_slice(br#"{"id": 5}"#);
+        let inferred = infer_from_sample(&framed).unwrap();
+        assert_eq!(inferred.confidence, InferenceConfidence::High);
+        assert!(matches!(
+            inferred.schema.format,
+            Some(Format::Json(ref json)) if json.confluent_schema_registry
+        ));
+
+        let inferred = infer_from_sample(&[0, 0, 0, 0, 1, 2, 4, 6]).unwrap();
+        assert_eq!(inferred.confidence, InferenceConfidence::Low);
+        assert!(matches!(
+            inferred.schema.format,
+            Some(Format::Avro(ref avro)) if avro.confluent_schema_registry
+        ));
+
+        // JSON that isn't an object isn't enough to guess a structured schema
+        let inferred = infer_from_sample(b"[1, 2, 3]").unwrap();
+        assert_eq!(inferred.confidence, InferenceConfidence::Low);
+        assert!(matches!(inferred.schema.format, Some(Format::RawString(_))));
+        assert_eq!(inferred.schema.fields[0].field_name, "value");
+
+        let inferred = infer_from_sample(&[0xff, 0xfe]).unwrap();
+        assert_eq!(inferred.confidence, InferenceConfidence::Low);
+        assert!(matches!(inferred.schema.format, Some(Format::RawBytes(_))));
+    }
+
+    #[test]
+    fn test_infer_from_samples() {
+        let samples: [&[u8]; 2] = [
+            br#"{"id": 1, "score": 3, "user": {"name": "a", "address": {"city": "x"}}, "note": null}"#,
+            br#"{"id": 2, "score": 4.5, "user": {"name": "b", "address": {"city": "y", "zip": 1}}}"#,
+        ];
+
+        let schema = infer_from_samples(Format::Json(JsonFormat::default()), &samples).unwrap();
+        assert_eq!(schema.inferred, Some(true));
+
+        let field = |name: &str| {
+            let f = schema.fields.iter().find(|f| f.field_name == name).unwrap();
+            (f.field_type.r#type.clone(), f.nullable)
+        };
+
+        assert_eq!(
+            field("id"),
+            (FieldType::Primitive(PrimitiveType::Int64), false)
+        );
+        assert_eq!(
+            field("score"),
+            (FieldType::Primitive(PrimitiveType::F64), false)
+        );
+        assert_eq!(
+            field("note"),
+            (FieldType::Primitive(PrimitiveType::Json), true)
+        );
+
+        let (FieldType::Struct(user), false) = field("user") else {
+            panic!("expected user to be a non-nullable struct");
+        };
+        let name = user.fields.iter().find(|f| f.field_name == "name").unwrap();
+        assert_eq!(
+            name.field_type.r#type,
+            FieldType::Primitive(PrimitiveType::String)
+        );
+        assert!(!name.nullable);
+
+        let address = user
+            .fields
+            .iter()
+            .find(|f| f.field_name == "address")
+            .unwrap();
+        let FieldType::Struct(address) = &address.field_type.r#type else {
+            panic!("expected address to be a struct");
+        };
+        let nullability: Vec<_> = address
+            .fields
+            .iter()
+            .map(|f| (f.field_name.as_str(), f.nullable))
+            .collect();
+        assert_eq!(nullability, vec![("city", false), ("zip", true)]);
+
+        let Err(err) = infer_from_samples(Format::Json(JsonFormat::default()), &[b"[1, 2]"]) else {
+            panic!("expected a non-object sample to fail");
+        };
+        assert!(err.to_string().contains("not a JSON object"), "{}", err);
+
+        assert!(
+            infer_from_samples(Format::Avro(AvroFormat::new(false, false, false)), &[b"x"])
+                .is_err()
+        );
+    }
+}
@@ -1,12 +1,15 @@
 use crate::avro::schema;
 use crate::{avro, json};
+use arrow::compute::cast;
+use arrow::ipc::writer::{FileWriter, StreamWriter};
 use arrow_array::cast::AsArray;
 use arrow_array::types::GenericBinaryType;
 use arrow_array::RecordBatch;
 use arrow_json::writer::record_batch_to_vec;
-use arrow_schema::{DataType, Field};
+use arrow_schema::{DataType, Field, FieldRef, TimeUnit};
 use arroyo_rpc::formats::{
-    AvroFormat, Format, JsonFormat, RawBytesFormat, RawStringFormat, TimestampFormat,
+    ArrowIpcFormat, AvroFormat, Format, JsonFormat, RawBytesFormat, RawStringFormat,
+    TimestampFormat,
 };
 use arroyo_rpc::TIMESTAMP_FIELD;
 use serde_json::Value;
@@ -14,6 +17,9 @@ use std::sync::Arc;
 
 pub struct ArrowSerializer {
     kafka_schema: Option<Value>,
+    // the start of the `{"schema": ..., "payload": ...}` envelope written around each record
+    // when including the schema; this only depends on the schema, so it is encoded once
+    kafka_envelope_prefix: Option<Arc<[u8]>>,
     avro_schema: Option<Arc<apache_avro::schema::Schema>>,
     format: Format,
     projection: Vec<usize>,
@@ -23,6 +29,7 @@ impl ArrowSerializer {
     pub fn new(format: Format) -> Self {
         Self {
             kafka_schema: None,
+            kafka_envelope_prefix: None,
             avro_schema: None,
             format,
             projection: vec![],
@@ -68,6 +75,13 @@ impl ArrowSerializer {
             self.kafka_schema = Some(Self::kafka_schema(&batch.schema()));
         }
 
+        if self.kafka_envelope_prefix.is_none() {
+            let mut prefix = br#"{"schema":"#.to_vec();
+            serde_json::to_writer(&mut prefix, self.kafka_schema.as_ref().unwrap()).unwrap();
+            prefix.extend_from_slice(br#","payload":"#);
+            self.kafka_envelope_prefix = Some(prefix.into());
+        }
+
         if self.avro_schema.is_none() {
             self.avro_schema = Some(Arc::new(Self::avro_schema(&batch.schema())));
         }
@@ -82,6 +96,7 @@ impl ArrowSerializer {
             Format::Parquet(_) => todo!("parquet"),
             Format::RawString(RawStringFormat {}) => self.serialize_raw_string(&batch),
             Format::RawBytes(RawBytesFormat {}) => self.serialize_raw_bytes(&batch),
+            Format::ArrowIpc(ipc) => self.serialize_arrow_ipc(ipc, &batch),
             Format::Protobuf(_) => {
                 todo!("protobuf serializer!")
             }
@@ -104,37 +119,44 @@ impl ArrowSerializer {
             v
         });
 
-        let rows = record_batch_to_vec(
-            batch,
-            true,
+        let rows = if json.unstructured {
+            Self::unstructured_json_rows(batch)
+        } else {
             match json.timestamp_format {
-                TimestampFormat::RFC3339 => arrow_json::writer::TimestampFormat::RFC3339,
-                TimestampFormat::UnixMillis => arrow_json::writer::TimestampFormat::UnixMillis,
-            },
-        )
-        .unwrap();
+                TimestampFormat::RFC3339 => {
+                    record_batch_to_vec(batch, true, arrow_json::writer::TimestampFormat::RFC3339)
+                }
+                TimestampFormat::UnixMillis => record_batch_to_vec(
+                    batch,
+                    true,
+                    arrow_json::writer::TimestampFormat::UnixMillis,
+                ),
+                TimestampFormat::UnixMicros => record_batch_to_vec(
+                    &Self::timestamps_to_micros(batch),
+                    true,
+                    arrow_json::writer::TimestampFormat::RFC3339,
+                ),
+            }
+            .unwrap()
+        };
 
-        let include_schema = json.include_schema.then(|| self.kafka_schema.clone());
+        let envelope_prefix = json
+            .include_schema
+            .then(|| self.kafka_envelope_prefix.clone().unwrap());
 
+        // rows are already encoded, so the header and envelope are written around them directly
+        // rather than re-parsing each row, with a single allocation sized for the whole record
         Box::new(rows.into_iter().map(move |row| {
-            if let Some(schema) = &include_schema {
-                let parsed: serde_json::Value = serde_json::from_slice(&row).unwrap();
-                let record = json! {{
-                    "schema": schema,
-                    "payload": parsed,
-                }};
-
-                let mut buf = if let Some(header) = header {
-                    header.to_vec()
-                } else {
-                    vec![]
-                };
-
-                serde_json::to_writer(&mut buf, &record).unwrap();
+            if let Some(prefix) = &envelope_prefix {
+                let mut buf = Vec::with_capacity(prefix.len() + row.len() + 1);
+                buf.extend_from_slice(prefix);
+                buf.extend_from_slice(&row);
+                buf.push(b'}');
                 buf
             } else if let Some(header) = header {
-                let mut buf = header.to_vec();
-                buf.extend(&row);
+                let mut buf = Vec::with_capacity(header.len() + row.len());
+                buf.extend_from_slice(&header);
+                buf.extend_from_slice(&row);
                 buf
             } else {
                 row
@@ -142,6 +164,57 @@ impl ArrowSerializer {
         }))
     }
 
+    /// The JSON writer only supports RFC3339 and millisecond timestamps, so for microsecond
+    /// output timestamps are converted to integer micros before writing, including those nested
+    /// in structs, lists, and maps
+    fn timestamps_to_micros(batch: &RecordBatch) -> RecordBatch {
+        let (fields, columns): (Vec<_>, Vec<_>) = batch
+            .schema()
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .map(|(f, c)| {
+                let ints = replace_timestamps(f.data_type(), &|_| DataType::Int64);
+                if &ints == f.data_type() {
+                    return (f.clone(), c.clone());
+                }
+
+                let micros = replace_timestamps(f.data_type(), &|tz| {
+                    DataType::Timestamp(TimeUnit::Microsecond, tz.clone())
+                });
+                let c = cast(c, &micros)
+                    .and_then(|c| cast(&c, &ints))
+                    .expect("timestamps can always be cast to micros");
+                (Arc::new(f.as_ref().clone().with_data_type(ints)), c)
+            })
+            .unzip();
+
+        RecordBatch::try_new(Arc::new(arrow_schema::Schema::new(fields)), columns)
+            .expect("converted batch must match its schema")
+    }
+
+    /// Unstructured JSON is stored as already-encoded text in the `value` column, so it's written
+    /// out verbatim rather than wrapped in an object
+    fn unstructured_json_rows(batch: &RecordBatch) -> Vec<Vec<u8>> {
+        let value_idx = batch.schema().index_of("value").unwrap_or_else(|_| {
+            panic!(
+                "invalid schema for unstructured json serializer: {}; a VALUE column is required",
+                batch.schema()
+            )
+        });
+
+        if *batch.schema().field(value_idx).data_type() != DataType::Utf8 {
+            panic!("invalid schema for unstructured json serializer: {}; a must have a column VALUE of type JSON", batch.schema());
+        }
+
+        batch
+            .column(value_idx)
+            .as_string::<i32>()
+            .iter()
+            .map(|v| v.unwrap_or("null").as_bytes().to_vec())
+            .collect()
+    }
+
     fn serialize_raw_string(
         &self,
         batch: &RecordBatch,
@@ -189,6 +262,28 @@ impl ArrowSerializer {
         Box::new(values.into_iter())
     }
 
+    /// Each batch is written as a single self-contained IPC message, including its schema
+    fn serialize_arrow_ipc(
+        &self,
+        format: &ArrowIpcFormat,
+        batch: &RecordBatch,
+    ) -> Box<dyn Iterator<Item = Vec<u8>> + Send> {
+        let mut buf = Vec::with_capacity(batch.get_array_memory_size());
+        if format.file {
+            let mut writer = FileWriter::try_new(&mut buf, &batch.schema())
+                .expect("failed to create arrow ipc writer");
+            writer.write(batch).expect("arrow ipc serialization failed");
+            writer.finish().expect("arrow ipc serialization failed");
+        } else {
+            let mut writer = StreamWriter::try_new(&mut buf, &batch.schema())
+                .expect("failed to create arrow ipc writer");
+            writer.write(batch).expect("arrow ipc serialization failed");
+            writer.finish().expect("arrow ipc serialization failed");
+        }
+
+        Box::new(std::iter::once(buf))
+    }
+
     fn serialize_avro(
         &self,
         format: &AvroFormat,
@@ -236,12 +331,38 @@ impl ArrowSerializer {
     }
 }
 
+/// Replaces every timestamp type in `data_type`, including within nested types, with the type
+/// returned by `f` for its timezone
+fn replace_timestamps(data_type: &DataType, f: &dyn Fn(&Option<Arc<str>>) -> DataType) -> DataType {
+    let field = |field: &FieldRef| {
+        Arc::new(
+            field
+                .as_ref()
+                .clone()
+                .with_data_type(replace_timestamps(field.data_type(), f)),
+        )
+    };
+
+    match data_type {
+        DataType::Timestamp(_, tz) => f(tz),
+        DataType::Struct(fields) => DataType::Struct(fields.iter().map(field).collect()),
+        DataType::List(item) => DataType::List(field(item)),
+        DataType::LargeList(item) => DataType::LargeList(field(item)),
+        DataType::FixedSizeList(item, size) => DataType::FixedSizeList(field(item), *size),
+        DataType::Map(entries, sorted) => DataType::Map(field(entries), *sorted),
+        other => other.clone(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::ser::ArrowSerializer;
     use arrow_array::builder::TimestampNanosecondBuilder;
+    use arrow_array::Array;
     use arrow_schema::{Schema, TimeUnit};
-    use arroyo_rpc::formats::{Format, RawBytesFormat, RawStringFormat, TimestampFormat};
+    use arroyo_rpc::formats::{
+        AvroFormat, Format, RawBytesFormat, RawStringFormat, TimestampFormat,
+    };
     use arroyo_types::to_nanos;
     use std::sync::Arc;
     use std::time::{Duration, SystemTime};
@@ -324,6 +445,55 @@ mod tests {
         assert_eq!(iter.next(), None);
     }
 
+    #[test]
+    fn test_unstructured_json() {
+        let mut serializer = ArrowSerializer::new(Format::Json(arroyo_rpc::formats::JsonFormat {
+            confluent_schema_registry: false,
+            schema_id: None,
+            include_schema: false,
+            debezium: false,
+            unstructured: true,
+            timestamp_format: Default::default(),
+        }));
+
+        let data = vec![
+            Some(r#"{"a":1,"b":[true]}"#),
+            Some("[1,2]"),
+            Some("3"),
+            None,
+        ];
+        let ts: Vec<_> = data
+            .iter()
+            .enumerate()
+            .map(|(i, _)| to_nanos(SystemTime::now() + Duration::from_secs(i as u64)) as i64)
+            .collect();
+
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("value", arrow_schema::DataType::Utf8, true),
+            arrow_schema::Field::new(
+                "_timestamp",
+                arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+
+        let batch = arrow_array::RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(arrow_array::StringArray::from(data)),
+                Arc::new(arrow_array::TimestampNanosecondArray::from(ts)),
+            ],
+        )
+        .unwrap();
+
+        let mut iter = serializer.serialize(&batch);
+        assert_eq!(iter.next().unwrap(), br#"{"a":1,"b":[true]}"#);
+        assert_eq!(iter.next().unwrap(), b"[1,2]");
+        assert_eq!(iter.next().unwrap(), b"3");
+        assert_eq!(iter.next().unwrap(), b"null");
+        assert_eq!(iter.next(), None);
+    }
+
     #[test]
     fn test_json() {
         let mut serializer = ArrowSerializer::new(Format::Json(arroyo_rpc::formats::JsonFormat {
@@ -376,17 +546,95 @@ mod tests {
         assert_eq!(iter.next(), None);
     }
 
+    fn json_batch(values: &[&str]) -> arrow_array::RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("value", arrow_schema::DataType::Utf8, false),
+            arrow_schema::Field::new(
+                "_timestamp",
+                arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+
+        arrow_array::RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(arrow_array::StringArray::from(values.to_vec())),
+                Arc::new(arrow_array::TimestampNanosecondArray::from(vec![
+                    0;
+                    values
+                        .len()
+                ])),
+            ],
+        )
+        .unwrap()
+    }
+
     #[test]
-    fn test_json_unix_ts() {
+    fn test_json_include_schema() {
         let mut serializer = ArrowSerializer::new(Format::Json(arroyo_rpc::formats::JsonFormat {
             confluent_schema_registry: false,
             schema_id: None,
+            include_schema: true,
+            debezium: false,
+            unstructured: false,
+            timestamp_format: Default::default(),
+        }));
+
+        let batch = json_batch(&["a", "b\n\"c\""]);
+        let schema = ArrowSerializer::kafka_schema(&batch.schema());
+
+        // the envelope is reused across batches
+        for batch in [batch, json_batch(&["d"])] {
+            let values: Vec<_> = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<arrow_array::StringArray>()
+                .unwrap()
+                .iter()
+                .map(|v| v.unwrap().to_string())
+                .collect();
+
+            let rows: Vec<_> = serializer.serialize(&batch).collect();
+            assert_eq!(rows.len(), values.len());
+
+            for (row, value) in rows.iter().zip(values) {
+                let parsed: serde_json::Value = serde_json::from_slice(row).unwrap();
+                assert_eq!(
+                    parsed,
+                    serde_json::json!({
+                        "schema": schema,
+                        "payload": {"value": value},
+                    })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_confluent_header() {
+        let mut serializer = ArrowSerializer::new(Format::Json(arroyo_rpc::formats::JsonFormat {
+            confluent_schema_registry: true,
+            schema_id: Some(258),
             include_schema: false,
             debezium: false,
             unstructured: false,
-            timestamp_format: TimestampFormat::UnixMillis,
+            timestamp_format: Default::default(),
         }));
 
+        let mut iter = serializer.serialize(&json_batch(&["a", "b"]));
+        assert_eq!(
+            iter.next().unwrap(),
+            b"\x00\x00\x00\x01\x02{\"value\":\"a\"}"
+        );
+        assert_eq!(
+            iter.next().unwrap(),
+            b"\x00\x00\x00\x01\x02{\"value\":\"b\"}"
+        );
+        assert_eq!(iter.next(), None);
+    }
+
+    fn timestamp_batch() -> arrow_array::RecordBatch {
         let mut timestamp_array = TimestampNanosecondBuilder::new();
         timestamp_array.append_value(1612274910045331968);
         timestamp_array.append_null();
@@ -412,18 +660,145 @@ mod tests {
             ),
         ]));
 
-        let batch = arrow_array::RecordBatch::try_new(
+        arrow_array::RecordBatch::try_new(
             schema,
             vec![
                 ts,
                 Arc::new(arrow_array::TimestampNanosecondArray::from(event_times)),
             ],
         )
-        .unwrap();
+        .unwrap()
+    }
 
-        let mut iter = serializer.serialize(&batch);
+    fn json_ts_serializer(timestamp_format: TimestampFormat) -> ArrowSerializer {
+        ArrowSerializer::new(Format::Json(arroyo_rpc::formats::JsonFormat {
+            confluent_schema_registry: false,
+            schema_id: None,
+            include_schema: false,
+            debezium: false,
+            unstructured: false,
+            timestamp_format,
+        }))
+    }
+
+    #[test]
+    fn test_json_unix_ts() {
+        let mut serializer = json_ts_serializer(TimestampFormat::UnixMillis);
+
+        let mut iter = serializer.serialize(&timestamp_batch());
         assert_eq!(iter.next().unwrap(), br#"{"value":1612274910045}"#);
         assert_eq!(iter.next().unwrap(), br#"{"value":null}"#);
         assert_eq!(iter.next().unwrap(), br#"{"value":1712274910045}"#);
     }
+
+    #[test]
+    fn test_json_unix_micros_ts() {
+        let mut serializer = json_ts_serializer(TimestampFormat::UnixMicros);
+
+        let mut iter = serializer.serialize(&timestamp_batch());
+        assert_eq!(iter.next().unwrap(), br#"{"value":1612274910045331}"#);
+        assert_eq!(iter.next().unwrap(), br#"{"value":null}"#);
+        assert_eq!(iter.next().unwrap(), br#"{"value":1712274910045331}"#);
+    }
+
+    #[test]
+    fn test_json_unix_micros_nested_ts() {
+        let mut serializer = json_ts_serializer(TimestampFormat::UnixMicros);
+
+        let ts_field = Arc::new(arrow_schema::Field::new(
+            "ts",
+            arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
+            true,
+        ));
+        let ts: arrow_array::ArrayRef =
+            Arc::new(arrow_array::TimestampNanosecondArray::from(vec![
+                1612274910045331968,
+                1712274910045331968,
+            ]));
+        let event = arrow_array::StructArray::from(vec![(ts_field.clone(), ts.clone())]);
+        let history = arrow_array::ListArray::new(
+            ts_field.clone(),
+            arrow::buffer::OffsetBuffer::from_lengths([2]),
+            ts,
+            None,
+        );
+
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("event", event.data_type().clone(), false),
+            arrow_schema::Field::new("history", history.data_type().clone(), false),
+        ]));
+        let batch = arrow_array::RecordBatch::try_new(
+            schema,
+            vec![Arc::new(event.slice(0, 1)), Arc::new(history)],
+        )
+        .unwrap();
+
+        let mut iter = serializer.serialize(&batch);
+        assert_eq!(
+            iter.next().unwrap(),
+            br#"{"event":{"ts":1612274910045331},"history":[1612274910045331,1712274910045331]}"#
+        );
+    }
+
+    #[test]
+    fn test_json_rfc3339_ts() {
+        let mut serializer = json_ts_serializer(TimestampFormat::RFC3339);
+
+        let mut iter = serializer.serialize(&timestamp_batch());
+        assert_eq!(
+            iter.next().unwrap(),
+            br#"{"value":"2021-02-02T14:08:30.045331968"}"#
+        );
+        assert_eq!(iter.next().unwrap(), br#"{"value":null}"#);
+        assert_eq!(
+            iter.next().unwrap(),
+            br#"{"value":"2024-04-04T23:55:10.045331968"}"#
+        );
+    }
+
+    #[test]
+    fn test_avro_confluent_header() {
+        let mut serializer = ArrowSerializer::new(Format::Avro(AvroFormat {
+            confluent_schema_registry: true,
+            raw_datums: false,
+            into_unstructured_json: false,
+            reader_schema: None,
+            schema_id: Some(258),
+        }));
+
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("value", arrow_schema::DataType::Int64, false),
+            arrow_schema::Field::new(
+                "_timestamp",
+                arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+
+        let batch = arrow_array::RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(arrow_array::Int64Array::from(vec![5, 6])),
+                Arc::new(arrow_array::TimestampNanosecondArray::from(vec![0, 1])),
+            ],
+        )
+        .unwrap();
+
+        let avro_schema = ArrowSerializer::avro_schema(&schema);
+
+        for (i, record) in serializer.serialize(&batch).enumerate() {
+            // magic byte followed by the big-endian schema id
+            assert_eq!(&record[..5], &[0, 0, 0, 1, 2]);
+
+            let value =
+                apache_avro::from_avro_datum(&avro_schema, &mut &record[5..], None).unwrap();
+            assert_eq!(
+                value,
+                apache_avro::types::Value::Record(vec![(
+                    "value".to_string(),
+                    apache_avro::types::Value::Long(5 + i as i64)
+                )])
+            );
+        }
+    }
 }
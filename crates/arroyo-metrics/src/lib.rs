@@ -1,14 +1,15 @@
 use std::collections::HashMap;
 use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
 
 use arroyo_types::{
-    ChainInfo, BATCHES_RECV, BATCHES_SENT, BYTES_RECV, BYTES_SENT, DESERIALIZATION_ERRORS,
-    MESSAGES_RECV, MESSAGES_SENT,
+    ChainInfo, BACKPRESSURE_MICROS, BATCHES_RECV, BATCHES_SENT, BYTES_RECV, BYTES_SENT,
+    DESERIALIZATION_ERRORS, MESSAGES_RECV, MESSAGES_SENT, WATERMARK_LAG_SECONDS,
 };
 use lazy_static::lazy_static;
 use prometheus::{
-    labels, register_histogram, register_int_counter_vec, register_int_gauge, Histogram,
-    HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts,
+    labels, register_gauge_vec, register_histogram, register_int_counter_vec, register_int_gauge,
+    GaugeVec, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts,
 };
 
 pub fn gauge_for_task(
@@ -85,6 +86,37 @@ lazy_static! {
         &TASK_METRIC_LABELS
     )
     .unwrap();
+    // labeled only by task, as both operators and the collector of their chain report into it
+    pub static ref BACKPRESSURE_MICROS_COUNTER: IntCounterVec = register_int_counter_vec!(
+        BACKPRESSURE_MICROS,
+        "Time spent blocked on downstream sends, in microseconds",
+        &["node_id", "subtask_idx"]
+    )
+    .unwrap();
+    pub static ref WATERMARK_LAG_SECONDS_GAUGE: GaugeVec = register_gauge_vec!(
+        WATERMARK_LAG_SECONDS,
+        "How far the current watermark is behind wall-clock time, in seconds",
+        &["node_id", "subtask_idx"]
+    )
+    .unwrap();
+}
+
+/// Returns the counter of time, in microseconds, that a task spent blocked waiting for downstream
+/// to accept its output; it's looked up once and held by the task, as it's updated on every send
+pub fn backpressure_counter(node_id: u32, task_index: u32) -> IntCounter {
+    BACKPRESSURE_MICROS_COUNTER.with_label_values(&[&node_id.to_string(), &task_index.to_string()])
+}
+
+/// Records how far behind wall-clock time a task's current watermark is; watermarks in the
+/// future are reported as zero lag
+pub fn record_watermark_lag(node_id: u32, task_index: u32, watermark: SystemTime) {
+    let lag = SystemTime::now()
+        .duration_since(watermark)
+        .unwrap_or(Duration::ZERO);
+
+    WATERMARK_LAG_SECONDS_GAUGE
+        .with_label_values(&[&node_id.to_string(), &task_index.to_string()])
+        .set(lag.as_secs_f64());
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
@@ -194,3 +226,23 @@ pub fn register_queue_gauge<T>(
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watermark_lag() {
+        let lag = |node_id: u32| {
+            WATERMARK_LAG_SECONDS_GAUGE
+                .with_label_values(&[&node_id.to_string(), "0"])
+                .get()
+        };
+
+        record_watermark_lag(1, 0, SystemTime::now() - Duration::from_secs(120));
+        assert!((120.0..130.0).contains(&lag(1)), "lag was {}", lag(1));
+
+        record_watermark_lag(2, 0, SystemTime::now() + Duration::from_secs(60));
+        assert_eq!(lag(2), 0.0);
+    }
+}
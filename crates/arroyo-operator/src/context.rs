@@ -3,26 +3,27 @@ use arrow::array::{Array, PrimitiveArray, RecordBatch};
 use arrow::compute::{partition, sort_to_indices, take};
 use arrow::datatypes::UInt64Type;
 use arroyo_formats::de::{ArrowDeserializer, FieldValueType};
-use arroyo_metrics::{register_queue_gauge, QueueGauges, TaskCounters};
+use arroyo_metrics::{backpressure_counter, register_queue_gauge, QueueGauges, TaskCounters};
 use arroyo_rpc::config::config;
 use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::formats::{BadData, Format, Framing};
+use arroyo_rpc::grpc::api::CheckpointAlignment;
 use arroyo_rpc::grpc::rpc::{CheckpointMetadata, TableConfig, TaskCheckpointEventType};
 use arroyo_rpc::schema_resolver::SchemaResolver;
-use arroyo_rpc::{get_hasher, CompactionResult, ControlMessage, ControlResp, MetadataField};
+use arroyo_rpc::{CompactionResult, ControlMessage, ControlResp, MetadataField};
 use arroyo_state::tables::table_manager::TableManager;
 use arroyo_types::{
     ArrowMessage, ChainInfo, CheckpointBarrier, SignalMessage, SourceError, TaskInfo, UserError,
     Watermark,
 };
 use async_trait::async_trait;
-use datafusion::common::hash_utils;
+use prometheus::IntCounter;
 use rand::Rng;
 use std::collections::HashMap;
 use std::mem::size_of_val;
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::{unbounded_channel, Receiver, Sender, UnboundedReceiver, UnboundedSender};
 use tokio::sync::Notify;
@@ -168,6 +169,11 @@ pub struct BatchReceiver {
 }
 
 impl BatchReceiver {
+    /// The number of messages currently waiting in the queue
+    pub fn queued_messages(&self) -> u32 {
+        self.queued_messages.load(Ordering::Relaxed)
+    }
+
     pub async fn recv(&mut self) -> Option<QueueItem> {
         let item = self.rx.recv().await;
         if let Some(item) = &item {
@@ -460,6 +466,10 @@ pub struct OperatorContext {
     pub out_schema: Option<Arc<ArroyoSchema>>,
     pub table_manager: TableManager,
     pub error_reporter: ErrorReporter,
+    /// How checkpoint barriers are aligned across this operator's inputs; only consulted for the
+    /// head of a chain, which owns the input queues
+    pub checkpoint_alignment: CheckpointAlignment,
+    backpressure: IntCounter,
 }
 
 #[derive(Clone)]
@@ -497,22 +507,16 @@ pub struct ArrowCollector {
     tx_queue_rem_gauges: QueueGauges,
     tx_queue_size_gauges: QueueGauges,
     tx_queue_bytes_gauges: QueueGauges,
+    backpressure: IntCounter,
 }
 
 fn repartition<'a>(
     record: &'a RecordBatch,
-    keys: Option<&'a Vec<usize>>,
+    hashes: Option<PrimitiveArray<UInt64Type>>,
     qs: usize,
 ) -> impl Iterator<Item = (usize, RecordBatch)> + 'a {
-    let mut buf = vec![0; record.num_rows()];
-
-    if let Some(keys) = keys {
-        let keys: Vec<_> = keys.iter().map(|i| record.column(*i).clone()).collect();
-
-        hash_utils::create_hashes(&keys[..], &get_hasher(), &mut buf).unwrap();
-        let buf_array = PrimitiveArray::from(buf);
-
-        let servers = server_for_hash_array(&buf_array, qs).unwrap();
+    if let Some(hashes) = hashes {
+        let servers = server_for_hash_array(&hashes, qs).unwrap();
 
         let indices = sort_to_indices(&servers, None, None).unwrap();
         let columns = record
@@ -579,13 +583,21 @@ impl Collector for ArrowCollector {
             });
 
         for (i, out_q) in self.out_qs.iter_mut().enumerate() {
-            let partitions = repartition(&record, out_schema.routing_keys(), out_q.len());
+            let partitions = repartition(
+                &record,
+                out_schema
+                    .routing_keys()
+                    .map(|_| out_schema.hash_keys(&record).unwrap()),
+                out_q.len(),
+            );
 
             for (partition, batch) in partitions {
+                let start = Instant::now();
                 out_q[partition]
                     .send(ArrowMessage::Data(batch))
                     .await
                     .unwrap();
+                self.backpressure.inc_by(start.elapsed().as_micros() as u64);
 
                 self.tx_queue_rem_gauges[i][partition]
                     .iter()
@@ -642,6 +654,8 @@ impl ArrowCollector {
             m.for_task(&chain_info, |_| {});
         }
 
+        let backpressure = backpressure_counter(chain_info.node_id, chain_info.task_index);
+
         Self {
             chain_info,
             out_schema,
@@ -649,6 +663,7 @@ impl ArrowCollector {
             tx_queue_rem_gauges,
             tx_queue_size_gauges,
             tx_queue_bytes_gauges,
+            backpressure,
         }
     }
 
@@ -685,6 +700,8 @@ impl OperatorContext {
                 .await
                 .expect("should be able to create TableManager");
 
+        let backpressure = backpressure_counter(task_info.node_id, task_info.task_index);
+
         Self {
             task_info: task_info.clone(),
             control_tx: control_tx.clone(),
@@ -699,6 +716,8 @@ impl OperatorContext {
                 tx: control_tx,
                 task_info,
             },
+            checkpoint_alignment: CheckpointAlignment::Aligned,
+            backpressure,
         }
     }
 
@@ -721,6 +740,12 @@ impl OperatorContext {
     pub async fn report_error(&mut self, message: impl Into<String>, details: impl Into<String>) {
         self.error_reporter.report_error(message, details).await;
     }
+
+    /// Records time this operator spent blocked waiting for its output to be accepted, for
+    /// operators (like sinks) that send to external systems rather than through the collector
+    pub fn record_backpressure(&self, duration: Duration) {
+        self.backpressure.inc_by(duration.as_micros() as u64);
+    }
 }
 
 #[cfg(test)]
@@ -828,6 +853,7 @@ mod tests {
             tx_queue_rem_gauges,
             tx_queue_size_gauges,
             tx_queue_bytes_gauges,
+            backpressure: backpressure_counter(1, 0),
         };
 
         collector.collect(record).await;
@@ -869,11 +895,14 @@ mod tests {
         tx.send(ArrowMessage::Data(msg.clone())).await.unwrap();
 
         assert_eq!(tx.capacity(), 0);
+        assert_eq!(rx.queued_messages(), 8);
 
         rx.recv().await.unwrap();
+        assert_eq!(rx.queued_messages(), 4);
         rx.recv().await.unwrap();
 
         assert_eq!(tx.capacity(), 8);
+        assert_eq!(rx.queued_messages(), 0);
     }
 
     #[tokio::test]
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::future::Future;
 use std::ops::Sub;
@@ -69,46 +70,57 @@ pub enum ControlOutcome {
 
 #[derive(Debug)]
 pub struct CheckpointCounter {
-    inputs: Vec<Option<u32>>,
-    counter: Option<usize>,
+    size: usize,
+    // the inputs that have delivered their barrier, for each epoch that hasn't completed yet;
+    // with at-least-once checkpoints a fast input may deliver the barrier for the next epoch before
+    // a slow input has delivered the current one
+    epochs: BTreeMap<u32, HashSet<usize>>,
 }
 
 impl CheckpointCounter {
     pub fn new(size: usize) -> CheckpointCounter {
         CheckpointCounter {
-            inputs: vec![None; size],
-            counter: None,
+            size,
+            epochs: BTreeMap::new(),
         }
     }
 
     pub fn is_blocked(&self, idx: usize) -> bool {
-        self.inputs[idx].is_some()
+        self.epochs.values().any(|inputs| inputs.contains(&idx))
     }
 
     pub fn all_clear(&self) -> bool {
-        self.inputs.iter().all(|x| x.is_none())
+        self.epochs.is_empty()
     }
 
-    pub fn mark(&mut self, idx: usize, checkpoint: &CheckpointBarrier) -> bool {
-        assert!(self.inputs[idx].is_none());
+    /// Whether a barrier for this epoch has already arrived on some input
+    pub fn in_progress(&self, epoch: u32) -> bool {
+        self.epochs.contains_key(&epoch)
+    }
 
-        if self.inputs.len() == 1 {
+    /// Records the barrier arriving on input `idx`, returning true once every input has
+    /// delivered the barrier for its epoch
+    pub fn mark(&mut self, idx: usize, checkpoint: &CheckpointBarrier) -> bool {
+        if self.size == 1 {
             return true;
         }
 
-        self.inputs[idx] = Some(checkpoint.epoch);
-        self.counter = match self.counter {
-            None => Some(self.inputs.len() - 1),
-            Some(1) => {
-                for v in self.inputs.iter_mut() {
-                    *v = None;
-                }
-                None
-            }
-            Some(n) => Some(n - 1),
-        };
-
-        self.counter.is_none()
+        let inputs = self.epochs.entry(checkpoint.epoch).or_default();
+        assert!(
+            inputs.insert(idx),
+            "received a second barrier for epoch {} on input {}",
+            checkpoint.epoch,
+            idx
+        );
+
+        if inputs.len() < self.size {
+            return false;
+        }
+
+        self.epochs.remove(&checkpoint.epoch);
+        // barriers arrive in order on each input, so earlier epochs always complete first
+        debug_assert!(self.epochs.keys().all(|e| *e > checkpoint.epoch));
+        true
     }
 }
 
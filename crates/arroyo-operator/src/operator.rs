@@ -10,14 +10,15 @@ use arrow::array::RecordBatch;
 use arrow::datatypes::DataType;
 use arrow::datatypes::Schema;
 use arroyo_datastream::logical::{DylibUdfConfig, PythonUdfConfig};
-use arroyo_metrics::TaskCounters;
+use arroyo_metrics::{gauge_for_task, TaskCounters};
 use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::api::CheckpointAlignment;
 use arroyo_rpc::grpc::rpc::{TableConfig, TaskCheckpointEventType};
 use arroyo_rpc::{ControlMessage, ControlResp};
 use arroyo_state::tables::table_manager::TableManager;
 use arroyo_storage::StorageProvider;
 use arroyo_types::{
-    ArrowMessage, ChainInfo, CheckpointBarrier, SignalMessage, TaskInfo, Watermark,
+    ArrowMessage, ChainInfo, CheckpointBarrier, SignalMessage, TaskInfo, Watermark, RX_QUEUE_DEPTH,
 };
 use arroyo_udf_host::parse::inner_type;
 use arroyo_udf_host::{ContainerOrLocal, LocalUdf, SyncUdfDylib, UdfDylib, UdfInterface};
@@ -624,7 +625,7 @@ impl ChainedOperator {
             SignalMessage::Barrier(t) => {
                 debug!("received barrier in {}[{}]", chain_info, idx);
 
-                if counter.all_clear() {
+                if !counter.in_progress(t.epoch) {
                     control_tx
                         .send(ControlResp::CheckpointEvent(arroyo_rpc::CheckpointEvent {
                             checkpoint_epoch: t.epoch,
@@ -886,14 +887,26 @@ async fn operator_run_behavior(
         .unwrap();
 
     let name = this.name();
+    let aligned = this.context.checkpoint_alignment == CheckpointAlignment::Aligned;
     let mut counter = CheckpointCounter::new(in_qs.len());
     let mut closed: HashSet<usize> = HashSet::new();
     let mut sel = InQReader::new();
     let in_partitions = in_qs.len();
 
     for (i, q) in in_qs.iter_mut().enumerate() {
+        let depth = gauge_for_task(
+            chain_info,
+            RX_QUEUE_DEPTH,
+            "Number of messages waiting in an input queue",
+            [("in_idx".to_string(), i.to_string())]
+                .into_iter()
+                .collect(),
+        );
         let stream = async_stream::stream! {
           while let Some(item) = q.recv().await {
+            if let Some(depth) = &depth {
+                depth.set(q.queued_messages() as i64);
+            }
             yield(i, item);
           }
         };
@@ -963,7 +976,9 @@ async fn operator_run_behavior(
                             }
                         }
 
-                        if counter.is_blocked(idx){
+                        // with at-least-once checkpoints we keep reading inputs that have already
+                        // delivered their barrier rather than waiting for the others
+                        if aligned && counter.is_blocked(idx){
                             blocked.push(s);
                         } else {
                             if counter.all_clear() && !blocked.is_empty(){
@@ -1429,3 +1444,139 @@ impl FunctionRegistry for Registry {
         vec![]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::batch_bounded;
+    use arrow::array::{AsArray, Int64Array, TimestampNanosecondArray};
+    use arrow::datatypes::{Field, Int64Type, TimeUnit};
+    use arroyo_types::to_nanos;
+    use tokio::sync::mpsc::channel;
+
+    struct PassThrough;
+
+    #[async_trait]
+    impl ArrowOperator for PassThrough {
+        fn name(&self) -> String {
+            "pass_through".to_string()
+        }
+
+        async fn process_batch(
+            &mut self,
+            batch: RecordBatch,
+            _: &mut OperatorContext,
+            collector: &mut dyn Collector,
+        ) {
+            collector.collect(batch).await;
+        }
+    }
+
+    fn barrier(epoch: u32) -> ArrowMessage {
+        ArrowMessage::Signal(SignalMessage::Barrier(CheckpointBarrier {
+            epoch,
+            min_epoch: 0,
+            timestamp: SystemTime::now(),
+            then_stop: false,
+        }))
+    }
+
+    async fn next(rx: &mut BatchReceiver) -> ArrowMessage {
+        tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .expect("timed out waiting for the operator")
+            .expect("operator exited")
+    }
+
+    #[tokio::test]
+    async fn test_at_least_once_input_an_epoch_ahead() {
+        let schema = Arc::new(ArroyoSchema::new_unkeyed(
+            Arc::new(Schema::new(vec![
+                Field::new(
+                    "_timestamp",
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    false,
+                ),
+                Field::new("value", DataType::Int64, false),
+            ])),
+            0,
+        ));
+        let batch = |v: i64| {
+            ArrowMessage::Data(
+                RecordBatch::try_new(
+                    schema.schema.clone(),
+                    vec![
+                        Arc::new(TimestampNanosecondArray::from(vec![
+                            to_nanos(SystemTime::now()) as i64,
+                        ])),
+                        Arc::new(Int64Array::from(vec![v])),
+                    ],
+                )
+                .unwrap(),
+            )
+        };
+
+        let (control_tx, _control_rx) = channel(128);
+        let (_to_operator_tx, to_operator_rx) = channel(16);
+        let mut ctx = OperatorContext::new(
+            Arc::new(TaskInfo::for_test("job", "pass_through")),
+            None,
+            control_tx.clone(),
+            2,
+            vec![schema.clone(), schema.clone()],
+            Some(schema.clone()),
+            HashMap::new(),
+        )
+        .await;
+        ctx.checkpoint_alignment = CheckpointAlignment::AtLeastOnce;
+        let node = OperatorNode::Chained(ChainedOperator::new(Box::new(PassThrough), ctx));
+
+        let (fast_tx, fast_rx) = batch_bounded(16);
+        let (slow_tx, slow_rx) = batch_bounded(16);
+        let (out_tx, mut out_rx) = batch_bounded(16);
+
+        tokio::spawn(Box::new(node).start(
+            control_tx,
+            to_operator_rx,
+            vec![fast_rx, slow_rx],
+            vec![vec![out_tx]],
+            Some(schema.clone()),
+            Arc::new(Barrier::new(1)),
+        ));
+
+        let value = |message: ArrowMessage| match message {
+            ArrowMessage::Data(b) => b.column(1).as_primitive::<Int64Type>().value(0),
+            m => panic!("expected data, got {:?}", m),
+        };
+
+        // the fast input runs a full epoch ahead, delivering the barriers for epochs 1 and 2
+        // before the slow input has delivered either
+        for message in [batch(1), barrier(1), batch(2), barrier(2), batch(3)] {
+            fast_tx.send(message).await.unwrap();
+        }
+        for v in 1..=3 {
+            assert_eq!(value(next(&mut out_rx).await), v);
+        }
+
+        for message in [batch(10), barrier(1), barrier(2)] {
+            slow_tx.send(message).await.unwrap();
+        }
+        for tx in [&fast_tx, &slow_tx] {
+            tx.send(ArrowMessage::Signal(SignalMessage::Stop))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(value(next(&mut out_rx).await), 10);
+        for epoch in [1, 2] {
+            match next(&mut out_rx).await {
+                ArrowMessage::Signal(SignalMessage::Barrier(b)) => assert_eq!(b.epoch, epoch),
+                m => panic!("expected barrier {}, got {:?}", epoch, m),
+            }
+        }
+        assert!(matches!(
+            next(&mut out_rx).await,
+            ArrowMessage::Signal(SignalMessage::Stop)
+        ));
+    }
+}
@@ -35,6 +35,7 @@ use crate::physical::{
 };
 use crate::schemas::add_timestamp_field_arrow;
 use crate::ArroyoSchemaProvider;
+use crate::PlanningOptions;
 use datafusion_proto::physical_plan::to_proto::serialize_physical_expr;
 use datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec;
 use datafusion_proto::{
@@ -86,6 +87,10 @@ impl<'a> Planner<'a> {
         }
     }
 
+    pub(crate) fn planning_options(&self) -> &PlanningOptions {
+        &self.schema_provider.planning_options
+    }
+
     pub(crate) fn sync_plan(&self, plan: &LogicalPlan) -> Result<Arc<dyn ExecutionPlan>> {
         let fut = self.planner.create_physical_plan(plan, self.session_state);
         let (tx, mut rx) = oneshot::channel();
@@ -321,6 +326,18 @@ impl PlanToGraphVisitor<'_> {
             .plan_node(&self.planner, self.graph.node_count(), input_schemas)
             .map_err(|e| e.context(format!("planning operator {:?}", extension)))?;
 
+        let output_schema = extension.output_schema();
+        let node = node.with_metadata(
+            "output_schema",
+            output_schema
+                .schema
+                .fields()
+                .iter()
+                .map(|f| format!("{}: {}", f.name(), f.data_type()))
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+
         let node_index = self.graph.add_node(node);
         self.add_index_to_traversal(node_index);
 
@@ -328,8 +345,7 @@ impl PlanToGraphVisitor<'_> {
             self.graph.add_edge(source, node_index, edge);
         }
 
-        self.output_schemas
-            .insert(node_index, extension.output_schema().into());
+        self.output_schemas.insert(node_index, output_schema.into());
 
         if let Some(node_name) = extension.node_name() {
             self.named_nodes.insert(node_name, node_index);
@@ -9,7 +9,7 @@ use arroyo_datastream::{
 use arroyo_rpc::{
     df::{ArroyoSchema, ArroyoSchemaRef},
     grpc::api::{
-        SessionWindowAggregateOperator, SlidingWindowAggregateOperator,
+        SessionWindowAggregateOperator, SlidingWindowAggregateOperator, SlidingWindowEmit,
         TumblingWindowAggregateOperator,
     },
     TIMESTAMP_FIELD,
@@ -39,6 +39,23 @@ use crate::{
 
 pub(crate) const AGGREGATE_EXTENSION_NAME: &str = "AggregateExtension";
 
+/// Aggregates that can be split into partial and final stages when used with DISTINCT. The
+/// first group carries the set of distinct values in its partial state, so the final stage
+/// can merge them across bins; the second is unaffected by duplicate inputs.
+const SPLITTABLE_DISTINCT_AGGREGATES: &[&str] = &[
+    "count",
+    "sum",
+    "array_agg",
+    "median",
+    "bit_xor",
+    "min",
+    "max",
+    "bit_and",
+    "bit_or",
+    "bool_and",
+    "bool_or",
+];
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct AggregateExtension {
     pub(crate) window_behavior: WindowBehavior,
@@ -46,6 +63,7 @@ pub(crate) struct AggregateExtension {
     pub(crate) schema: DFSchemaRef,
     pub(crate) key_fields: Vec<usize>,
     pub(crate) final_calculation: LogicalPlan,
+    pub(crate) sliding_window_emit: SlidingWindowEmit,
 }
 
 multifield_partial_ord!(AggregateExtension, aggregate, key_fields, final_calculation);
@@ -55,6 +73,7 @@ impl AggregateExtension {
         window_behavior: WindowBehavior,
         aggregate: LogicalPlan,
         key_fields: Vec<usize>,
+        sliding_window_emit: SlidingWindowEmit,
     ) -> Self {
         let final_calculation =
             Self::final_projection(&aggregate, window_behavior.clone()).unwrap();
@@ -65,7 +84,33 @@ impl AggregateExtension {
             schema: final_calculation.schema().clone(),
             key_fields,
             final_calculation,
+            sliding_window_emit,
+        }
+    }
+
+    // Windowed aggregates are computed as a partial aggregation per bin that is later merged by
+    // the final aggregation. That's only correct for DISTINCT aggregates if the partial state
+    // retains the distinct values, so we reject any that we can't split safely.
+    fn check_distinct_aggregates(&self) -> Result<()> {
+        let LogicalPlan::Aggregate(aggregate) = &self.aggregate else {
+            return internal_err!("expected aggregate, found {}", self.aggregate.display());
+        };
+
+        for expr in &aggregate.aggr_expr {
+            let Expr::AggregateFunction(function) = expr.clone().unalias_nested().data else {
+                continue;
+            };
+
+            if function.distinct && !SPLITTABLE_DISTINCT_AGGREGATES.contains(&function.func.name())
+            {
+                return plan_err!(
+                    "{}(DISTINCT ...) is not supported in windowed aggregates, as its partial results cannot be merged across bins",
+                    function.func.name()
+                );
+            }
         }
+
+        Ok(())
     }
 
     pub fn tumbling_window_config(
@@ -75,6 +120,7 @@ impl AggregateExtension {
         input_schema: DFSchemaRef,
         width: Duration,
     ) -> Result<LogicalNode> {
+        self.check_distinct_aggregates()?;
         let binning_function_proto = planner.binning_function_proto(width, input_schema.clone())?;
         let SplitPlanOutput {
             partial_aggregation_plan,
@@ -112,7 +158,9 @@ impl AggregateExtension {
             config.encode_to_vec(),
             format!("TumblingWindow<{}>", config.name),
             1,
-        ))
+        )
+        .with_metadata("window", "tumbling")
+        .with_metadata("width", format!("{:?}", width)))
     }
 
     pub fn sliding_window_config(
@@ -123,6 +171,7 @@ impl AggregateExtension {
         width: Duration,
         slide: Duration,
     ) -> Result<LogicalNode> {
+        self.check_distinct_aggregates()?;
         let binning_function_proto = planner.binning_function_proto(slide, input_schema.clone())?;
 
         let SplitPlanOutput {
@@ -153,6 +202,7 @@ impl AggregateExtension {
             partial_aggregation_plan: partial_aggregation_plan.encode_to_vec(),
             final_aggregation_plan: finish_plan.encode_to_vec(),
             final_projection: final_physical_plan_node.encode_to_vec(),
+            emit: self.sliding_window_emit as i32,
             // TODO add final aggregation.
         };
 
@@ -163,6 +213,13 @@ impl AggregateExtension {
             config.encode_to_vec(),
             "sliding window".to_string(),
             1,
+        )
+        .with_metadata("window", "sliding")
+        .with_metadata("width", format!("{:?}", width))
+        .with_metadata("slide", format!("{:?}", slide))
+        .with_metadata(
+            "emit",
+            self.sliding_window_emit.as_str_name().to_lowercase(),
         ))
     }
 
@@ -226,7 +283,9 @@ impl AggregateExtension {
             config.encode_to_vec(),
             config.name.clone(),
             1,
-        ))
+        )
+        .with_metadata("window", "session")
+        .with_metadata("gap", format!("{:?}", gap)))
     }
 
     pub fn instant_window_config(
@@ -284,7 +343,8 @@ impl AggregateExtension {
             config.encode_to_vec(),
             "instant window".to_string(),
             1,
-        ))
+        )
+        .with_metadata("window", "instant"))
     }
 
     // projection assuming that _timestamp has been populated with the start of the bin.
@@ -476,6 +536,7 @@ impl UserDefinedLogicalNodeCore for AggregateExtension {
             self.window_behavior.clone(),
             inputs[0].clone(),
             self.key_fields.clone(),
+            self.sliding_window_emit,
         ))
     }
 }
@@ -23,7 +23,7 @@ use self::updating_aggregate::UpdatingAggregateExtension;
 use self::{
     aggregate::AggregateExtension, key_calculation::KeyCalculationExtension,
     remote_table::RemoteTableExtension, sink::SinkExtension, table_source::TableSourceExtension,
-    window_fn::WindowFunctionExtension,
+    top_n::TopNExtension, window_fn::WindowFunctionExtension,
 };
 use crate::builder::{NamedNode, Planner};
 use crate::extension::lookup::LookupJoin;
@@ -39,6 +39,7 @@ pub(crate) mod lookup;
 pub(crate) mod remote_table;
 pub(crate) mod sink;
 pub(crate) mod table_source;
+pub(crate) mod top_n;
 pub(crate) mod updating_aggregate;
 pub(crate) mod watermark_node;
 pub(crate) mod window_fn;
@@ -84,6 +85,7 @@ impl<'a> TryFrom<&'a dyn UserDefinedLogicalNode> for &'a dyn ArroyoExtension {
             .or_else(|_| try_from_t::<RemoteTableExtension>(node))
             .or_else(|_| try_from_t::<JoinExtension>(node))
             .or_else(|_| try_from_t::<WindowFunctionExtension>(node))
+            .or_else(|_| try_from_t::<TopNExtension>(node))
             .or_else(|_| try_from_t::<AsyncUDFExtension>(node))
             .or_else(|_| try_from_t::<ToDebeziumExtension>(node))
             .or_else(|_| try_from_t::<DebeziumUnrollingExtension>(node))
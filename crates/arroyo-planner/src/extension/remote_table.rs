@@ -56,7 +56,7 @@ impl ArroyoExtension for RemoteTableExtension {
                 // check they are all the same
                 let first = input_schemas[0].clone();
                 for schema in input_schemas.iter().skip(1) {
-                    if *schema != first {
+                    if !schema.structurally_equals(&first) {
                         return plan_err!(
                             "If a node has multiple inputs, they must all have the same schema"
                         );
@@ -3,9 +3,9 @@ use std::sync::Arc;
 use arroyo_datastream::logical::{LogicalEdge, LogicalEdgeType, LogicalNode, OperatorName};
 use arroyo_rpc::{
     df::{ArroyoSchema, ArroyoSchemaRef},
-    UPDATING_META_FIELD,
+    OperatorConfig, UPDATING_META_FIELD,
 };
-use datafusion::common::{plan_err, DFSchemaRef, Result, TableReference};
+use datafusion::common::{plan_err, DFSchemaRef, DataFusionError, Result, TableReference};
 
 use datafusion::logical_expr::{Expr, Extension, LogicalPlan, UserDefinedLogicalNodeCore};
 
@@ -148,22 +148,31 @@ impl ArroyoExtension for SinkExtension {
 
     fn plan_node(
         &self,
-        _planner: &Planner,
+        planner: &Planner,
         index: usize,
         input_schemas: Vec<ArroyoSchemaRef>,
     ) -> Result<NodeWithIncomingEdges> {
-        let operator_config = (self
+        let mut connector_op = self
             .table
             .connector_op()
-            .map_err(|e| e.context("connector op"))?)
-        .encode_to_vec();
+            .map_err(|e| e.context("connector op"))?;
+
+        // sinks need to know how checkpoints are aligned to decide what delivery guarantees
+        // they can provide
+        if let Table::ConnectorTable(_) = &self.table {
+            let mut config: OperatorConfig = serde_json::from_str(&connector_op.config)
+                .map_err(|e| DataFusionError::Plan(format!("invalid sink config: {}", e)))?;
+            config.checkpoint_alignment = planner.planning_options().checkpoint_alignment;
+            connector_op.config = serde_json::to_string(&config).unwrap();
+        }
 
+        let description = connector_op.description.clone();
         let node = LogicalNode::single(
             index as u32,
             format!("sink_{}_{}", self.name, index),
             OperatorName::ConnectorSink,
-            operator_config,
-            self.table.connector_op().unwrap().description.clone(),
+            connector_op.encode_to_vec(),
+            description,
             1,
         );
 
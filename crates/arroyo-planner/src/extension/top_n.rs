@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use arroyo_datastream::logical::{LogicalEdge, LogicalEdgeType, LogicalNode, OperatorName};
+use arroyo_rpc::{df::ArroyoSchema, grpc::api::TopNOperator};
+use datafusion::common::{plan_err, DFSchemaRef, Result};
+use datafusion::logical_expr::{expr::Sort, Expr, LogicalPlan, UserDefinedLogicalNodeCore};
+use datafusion_proto::physical_plan::to_proto::serialize_physical_expr;
+use datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec;
+use datafusion_proto::protobuf::PhysicalSortExprNode;
+use prost::Message;
+
+use crate::builder::{NamedNode, Planner};
+
+use super::{ArroyoExtension, NodeWithIncomingEdges};
+
+pub(crate) const TOP_N_EXTENSION_NAME: &str = "TopNExtension";
+
+/// The largest number of rows that may be retained per window by a top-N query; as the operator
+/// holds the current top rows for every open window in memory, this bounds its memory usage.
+pub(crate) const MAX_TOP_N: usize = 10_000;
+
+/// Computes the first `limit` rows of each window of its (windowed) input, ordered by `sort_exprs`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd)]
+pub(crate) struct TopNExtension {
+    pub(crate) input: LogicalPlan,
+    pub(crate) sort_exprs: Vec<Sort>,
+    pub(crate) limit: usize,
+}
+
+impl TopNExtension {
+    pub fn new(input: LogicalPlan, sort_exprs: Vec<Sort>, limit: usize) -> Self {
+        Self {
+            input,
+            sort_exprs,
+            limit,
+        }
+    }
+}
+
+impl UserDefinedLogicalNodeCore for TopNExtension {
+    fn name(&self) -> &str {
+        TOP_N_EXTENSION_NAME
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        self.input.schema()
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "TopN: limit={} order_by=[{}]",
+            self.limit,
+            self.sort_exprs
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    fn with_exprs_and_inputs(&self, _exprs: Vec<Expr>, inputs: Vec<LogicalPlan>) -> Result<Self> {
+        Ok(Self::new(
+            inputs[0].clone(),
+            self.sort_exprs.clone(),
+            self.limit,
+        ))
+    }
+}
+
+impl ArroyoExtension for TopNExtension {
+    fn node_name(&self) -> Option<NamedNode> {
+        None
+    }
+
+    fn plan_node(
+        &self,
+        planner: &Planner,
+        index: usize,
+        input_schemas: Vec<arroyo_rpc::df::ArroyoSchemaRef>,
+    ) -> Result<NodeWithIncomingEdges> {
+        if input_schemas.len() != 1 {
+            return plan_err!("TopNExtension requires exactly one input");
+        }
+        let input_schema = input_schemas[0].clone();
+
+        let sort_exprs = self
+            .sort_exprs
+            .iter()
+            .map(|sort| {
+                let expr = planner.create_physical_expr(&sort.expr, self.input.schema())?;
+                Ok(PhysicalSortExprNode {
+                    expr: Some(Box::new(serialize_physical_expr(
+                        &expr,
+                        &DefaultPhysicalExtensionCodec {},
+                    )?)),
+                    asc: sort.asc,
+                    nulls_first: sort.nulls_first,
+                }
+                .encode_to_vec())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // every row of a window shares a timestamp, so routing by it sends each window to a
+        // single subtask
+        let routed_schema = ArroyoSchema::new(
+            input_schema.schema.clone(),
+            input_schema.timestamp_index,
+            None,
+            Some(vec![input_schema.timestamp_index]),
+        );
+
+        let config = TopNOperator {
+            name: "TopN".to_string(),
+            input_schema: Some(routed_schema.clone().into()),
+            sort_exprs,
+            limit: self.limit as u64,
+        };
+
+        let logical_node = LogicalNode::single(
+            index as u32,
+            format!("top_n_{}", index),
+            OperatorName::TopN,
+            config.encode_to_vec(),
+            format!("TopN<{}>", self.limit),
+            1,
+        )
+        .with_metadata("limit", self.limit.to_string());
+
+        let edge = LogicalEdge::project_all(LogicalEdgeType::Shuffle, routed_schema);
+
+        Ok(NodeWithIncomingEdges {
+            node: logical_node,
+            edges: vec![edge],
+        })
+    }
+
+    fn output_schema(&self) -> ArroyoSchema {
+        ArroyoSchema::from_schema_unkeyed(Arc::new(self.schema().as_ref().clone().into())).unwrap()
+    }
+}
@@ -27,6 +27,7 @@ pub(crate) struct UpdatingAggregateExtension {
     pub(crate) final_calculation: LogicalPlan,
     pub(crate) timestamp_qualifier: Option<TableReference>,
     pub(crate) ttl: Duration,
+    pub(crate) max_keys: Option<u64>,
 }
 
 impl UpdatingAggregateExtension {
@@ -35,6 +36,7 @@ impl UpdatingAggregateExtension {
         key_fields: Vec<usize>,
         timestamp_qualifier: Option<TableReference>,
         ttl: Duration,
+        max_keys: Option<u64>,
     ) -> Result<Self> {
         let final_calculation = LogicalPlan::Extension(Extension {
             node: Arc::new(IsRetractExtension::new(
@@ -49,6 +51,7 @@ impl UpdatingAggregateExtension {
             final_calculation,
             timestamp_qualifier,
             ttl,
+            max_keys,
         })
     }
 }
@@ -84,6 +87,7 @@ impl UserDefinedLogicalNodeCore for UpdatingAggregateExtension {
             self.key_fields.clone(),
             self.timestamp_qualifier.clone(),
             self.ttl,
+            self.max_keys,
         )
     }
 }
@@ -143,6 +147,7 @@ impl ArroyoExtension for UpdatingAggregateExtension {
                 .update_aggregate_flush_interval
                 .as_micros() as u64,
             ttl_micros: self.ttl.as_micros() as u64,
+            max_keys: self.max_keys,
         };
 
         let node = LogicalNode::single(
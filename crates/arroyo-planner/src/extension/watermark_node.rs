@@ -13,6 +13,7 @@ use datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec;
 use prost::Message;
 use std::fmt::Formatter;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub(crate) const WATERMARK_NODE_NAME: &str = "WatermarkNode";
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -21,6 +22,7 @@ pub struct WatermarkNode {
     pub qualifier: TableReference,
     pub watermark_expression: Expr,
     pub schema: DFSchemaRef,
+    pub throttle_max_skew: Option<Duration>,
     timestamp_index: usize,
 }
 
@@ -29,6 +31,7 @@ multifield_partial_ord!(
     input,
     qualifier,
     watermark_expression,
+    throttle_max_skew,
     timestamp_index
 );
 
@@ -71,6 +74,7 @@ impl UserDefinedLogicalNodeCore for WatermarkNode {
             qualifier: self.qualifier.clone(),
             watermark_expression: exprs.into_iter().next().unwrap(),
             schema: self.schema.clone(),
+            throttle_max_skew: self.throttle_max_skew,
             timestamp_index,
         })
     }
@@ -98,6 +102,9 @@ impl ArroyoExtension for WatermarkNode {
                 idle_time_micros: None,
                 expression: expression.encode_to_vec(),
                 input_schema: Some(self.arroyo_schema().into()),
+                throttle_max_skew_micros: self
+                    .throttle_max_skew
+                    .map(|skew| skew.as_micros() as u64),
             }
             .encode_to_vec(),
             "watermark".to_string(),
@@ -121,6 +128,7 @@ impl WatermarkNode {
         input: LogicalPlan,
         qualifier: TableReference,
         watermark_expression: Expr,
+        throttle_max_skew: Option<Duration>,
     ) -> Result<Self> {
         let schema = add_timestamp_field(input.schema().clone(), Some(qualifier.clone()))?;
         let timestamp_index = schema
@@ -131,6 +139,7 @@ impl WatermarkNode {
             qualifier,
             watermark_expression,
             schema,
+            throttle_max_skew,
             timestamp_index,
         })
     }
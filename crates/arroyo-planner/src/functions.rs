@@ -1,10 +1,11 @@
+use crate::schemas::window_arrow_struct;
 use crate::ArroyoSchemaProvider;
 use arrow::row::{RowConverter, SortField};
 use arrow_array::builder::{FixedSizeBinaryBuilder, ListBuilder, StringBuilder};
 use arrow_array::cast::{as_string_array, AsArray};
 use arrow_array::types::{Float64Type, Int64Type};
 use arrow_array::{Array, ArrayRef, StringArray, UnionArray};
-use arrow_schema::{DataType, Field, UnionFields, UnionMode};
+use arrow_schema::{DataType, Field, TimeUnit, UnionFields, UnionMode};
 use datafusion::common::{DataFusionError, ScalarValue};
 use datafusion::common::{Result, TableReference};
 use datafusion::execution::FunctionRegistry;
@@ -93,6 +94,57 @@ pub fn register_all(registry: &mut dyn FunctionRegistry) {
         .unwrap();
 
     registry.register_udf(multi_hash()).unwrap();
+
+    registry
+        .register_udf(Arc::new(create_udf(
+            "window_start",
+            vec![window_arrow_struct()],
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            Volatility::Immutable,
+            Arc::new(window_start),
+        )))
+        .unwrap();
+
+    registry
+        .register_udf(Arc::new(create_udf(
+            "window_end",
+            vec![window_arrow_struct()],
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            Volatility::Immutable,
+            Arc::new(window_end),
+        )))
+        .unwrap();
+}
+
+fn window_start(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    window_bound("window_start", args, 0)
+}
+
+fn window_end(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    window_bound("window_end", args, 1)
+}
+
+// Flattens one of the fields of a window struct into its own timestamp column, so that
+// windowed queries can output the window boundaries as separate columns
+fn window_bound(name: &str, args: &[ColumnarValue], index: usize) -> Result<ColumnarValue> {
+    let [window] = args else {
+        return Err(DataFusionError::Execution(format!(
+            "{name} takes exactly one argument"
+        )));
+    };
+
+    match window {
+        ColumnarValue::Array(windows) => Ok(ColumnarValue::Array(
+            windows.as_struct().column(index).clone(),
+        )),
+        ColumnarValue::Scalar(ScalarValue::Struct(window)) => Ok(ColumnarValue::Scalar(
+            ScalarValue::try_from_array(window.column(index), 0)?,
+        )),
+        ColumnarValue::Scalar(s) => Err(DataFusionError::Execution(format!(
+            "{name} must be called on a window, not {}",
+            s.data_type()
+        ))),
+    }
 }
 
 fn parse_path(name: &str, path: &ScalarValue) -> Result<Arc<JsonPath>> {
@@ -457,7 +509,8 @@ pub(crate) fn serialize_outgoing_json(
 #[cfg(test)]
 mod test {
     use arrow_array::builder::{ListBuilder, StringBuilder};
-    use arrow_array::StringArray;
+    use arrow_array::{StringArray, StructArray, TimestampNanosecondArray};
+    use arrow_schema::DataType;
     use datafusion::common::ScalarValue;
     use std::sync::Arc;
 
@@ -503,6 +556,52 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_window_bounds() {
+        let window_type = crate::schemas::window_arrow_struct();
+        let DataType::Struct(fields) = &window_type else {
+            panic!("window should be a struct");
+        };
+
+        let start = Arc::new(TimestampNanosecondArray::from(vec![0, 60_000_000_000]));
+        let end = Arc::new(TimestampNanosecondArray::from(vec![
+            60_000_000_000,
+            120_000_000_000,
+        ]));
+        let windows = Arc::new(StructArray::new(
+            fields.clone(),
+            vec![start.clone(), end.clone()],
+            None,
+        ));
+
+        let super::ColumnarValue::Array(result) =
+            super::window_start(&[super::ColumnarValue::Array(windows.clone())]).unwrap()
+        else {
+            panic!("Expected array, got scalar");
+        };
+        assert_eq!(*result, *start);
+
+        let super::ColumnarValue::Array(result) =
+            super::window_end(&[super::ColumnarValue::Array(windows.clone())]).unwrap()
+        else {
+            panic!("Expected array, got scalar");
+        };
+        assert_eq!(*result, *end);
+
+        let window = ScalarValue::Struct(Arc::new(windows.slice(1, 1)));
+        let super::ColumnarValue::Scalar(result) =
+            super::window_end(&[super::ColumnarValue::Scalar(window)]).unwrap()
+        else {
+            panic!("Expected scalar");
+        };
+        assert_eq!(
+            result,
+            ScalarValue::TimestampNanosecond(Some(120_000_000_000), None)
+        );
+
+        assert!(super::window_start(&[super::ColumnarValue::Scalar(1i64.into())]).is_err());
+    }
+
     #[test]
     fn test_get_first_json_object() {
         let input = Arc::new(StringArray::from(vec![
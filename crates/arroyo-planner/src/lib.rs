@@ -53,7 +53,7 @@ use crate::plan::ArroyoRewriter;
 use arroyo_datastream::logical::{DylibUdfConfig, ProgramConfig, PythonUdfConfig};
 use arroyo_rpc::api_types::connections::ConnectionProfile;
 use datafusion::common::DataFusionError;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::{Debug, Formatter};
 
 use crate::functions::{is_json_union, serialize_outgoing_json};
@@ -65,6 +65,7 @@ use arroyo_datastream::logical::LogicalProgram;
 use arroyo_datastream::optimizers::ChainingOptimizer;
 use arroyo_operator::connector::Connection;
 use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::api::{CheckpointAlignment, SlidingWindowEmit};
 use arroyo_rpc::{duration_from_sql, TIMESTAMP_FIELD};
 use arroyo_udf_host::parse::{inner_type, UdfDef};
 use arroyo_udf_host::ParsedUdfFile;
@@ -75,10 +76,11 @@ use datafusion::logical_expr;
 use datafusion::logical_expr::expr_rewriter::FunctionRewrite;
 use datafusion::logical_expr::planner::ExprPlanner;
 use datafusion::optimizer::Analyzer;
-use sqlparser::ast::{OneOrManyWithParens, Statement};
+use sqlparser::ast::{Expr as SqlExpr, OneOrManyWithParens, Statement, Value as SqlValue};
 use sqlparser::dialect::ArroyoDialect;
 use sqlparser::parser::{Parser, ParserError};
 use std::any::Any;
+use std::sync::OnceLock;
 use std::time::{Duration, SystemTime};
 use std::{collections::HashMap, sync::Arc};
 use syn::Item;
@@ -92,17 +94,77 @@ pub const ASYNC_RESULT_FIELD: &str = "__async_result";
 pub struct CompiledSql {
     pub program: LogicalProgram,
     pub connection_ids: Vec<i64>,
+    pub stats: PlanStats,
+}
+
+/// Operator configs larger than this are likely to run into gRPC message limits when the
+/// pipeline is scheduled
+const LARGE_OPERATOR_CONFIG_BYTES: usize = 4 * 1024 * 1024;
+
+/// Sizes of a compiled program, for diagnosing pipelines whose plans are too large to schedule
+#[derive(Clone, Debug, Default)]
+pub struct PlanStats {
+    pub node_count: usize,
+    pub edge_count: usize,
+    /// Encoded size of the operator configs in each node's chain, by node id
+    pub node_config_bytes: BTreeMap<u32, usize>,
+}
+
+impl PlanStats {
+    fn for_program(program: &LogicalProgram) -> Self {
+        let node_config_bytes: BTreeMap<_, _> = program
+            .graph
+            .node_weights()
+            .map(|node| {
+                let size = node
+                    .operator_chain
+                    .iter()
+                    .map(|(op, _)| op.operator_config.len())
+                    .sum::<usize>();
+
+                if size > LARGE_OPERATOR_CONFIG_BYTES {
+                    warn!(
+                        "operator config for node {} ({}) is {} bytes, which may exceed message size limits",
+                        node.node_id, node.description, size
+                    );
+                }
+
+                (node.node_id, size)
+            })
+            .collect();
+
+        Self {
+            node_count: program.graph.node_count(),
+            edge_count: program.graph.edge_count(),
+            node_config_bytes,
+        }
+    }
+
+    pub fn total_config_bytes(&self) -> usize {
+        self.node_config_bytes.values().sum()
+    }
 }
 
 #[derive(Clone)]
 pub struct PlanningOptions {
+    /// How long updating aggregates and joins keep the state for a key that stops appearing,
+    /// set with `SET updating_ttl`. This bounds memory for high-cardinality keys, at the cost
+    /// of correctness for keys that reappear after expiring, which start again from empty state.
     ttl: Duration,
+    watermark_throttle_max_skew: Option<Duration>,
+    checkpoint_alignment: CheckpointAlignment,
+    updating_max_keys: Option<u64>,
+    sliding_window_emit: SlidingWindowEmit,
 }
 
 impl Default for PlanningOptions {
     fn default() -> Self {
         Self {
             ttl: Duration::from_secs(24 * 60 * 60),
+            watermark_throttle_max_skew: None,
+            checkpoint_alignment: CheckpointAlignment::Aligned,
+            updating_max_keys: None,
+            sliding_window_emit: SlidingWindowEmit::OnSlide,
         }
     }
 }
@@ -149,6 +211,36 @@ pub fn register_functions(registry: &mut dyn FunctionRegistry) {
     }
 }
 
+/// Returns whether `name` refers to a built-in scalar, aggregate, or window function (including
+/// aliases), which a user-defined function of the same name would shadow
+pub fn is_builtin_function(name: &str) -> bool {
+    static BUILTINS: OnceLock<HashSet<String>> = OnceLock::new();
+
+    BUILTINS
+        .get_or_init(|| {
+            let provider = ArroyoSchemaProvider::new();
+            let mut names = HashSet::new();
+
+            for f in provider.functions.values() {
+                names.insert(f.name().to_lowercase());
+                names.extend(f.aliases().iter().map(|a| a.to_lowercase()));
+            }
+
+            for f in provider.aggregate_functions.values() {
+                names.insert(f.name().to_lowercase());
+                names.extend(f.aliases().iter().map(|a| a.to_lowercase()));
+            }
+
+            for f in provider.window_functions.values() {
+                names.insert(f.name().to_lowercase());
+                names.extend(f.aliases().iter().map(|a| a.to_lowercase()));
+            }
+
+            names
+        })
+        .contains(&name.to_lowercase())
+}
+
 /// A UDF implementation that exists only at plan time, and which will be re-planned
 /// into some other form before execution
 #[allow(clippy::type_complexity)]
@@ -753,25 +845,106 @@ fn try_handle_set_variable(
             return plan_err!("invalid syntax for `SET` call");
         };
 
-        if opt.to_string() != "updating_ttl" {
+        if value.len() != 1 {
             return plan_err!(
-                "invalid option '{}'; supported options are 'updating_ttl'",
+                "invalid `SET {}` call; expected exactly one expression",
                 opt
             );
         }
 
-        if value.len() != 1 {
-            return plan_err!("invalid `SET updating_ttl` call; expected exactly one expression");
+        match opt.to_string().as_str() {
+            "updating_ttl" => {
+                schema_provider.planning_options.ttl = duration_from_sql(value[0].clone())?;
+            }
+            "watermark_throttle_max_skew" => {
+                schema_provider.planning_options.watermark_throttle_max_skew =
+                    Some(duration_from_sql(value[0].clone())?);
+            }
+            "checkpoint_alignment" => {
+                schema_provider.planning_options.checkpoint_alignment = parse_string_option(
+                    "checkpoint_alignment",
+                    &value[0],
+                    &[
+                        ("aligned", CheckpointAlignment::Aligned),
+                        ("at_least_once", CheckpointAlignment::AtLeastOnce),
+                    ],
+                )?;
+            }
+            "updating_max_keys" => {
+                schema_provider.planning_options.updating_max_keys = match &value[0] {
+                    SqlExpr::Value(SqlValue::Number(n, _)) => match n.parse::<u64>() {
+                        Ok(n) if n > 0 => Some(n),
+                        _ => {
+                            return plan_err!(
+                                "invalid value {} for updating_max_keys; expected a positive integer",
+                                n
+                            );
+                        }
+                    },
+                    v => {
+                        return plan_err!(
+                            "invalid value {} for updating_max_keys; expected a positive integer",
+                            v
+                        );
+                    }
+                };
+            }
+            "sliding_window_emit" => {
+                schema_provider.planning_options.sliding_window_emit = parse_string_option(
+                    "sliding_window_emit",
+                    &value[0],
+                    &[
+                        ("on_slide", SlidingWindowEmit::OnSlide),
+                        ("on_window_close", SlidingWindowEmit::OnWindowClose),
+                    ],
+                )?;
+            }
+            _ => {
+                return plan_err!(
+                    "invalid option '{}'; supported options are 'updating_ttl', 'watermark_throttle_max_skew', 'checkpoint_alignment', 'updating_max_keys', and 'sliding_window_emit'",
+                    opt
+                );
+            }
         }
 
-        schema_provider.planning_options.ttl = duration_from_sql(value[0].clone())?;
-
         return Ok(true);
     }
 
     Ok(false)
 }
 
+/// Parses the value of a `SET` option that is one of a fixed set of strings, compared
+/// case-insensitively
+fn parse_string_option<T: Copy>(option: &str, value: &SqlExpr, choices: &[(&str, T)]) -> Result<T> {
+    let expected = choices
+        .iter()
+        .map(|(name, _)| format!("'{}'", name))
+        .collect::<Vec<_>>()
+        .join(" or ");
+
+    let SqlExpr::Value(SqlValue::SingleQuotedString(s)) = value else {
+        return plan_err!(
+            "invalid value {} for {}; expected {}",
+            value,
+            option,
+            expected
+        );
+    };
+
+    match choices
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(s))
+    {
+        Some((_, choice)) => Ok(*choice),
+        None => plan_err!(
+            "invalid value '{}' for {}; expected {}",
+            s,
+            option,
+            expected
+        ),
+    }
+}
+
 pub(crate) fn parse_sql(sql: &str) -> Result<Vec<Statement>, ParserError> {
     Parser::parse_sql(&ArroyoDialect {}, sql)
 }
@@ -907,6 +1080,7 @@ pub async fn parse_and_get_arrow_program(
         ProgramConfig {
             udf_dylibs: schema_provider.dylib_udfs.clone(),
             python_udfs: schema_provider.python_udfs.clone(),
+            checkpoint_alignment: schema_provider.planning_options.checkpoint_alignment,
         },
     );
 
@@ -914,9 +1088,18 @@ pub async fn parse_and_get_arrow_program(
         program.optimize(&ChainingOptimizer {});
     }
 
+    let stats = PlanStats::for_program(&program);
+    debug!(
+        "compiled program with {} nodes and {} edges; operator configs total {} bytes",
+        stats.node_count,
+        stats.edge_count,
+        stats.total_config_bytes()
+    );
+
     Ok(CompiledSql {
         program,
         connection_ids: used_connections.into_iter().collect(),
+        stats,
     })
 }
 
@@ -104,6 +104,7 @@ impl AggregateRewriter<'_> {
             (0..key_count).collect(),
             column.relation,
             schema_provider.planning_options.ttl,
+            schema_provider.planning_options.updating_max_keys,
         )?;
         let final_plan = LogicalPlan::Extension(Extension {
             node: Arc::new(updating_aggregate_extension),
@@ -126,6 +127,15 @@ impl TreeNodeRewriter for AggregateRewriter<'_> {
         else {
             return Ok(Transformed::no(node));
         };
+
+        // grouping sets add a synthetic grouping id column and produce multiple rows per input
+        // key, which the partial/final split of the window operators does not account for
+        if group_expr.iter().any(|e| matches!(e, Expr::GroupingSet(_))) {
+            return not_impl_err!(
+                "GROUPING SETS, CUBE, and ROLLUP are not supported in streaming aggregates"
+            );
+        }
+
         let mut window_group_expr: Vec<_> = group_expr
             .iter()
             .enumerate()
@@ -274,6 +284,7 @@ impl TreeNodeRewriter for AggregateRewriter<'_> {
             window_behavior,
             LogicalPlan::Aggregate(rewritten_aggregate),
             (0..key_count).collect(),
+            self.schema_provider.planning_options.sliding_window_emit,
         );
         let final_plan = LogicalPlan::Extension(Extension {
             node: Arc::new(aggregate_extension),
@@ -14,6 +14,7 @@ use datafusion::logical_expr::{
 };
 use join::JoinRewriter;
 
+use self::top_n::TopNRewriter;
 use self::window_fn::WindowFunctionRewriter;
 use crate::rewriters::TimeWindowNullCheckRemover;
 use crate::{
@@ -34,6 +35,7 @@ use crate::{
 
 mod aggregate;
 mod join;
+mod top_n;
 mod window_fn;
 
 #[derive(Debug, Default)]
@@ -341,7 +343,7 @@ impl TreeNodeRewriter for ArroyoRewriter<'_> {
                 return WindowFunctionRewriter {}.f_up(node);
             }
             LogicalPlan::Sort(_) => {
-                return plan_err!("ORDER BY is not currently supported ({})", node.display());
+                return TopNRewriter {}.f_up(node);
             }
             LogicalPlan::Repartition(_) => {
                 return plan_err!(
@@ -382,7 +384,7 @@ impl TreeNodeRewriter for ArroyoRewriter<'_> {
                 )));
             }
             LogicalPlan::Limit(_) => {
-                return plan_err!("LIMIT is not currently supported ({})", node.display());
+                return TopNRewriter {}.f_up(node);
             }
             LogicalPlan::Statement(s) => {
                 return plan_err!("Unsupported statement: {}", s.display());
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use arroyo_datastream::WindowType;
+use datafusion::common::tree_node::Transformed;
+use datafusion::common::{plan_err, tree_node::TreeNodeRewriter, Result as DFResult};
+use datafusion::logical_expr::{Extension, LogicalPlan, SkipType, Sort};
+
+use crate::extension::top_n::{TopNExtension, MAX_TOP_N, TOP_N_EXTENSION_NAME};
+
+use super::WindowDetectingVisitor;
+
+/// Rewrites an `ORDER BY ... LIMIT n` over windowed input into a per-window top-n. The limit
+/// has already been pushed into the sort by the optimizer, so the remaining `Limit` node is
+/// removed once its input has been rewritten.
+pub(crate) struct TopNRewriter {}
+
+impl TreeNodeRewriter for TopNRewriter {
+    type Node = LogicalPlan;
+
+    fn f_up(&mut self, node: Self::Node) -> DFResult<Transformed<Self::Node>> {
+        match node {
+            LogicalPlan::Sort(Sort { expr, input, fetch }) => {
+                let Some(limit) = fetch else {
+                    return plan_err!(
+                        "ORDER BY is only supported with a LIMIT over windowed input ({})",
+                        LogicalPlan::Sort(Sort { expr, input, fetch }).display()
+                    );
+                };
+
+                let mut window_detecting_visitor = WindowDetectingVisitor::default();
+                input.visit_with_subqueries(&mut window_detecting_visitor)?;
+
+                match window_detecting_visitor.window {
+                    None => {
+                        return plan_err!("ORDER BY with LIMIT requires windowed input");
+                    }
+                    Some(WindowType::Session { .. }) => {
+                        return plan_err!("ORDER BY with LIMIT does not support session windows");
+                    }
+                    Some(_) => {}
+                }
+
+                if limit > MAX_TOP_N {
+                    return plan_err!(
+                        "LIMIT {} over a window is too large; at most {} rows may be retained per window",
+                        limit,
+                        MAX_TOP_N
+                    );
+                }
+
+                Ok(Transformed::yes(LogicalPlan::Extension(Extension {
+                    node: Arc::new(TopNExtension::new(input.as_ref().clone(), expr, limit)),
+                })))
+            }
+            LogicalPlan::Limit(limit) => {
+                let LogicalPlan::Extension(Extension { node }) = limit.input.as_ref() else {
+                    return plan_err!(
+                        "LIMIT is only supported with an ORDER BY over windowed input ({})",
+                        LogicalPlan::Limit(limit).display()
+                    );
+                };
+
+                if node.name() != TOP_N_EXTENSION_NAME {
+                    return plan_err!(
+                        "LIMIT is only supported with an ORDER BY over windowed input ({})",
+                        LogicalPlan::Limit(limit).display()
+                    );
+                }
+
+                if !matches!(limit.get_skip_type()?, SkipType::Literal(0)) {
+                    return plan_err!("OFFSET is not supported with ORDER BY over a window");
+                }
+
+                Ok(Transformed::yes(limit.input.as_ref().clone()))
+            }
+            _ => Ok(Transformed::no(node)),
+        }
+    }
+}
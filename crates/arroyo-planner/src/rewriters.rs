@@ -26,7 +26,7 @@ use datafusion::common::tree_node::{
     Transformed, TreeNode, TreeNodeRecursion, TreeNodeRewriter, TreeNodeVisitor,
 };
 use datafusion::common::{
-    plan_err, Column, DataFusionError, Result as DFResult, ScalarValue, TableReference,
+    plan_err, Column, DFSchema, DataFusionError, Result as DFResult, ScalarValue, TableReference,
 };
 use datafusion::logical_expr;
 use datafusion::logical_expr::expr::ScalarFunction;
@@ -154,6 +154,16 @@ impl SourceRewriter<'_> {
     fn projection(&self, table_scan: &TableScan, table: &ConnectorTable) -> DFResult<LogicalPlan> {
         let qualifier = table_scan.table_name.clone();
 
+        if let Some(projection) = &table_scan.projection {
+            let source_fields: Vec<_> = table.fields.iter().map(|f| f.field().name()).collect();
+            validate_projection(
+                &qualifier,
+                projection,
+                &source_fields,
+                &table_scan.projected_schema,
+            )?;
+        }
+
         let table_source_extension = LogicalPlan::Extension(Extension {
             node: Arc::new(TableSourceExtension::new(
                 qualifier.to_owned(),
@@ -206,6 +216,9 @@ impl SourceRewriter<'_> {
             remote,
             table_scan.table_name.clone(),
             Self::watermark_expression(table)?,
+            self.schema_provider
+                .planning_options
+                .watermark_throttle_max_skew,
         )
         .map_err(|err| {
             DataFusionError::Internal(format!("failed to create watermark expression: {}", err))
@@ -234,23 +247,17 @@ impl SourceRewriter<'_> {
         table_scan: &TableScan,
         logical_plan: &LogicalPlan,
     ) -> DFResult<Transformed<LogicalPlan>> {
+        let fields = fields_with_qualifiers(logical_plan.schema());
         let column_expressions: Vec<_> = if let Some(projection) = &table_scan.projection {
-            fields_with_qualifiers(logical_plan.schema())
+            // the expressions are aliased to the projected schema positionally, so they must be
+            // in projection order
+            projection
                 .iter()
-                .enumerate()
-                .filter_map(|(i, f)| {
-                    if projection.contains(&i) {
-                        Some(Expr::Column(Column::new(
-                            f.qualifier().cloned(),
-                            f.name().to_string(),
-                        )))
-                    } else {
-                        None
-                    }
-                })
+                .filter_map(|i| fields.get(*i))
+                .map(|f| Expr::Column(Column::new(f.qualifier().cloned(), f.name().to_string())))
                 .collect()
         } else {
-            fields_with_qualifiers(logical_plan.schema())
+            fields
                 .iter()
                 .map(|f| Expr::Column(Column::new(f.qualifier().cloned(), f.name().to_string())))
                 .collect()
@@ -271,6 +278,47 @@ impl SourceRewriter<'_> {
     }
 }
 
+/// Checks that a table scan's projection refers to columns of its source, and that the columns
+/// it selects are the ones the scan's projected schema expects. A stale projection would otherwise
+/// silently read the wrong columns.
+pub(crate) fn validate_projection(
+    table: &TableReference,
+    projection: &[usize],
+    source_fields: &[&String],
+    projected_schema: &DFSchema,
+) -> DFResult<()> {
+    if let Some(index) = projection.iter().find(|i| **i >= source_fields.len()) {
+        return plan_err!(
+            "projection of table {} refers to column {}, but it only has {} columns",
+            table,
+            index,
+            source_fields.len()
+        );
+    }
+
+    if projection.len() != projected_schema.fields().len() {
+        return plan_err!(
+            "projection of table {} selects {} columns, but its scan expects {}",
+            table,
+            projection.len(),
+            projected_schema.fields().len()
+        );
+    }
+
+    for (index, expected) in projection.iter().zip(projected_schema.fields()) {
+        if source_fields[*index] != expected.name() {
+            return plan_err!(
+                "projection of table {} selects column '{}' where its scan expects '{}'",
+                table,
+                source_fields[*index],
+                expected.name()
+            );
+        }
+    }
+
+    Ok(())
+}
+
 impl TreeNodeRewriter for SourceRewriter<'_> {
     type Node = LogicalPlan;
 
@@ -310,7 +358,19 @@ impl TreeNodeRewriter for SourceRewriter<'_> {
             Table::TableFromQuery {
                 name: _,
                 logical_plan,
-            } => self.mutate_table_from_query(&table_scan, logical_plan),
+            } => {
+                if let Some(projection) = &table_scan.projection {
+                    let fields = fields_with_qualifiers(logical_plan.schema());
+                    let source_fields: Vec<_> = fields.iter().map(|f| f.name()).collect();
+                    validate_projection(
+                        &table_scan.table_name,
+                        projection,
+                        &source_fields,
+                        &table_scan.projected_schema,
+                    )?;
+                }
+                self.mutate_table_from_query(&table_scan, logical_plan)
+            }
             Table::PreviewSink { .. } => Err(DataFusionError::Plan(
                 "can't select from a preview sink".to_string(),
             )),
@@ -1,15 +1,26 @@
 mod plan_tests;
 
-use arrow_schema::DataType;
+use arrow_schema::{DataType, Field, Schema};
 use arroyo_connectors::{
     nexmark::{NexmarkConnector, NexmarkTable},
     EmptyConfig,
 };
+use arroyo_datastream::logical::{LogicalEdgeType, LogicalProgram, OperatorName};
 use arroyo_operator::connector::Connector;
+use arroyo_rpc::grpc::api::{
+    ArrowProgram, CheckpointAlignment, ConnectorOp, ExpressionWatermarkConfig,
+    SlidingWindowAggregateOperator, SlidingWindowEmit, UpdatingAggregateOperator,
+};
+use arroyo_rpc::OperatorConfig;
 use arroyo_udf_host::parse::NullableType;
+use datafusion::common::{DFSchema, TableReference};
+use petgraph::algo::has_path_connecting;
+use petgraph::Direction;
+use prost::Message;
 use test_log::test;
 
-use crate::{parse_and_get_program, ArroyoSchemaProvider, SqlConfig};
+use crate::rewriters::validate_projection;
+use crate::{is_builtin_function, parse_and_get_program, ArroyoSchemaProvider, SqlConfig};
 
 fn get_test_schema_provider() -> ArroyoSchemaProvider {
     let mut schema_provider = ArroyoSchemaProvider::new();
@@ -58,3 +69,457 @@ async fn test_udf() {
         .await
         .unwrap();
 }
+
+#[test(tokio::test)]
+async fn test_window_node_metadata() {
+    let sql = "SELECT bid.auction, count(*) FROM nexmark \
+        WHERE bid IS NOT NULL \
+        GROUP BY 1, tumble(INTERVAL '1 minute')";
+
+    let program = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap()
+        .program;
+
+    let node = program
+        .graph
+        .node_weights()
+        .find(|n| n.operator_chain.first().operator_name == OperatorName::TumblingWindowAggregate)
+        .expect("no tumbling window node");
+
+    assert_eq!(node.metadata.get("window").unwrap(), "tumbling");
+    assert_eq!(node.metadata.get("width").unwrap(), "60s");
+    assert!(node.metadata.contains_key("output_schema"));
+}
+
+#[test(tokio::test)]
+async fn test_plan_stats() {
+    let sql = "SELECT bid.auction, count(*) FROM nexmark \
+        WHERE bid IS NOT NULL \
+        GROUP BY 1, tumble(INTERVAL '1 minute')";
+
+    let compiled = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    let graph = &compiled.program.graph;
+    assert_eq!(compiled.stats.node_count, graph.node_count());
+    assert_eq!(compiled.stats.edge_count, graph.edge_count());
+    assert_eq!(compiled.stats.node_config_bytes.len(), graph.node_count());
+
+    for node in graph.node_weights() {
+        let expected: usize = node
+            .operator_chain
+            .iter()
+            .map(|(op, _)| op.operator_config.len())
+            .sum();
+        assert_eq!(compiled.stats.node_config_bytes[&node.node_id], expected);
+    }
+    assert!(compiled.stats.total_config_bytes() > 0);
+}
+
+#[test(tokio::test)]
+async fn test_group_by_expression() {
+    let sql = "SELECT lower(bid.channel) as channel, count(*) FROM nexmark \
+        WHERE bid IS NOT NULL \
+        GROUP BY lower(bid.channel), tumble(INTERVAL '1 minute')";
+
+    let program = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap()
+        .program;
+
+    let graph = &program.graph;
+    let window = graph
+        .node_indices()
+        .find(|n| {
+            graph[*n].operator_chain.first().operator_name == OperatorName::TumblingWindowAggregate
+        })
+        .expect("no tumbling window node");
+
+    // the grouping expression is computed into a key column before the shuffle into the window
+    let edge = graph
+        .edges_directed(window, Direction::Incoming)
+        .next()
+        .expect("window has no input");
+    assert_eq!(edge.weight().edge_type, LogicalEdgeType::Shuffle);
+
+    let key_columns = edge.weight().schema.storage_keys().unwrap();
+    assert_eq!(key_columns.len(), 1);
+    let key_field = edge.weight().schema.schema.field(key_columns[0]);
+    assert!(
+        key_field.name().starts_with("_key_"),
+        "{}",
+        key_field.name()
+    );
+    assert_eq!(key_field.data_type(), &DataType::Utf8);
+}
+
+#[test(tokio::test)]
+async fn test_window_top_n() {
+    let sql = "SELECT auction, window, count FROM (\
+        SELECT bid.auction as auction, tumble(INTERVAL '1 hour') as window, count(*) as count \
+        FROM nexmark \
+        WHERE bid IS NOT NULL \
+        GROUP BY 1, 2) \
+        ORDER BY count DESC \
+        LIMIT 5";
+
+    let program = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap()
+        .program;
+
+    let node = program
+        .graph
+        .node_weights()
+        .find(|n| n.operator_chain.first().operator_name == OperatorName::TopN)
+        .expect("no top-n node");
+
+    assert_eq!(node.metadata.get("limit").unwrap(), "5");
+}
+
+#[test]
+fn test_validate_projection() {
+    let table = TableReference::bare("events");
+    let (id, name, value) = ("id".to_string(), "name".to_string(), "value".to_string());
+    let source_fields = vec![&id, &name, &value];
+
+    let projected = DFSchema::try_from(Schema::new(vec![
+        Field::new("value", DataType::Int64, true),
+        Field::new("id", DataType::Int64, true),
+    ]))
+    .unwrap();
+
+    validate_projection(&table, &[2, 0], &source_fields, &projected).unwrap();
+
+    let err = validate_projection(&table, &[2, 3], &source_fields, &projected).unwrap_err();
+    assert!(err.to_string().contains("refers to column 3"), "{}", err);
+
+    let err = validate_projection(&table, &[2], &source_fields, &projected).unwrap_err();
+    assert!(err.to_string().contains("selects 1 columns"), "{}", err);
+
+    let err = validate_projection(&table, &[1, 0], &source_fields, &projected).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("selects column 'name' where its scan expects 'value'"),
+        "{}",
+        err
+    );
+}
+
+#[test(tokio::test)]
+async fn test_watermark_throttling() {
+    let mut schema_provider = get_test_schema_provider();
+    let second = (NexmarkConnector {})
+        .from_config(
+            Some(2),
+            "nexmark_b",
+            EmptyConfig {},
+            NexmarkTable {
+                event_rate: 10.0,
+                runtime: Some(10.0 * 1_000_000.0),
+            },
+            None,
+        )
+        .unwrap();
+    schema_provider.add_connector_table(second);
+
+    let sql = "SET watermark_throttle_max_skew = INTERVAL '10 seconds';
+        SELECT bid.auction FROM nexmark WHERE bid IS NOT NULL
+        UNION ALL
+        SELECT bid.auction FROM nexmark_b WHERE bid IS NOT NULL";
+
+    let program = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap()
+        .program;
+
+    let configs: Vec<_> = program
+        .graph
+        .node_weights()
+        .flat_map(|n| n.operator_chain.iter())
+        .filter(|(op, _)| op.operator_name == OperatorName::ExpressionWatermark)
+        .map(|(op, _)| ExpressionWatermarkConfig::decode(op.operator_config.as_slice()).unwrap())
+        .collect();
+
+    assert_eq!(configs.len(), 2);
+    for config in configs {
+        assert_eq!(config.throttle_max_skew_micros, Some(10_000_000));
+    }
+}
+
+#[test(tokio::test)]
+async fn test_checkpoint_alignment() {
+    let sql = "CREATE TABLE out (auction BIGINT) WITH (connector = 'blackhole');
+        INSERT INTO out SELECT bid.auction FROM nexmark WHERE bid IS NOT NULL";
+
+    // the sinks' configs carry the mode, so they can adjust their delivery guarantees
+    let sink_alignments = |program: &LogicalProgram| -> Vec<_> {
+        program
+            .graph
+            .node_weights()
+            .flat_map(|n| n.operator_chain.iter())
+            .filter(|(op, _)| op.operator_name == OperatorName::ConnectorSink)
+            .map(|(op, _)| {
+                let op = ConnectorOp::decode(op.operator_config.as_slice()).unwrap();
+                serde_json::from_str::<OperatorConfig>(&op.config)
+                    .unwrap()
+                    .checkpoint_alignment
+            })
+            .collect()
+    };
+
+    let program = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap()
+        .program;
+    assert_eq!(
+        program.program_config.checkpoint_alignment,
+        CheckpointAlignment::Aligned
+    );
+    assert_eq!(
+        sink_alignments(&program),
+        vec![CheckpointAlignment::Aligned]
+    );
+
+    let sql = format!("SET checkpoint_alignment = 'at_least_once'; {}", sql);
+    let program = parse_and_get_program(&sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap()
+        .program;
+    assert_eq!(
+        program.program_config.checkpoint_alignment,
+        CheckpointAlignment::AtLeastOnce
+    );
+    assert_eq!(
+        sink_alignments(&program),
+        vec![CheckpointAlignment::AtLeastOnce]
+    );
+
+    // the mode is carried to the workers with the rest of the program
+    let program: LogicalProgram = ArrowProgram::from(program).try_into().unwrap();
+    assert_eq!(
+        program.program_config.checkpoint_alignment,
+        CheckpointAlignment::AtLeastOnce
+    );
+    assert_eq!(
+        sink_alignments(&program),
+        vec![CheckpointAlignment::AtLeastOnce]
+    );
+
+    let sql = "SET checkpoint_alignment = 'unaligned'; SELECT 1";
+    let Err(err) =
+        parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default()).await
+    else {
+        panic!("expected invalid checkpoint_alignment to fail");
+    };
+    assert!(
+        err.to_string().contains("'aligned' or 'at_least_once'"),
+        "{}",
+        err
+    );
+}
+
+#[test(tokio::test)]
+async fn test_updating_max_keys() {
+    async fn max_keys(sql: &str) -> Vec<Option<u64>> {
+        parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+            .await
+            .unwrap()
+            .program
+            .graph
+            .node_weights()
+            .flat_map(|n| n.operator_chain.iter())
+            .filter(|(op, _)| op.operator_name == OperatorName::UpdatingAggregate)
+            .map(|(op, _)| {
+                UpdatingAggregateOperator::decode(op.operator_config.as_slice())
+                    .unwrap()
+                    .max_keys
+            })
+            .collect()
+    }
+
+    let sql = "SELECT bid.auction, count(*) FROM nexmark WHERE bid IS NOT NULL GROUP BY 1";
+    assert_eq!(max_keys(sql).await, vec![None]);
+    assert_eq!(
+        max_keys(&format!("SET updating_max_keys = 100000; {}", sql)).await,
+        vec![Some(100000)]
+    );
+
+    let sql = "SET updating_max_keys = 0; SELECT 1";
+    let Err(err) =
+        parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default()).await
+    else {
+        panic!("expected invalid updating_max_keys to fail");
+    };
+    assert!(err.to_string().contains("positive integer"), "{}", err);
+}
+
+#[test(tokio::test)]
+async fn test_sliding_window_emit() {
+    async fn emit(sql: &str) -> Vec<SlidingWindowEmit> {
+        parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+            .await
+            .unwrap()
+            .program
+            .graph
+            .node_weights()
+            .flat_map(|n| n.operator_chain.iter())
+            .filter(|(op, _)| op.operator_name == OperatorName::SlidingWindowAggregate)
+            .map(|(op, _)| {
+                SlidingWindowAggregateOperator::decode(op.operator_config.as_slice())
+                    .unwrap()
+                    .emit()
+            })
+            .collect()
+    }
+
+    let sql = "SELECT hop(interval '1 minute', interval '10 minutes') as window, count(*)
+        FROM nexmark GROUP BY 1";
+    assert_eq!(emit(sql).await, vec![SlidingWindowEmit::OnSlide]);
+    assert_eq!(
+        emit(&format!(
+            "SET sliding_window_emit = 'on_window_close'; {}",
+            sql
+        ))
+        .await,
+        vec![SlidingWindowEmit::OnWindowClose]
+    );
+
+    let sql = "SET sliding_window_emit = 'eventually'; SELECT 1";
+    let Err(err) =
+        parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default()).await
+    else {
+        panic!("expected invalid sliding_window_emit to fail");
+    };
+    assert!(
+        err.to_string().contains("'on_slide' or 'on_window_close'"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_is_builtin_function() {
+    assert!(is_builtin_function("coalesce"));
+    assert!(is_builtin_function("DATE_BIN"));
+    assert!(is_builtin_function("count"));
+    assert!(is_builtin_function("row_number"));
+    assert!(is_builtin_function("tumble"));
+    assert!(!is_builtin_function("my_sqr"));
+}
+
+#[test(tokio::test)]
+async fn test_fan_out_to_multiple_sinks() {
+    let sql = "CREATE TABLE live (auction BIGINT, price BIGINT) WITH (connector = 'blackhole');
+        CREATE TABLE archive (auction BIGINT, price BIGINT) WITH (connector = 'blackhole');
+        CREATE VIEW bids AS SELECT bid.auction as auction, bid.price as price
+            FROM nexmark WHERE bid IS NOT NULL;
+        INSERT INTO live SELECT * FROM bids;
+        INSERT INTO archive SELECT * FROM bids;";
+
+    let program = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap()
+        .program;
+    let graph = &program.graph;
+
+    let sources: Vec<_> = graph
+        .node_indices()
+        .filter(|n| graph[*n].operator_chain.is_source())
+        .collect();
+    let sinks: Vec<_> = graph
+        .node_indices()
+        .filter(|n| graph[*n].operator_chain.is_sink())
+        .collect();
+
+    // the view is computed once from a single source, then fans out to both sinks
+    assert_eq!(sources.len(), 1);
+    assert_eq!(sinks.len(), 2);
+    for sink in &sinks {
+        assert_eq!(graph.edges_directed(*sink, Direction::Incoming).count(), 1);
+        assert!(has_path_connecting(graph, sources[0], *sink, None));
+    }
+    assert!(graph
+        .node_indices()
+        .any(|n| graph.edges_directed(n, Direction::Outgoing).count() == 2));
+}
+
+#[test(tokio::test)]
+async fn test_union_all() {
+    let sql = "SELECT bid.auction as id, bid.price as value FROM nexmark WHERE bid IS NOT NULL \
+        UNION ALL \
+        SELECT auction.id as id, auction.reserve as value FROM nexmark WHERE auction IS NOT NULL";
+
+    let program = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap()
+        .program;
+    let graph = &program.graph;
+
+    // both sides of the union feed a single node, with the same schema on each edge
+    let union = graph
+        .node_indices()
+        .find(|n| graph.edges_directed(*n, Direction::Incoming).count() == 2)
+        .expect("no node with both union inputs");
+
+    let schemas: Vec<_> = graph
+        .edges_directed(union, Direction::Incoming)
+        .map(|e| e.weight().schema.clone())
+        .collect();
+    assert!(schemas[0].structurally_equals(&schemas[1]));
+    assert_eq!(schemas[0].schema.field(0).name(), "id");
+    assert_eq!(schemas[0].schema.field(1).name(), "value");
+}
+
+#[test(tokio::test)]
+async fn test_explain() {
+    let sql = "CREATE TABLE live (auction BIGINT, count BIGINT) WITH (connector = 'blackhole');
+        CREATE TABLE archive (auction BIGINT, count BIGINT) WITH (connector = 'blackhole');
+        CREATE VIEW counts AS SELECT bid.auction as auction, count(*) as count
+            FROM nexmark WHERE bid IS NOT NULL
+            GROUP BY 1, tumble(INTERVAL '1 minute');
+        INSERT INTO live SELECT * FROM counts;
+        INSERT INTO archive SELECT * FROM counts;";
+
+    let program = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap()
+        .program;
+
+    let explain = program.explain();
+    let lines: Vec<_> = explain.lines().collect();
+
+    // each sink starts a tree at the top level
+    let roots: Vec<_> = lines.iter().filter(|l| !l.starts_with(' ')).collect();
+    assert_eq!(roots.len(), 2, "{}", explain);
+    assert!(
+        roots.iter().all(|l| l.contains("ConnectorSink")),
+        "{}",
+        explain
+    );
+
+    // the aggregate is fed by a shuffle, and the shared source is only expanded once
+    assert!(
+        lines
+            .iter()
+            .any(|l| l.contains("<-[shuffle]- TumblingWindowAggregate")),
+        "{}",
+        explain
+    );
+    assert_eq!(
+        lines
+            .iter()
+            .filter(|l| l.contains("ConnectorSource"))
+            .count(),
+        1,
+        "{}",
+        explain
+    );
+    assert!(
+        lines.iter().any(|l| l.trim_end().ends_with("(see above)")),
+        "{}",
+        explain
+    );
+}
@@ -0,0 +1,31 @@
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+CREATE TABLE tumbling_sink (
+    auction BIGINT,
+    bidders BIGINT,
+    total_price BIGINT
+) WITH (
+    connector = 'blackhole'
+);
+
+CREATE TABLE sliding_sink (
+    auction BIGINT,
+    bidders BIGINT
+) WITH (
+    connector = 'blackhole'
+);
+
+INSERT INTO tumbling_sink
+SELECT bid.auction, count(distinct bid.bidder), sum(distinct bid.price)
+FROM nexmark
+WHERE bid IS NOT NULL
+GROUP BY 1, tumble(interval '1 minute');
+
+INSERT INTO sliding_sink
+SELECT bid.auction, count(distinct bid.bidder)
+FROM nexmark
+WHERE bid IS NOT NULL
+GROUP BY 1, hop(interval '1 minute', interval '5 minute');
@@ -0,0 +1,10 @@
+--fail=irate(DISTINCT ...) is not supported in windowed aggregates
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+SELECT bid.auction, irate(distinct cast(bid.price as float))
+FROM nexmark
+WHERE bid IS NOT NULL
+GROUP BY 1, tumble(interval '1 minute');
@@ -0,0 +1,17 @@
+--fail=GROUPING SETS, CUBE, and ROLLUP are not supported in streaming aggregates
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+SELECT
+    bid.auction as auction,
+    bid.channel as channel,
+    count(*) as count
+FROM
+    nexmark
+where
+    bid is not null
+GROUP BY
+    ROLLUP(bid.auction, bid.channel),
+    tumble(interval '1 minute')
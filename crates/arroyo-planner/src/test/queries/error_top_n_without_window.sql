@@ -0,0 +1,11 @@
+--fail=ORDER BY with LIMIT requires windowed input
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+SELECT bid.auction, bid.price
+FROM nexmark
+WHERE bid IS NOT NULL
+ORDER BY bid.price DESC
+LIMIT 5;
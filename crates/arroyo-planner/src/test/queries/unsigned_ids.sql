@@ -0,0 +1,23 @@
+CREATE TABLE events (
+    id BIGINT UNSIGNED NOT NULL,
+    shard INTEGER UNSIGNED
+) WITH (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    topic = 'events',
+    format = 'json',
+    type = 'source'
+);
+
+CREATE TABLE high_ids (
+    id BIGINT UNSIGNED NOT NULL,
+    shard INTEGER UNSIGNED
+) WITH (
+    connector = 'blackhole'
+);
+
+-- ids above i64::MAX must compare as unsigned values
+INSERT INTO high_ids
+SELECT id, shard
+FROM events
+WHERE id > 9223372036854775807 AND id <= 18446744073709551615;
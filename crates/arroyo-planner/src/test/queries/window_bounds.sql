@@ -0,0 +1,41 @@
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+CREATE TABLE bounds_sink (
+    window_start TIMESTAMP,
+    window_end TIMESTAMP,
+    auction BIGINT,
+    bids BIGINT
+) WITH (
+    connector = 'blackhole'
+);
+
+CREATE TABLE nested_bounds_sink (
+    window_start TIMESTAMP,
+    window_end TIMESTAMP,
+    auction BIGINT,
+    bids BIGINT
+) WITH (
+    connector = 'blackhole'
+);
+
+INSERT INTO bounds_sink
+SELECT
+    window_start(tumble(interval '1 minute')) as window_start,
+    window_end(tumble(interval '1 minute')) as window_end,
+    bid.auction,
+    count(*)
+FROM nexmark
+WHERE bid IS NOT NULL
+GROUP BY 3, tumble(interval '1 minute');
+
+INSERT INTO nested_bounds_sink
+SELECT window_start(window), window_end(window), auction, bids
+FROM (
+    SELECT hop(interval '1 minute', interval '5 minute') as window, bid.auction as auction, count(*) as bids
+    FROM nexmark
+    WHERE bid IS NOT NULL
+    GROUP BY 1, 2
+);
@@ -0,0 +1,14 @@
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+SELECT auction, window, count
+FROM (
+    SELECT bid.auction as auction, tumble(interval '1 hour') as window, count(*) as count
+    FROM nexmark
+    WHERE bid IS NOT NULL
+    GROUP BY 1, 2
+)
+ORDER BY count DESC
+LIMIT 5;
@@ -11,6 +11,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::configure()
         .protoc_arg("--experimental_allow_proto3_optional")
         .file_descriptor_set_path(out_dir.join("api_descriptor.bin"))
+        // ordered so that program hashes are stable
+        .btree_map([".api.ArrowNode.metadata"])
         .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
         .type_attribute(".", "#[serde(rename_all = \"camelCase\")]")
         .compile_protos(&["proto/api.proto"], &["proto/"])?;
@@ -3,8 +3,9 @@ use crate::formats::{BadData, Format, Framing};
 use crate::{primitive_to_sql, MetadataField};
 use ahash::HashSet;
 use anyhow::bail;
-use arrow_schema::{DataType, Field, Fields, TimeUnit};
+use arrow_schema::{DataType, Field, Fields, IntervalUnit, TimeUnit};
 use arroyo_types::ArroyoExtensionType;
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
@@ -71,6 +72,7 @@ impl TryFrom<String> for ConnectionType {
         match value.to_lowercase().as_str() {
             "source" => Ok(ConnectionType::Source),
             "sink" => Ok(ConnectionType::Sink),
+            "lookup" => Ok(ConnectionType::Lookup),
             _ => Err(format!("Invalid connection type: {}", value)),
         }
     }
@@ -91,6 +93,13 @@ pub enum PrimitiveType {
     UnixMicros,
     UnixNanos,
     DateTime,
+    DurationSeconds,
+    DurationMillis,
+    DurationMicros,
+    DurationNanos,
+    IntervalYearMonth,
+    IntervalDayTime,
+    IntervalMonthDayNano,
     Json,
 }
 
@@ -147,6 +156,17 @@ impl From<SourceField> for Field {
                 }
                 PrimitiveType::UnixNanos => (DataType::Timestamp(TimeUnit::Nanosecond, None), None),
                 PrimitiveType::DateTime => (DataType::Timestamp(TimeUnit::Microsecond, None), None),
+                PrimitiveType::DurationSeconds => (DataType::Duration(TimeUnit::Second), None),
+                PrimitiveType::DurationMillis => (DataType::Duration(TimeUnit::Millisecond), None),
+                PrimitiveType::DurationMicros => (DataType::Duration(TimeUnit::Microsecond), None),
+                PrimitiveType::DurationNanos => (DataType::Duration(TimeUnit::Nanosecond), None),
+                PrimitiveType::IntervalYearMonth => {
+                    (DataType::Interval(IntervalUnit::YearMonth), None)
+                }
+                PrimitiveType::IntervalDayTime => (DataType::Interval(IntervalUnit::DayTime), None),
+                PrimitiveType::IntervalMonthDayNano => {
+                    (DataType::Interval(IntervalUnit::MonthDayNano), None)
+                }
                 PrimitiveType::Json => (DataType::Utf8, Some(ArroyoExtensionType::JSON)),
             },
             FieldType::Struct(s) => (
@@ -169,6 +189,14 @@ impl TryFrom<Field> for SourceField {
     type Error = String;
 
     fn try_from(f: Field) -> Result<Self, Self::Error> {
+        // dictionary encoding only affects the physical layout, so these are converted as
+        // their value type
+        if let DataType::Dictionary(_, value_type) = f.data_type() {
+            return Field::new(f.name(), (**value_type).clone(), f.is_nullable())
+                .with_metadata(f.metadata().clone())
+                .try_into();
+        }
+
         let field_type = match (f.data_type(), ArroyoExtensionType::from_map(f.metadata())) {
             (DataType::Boolean, None) => FieldType::Primitive(PrimitiveType::Bool),
             (DataType::Int32, None) => FieldType::Primitive(PrimitiveType::Int32),
@@ -189,6 +217,27 @@ impl TryFrom<Field> for SourceField {
             (DataType::Timestamp(TimeUnit::Nanosecond, _), None) => {
                 FieldType::Primitive(PrimitiveType::UnixNanos)
             }
+            (DataType::Duration(TimeUnit::Second), None) => {
+                FieldType::Primitive(PrimitiveType::DurationSeconds)
+            }
+            (DataType::Duration(TimeUnit::Millisecond), None) => {
+                FieldType::Primitive(PrimitiveType::DurationMillis)
+            }
+            (DataType::Duration(TimeUnit::Microsecond), None) => {
+                FieldType::Primitive(PrimitiveType::DurationMicros)
+            }
+            (DataType::Duration(TimeUnit::Nanosecond), None) => {
+                FieldType::Primitive(PrimitiveType::DurationNanos)
+            }
+            (DataType::Interval(IntervalUnit::YearMonth), None) => {
+                FieldType::Primitive(PrimitiveType::IntervalYearMonth)
+            }
+            (DataType::Interval(IntervalUnit::DayTime), None) => {
+                FieldType::Primitive(PrimitiveType::IntervalDayTime)
+            }
+            (DataType::Interval(IntervalUnit::MonthDayNano), None) => {
+                FieldType::Primitive(PrimitiveType::IntervalMonthDayNano)
+            }
             (DataType::Utf8, None) => FieldType::Primitive(PrimitiveType::String),
             (DataType::Utf8, Some(ArroyoExtensionType::JSON)) => {
                 FieldType::Primitive(PrimitiveType::Json)
@@ -333,6 +382,119 @@ impl ConnectionSchema {
             })
             .collect()
     }
+
+    /// Compares this schema to a newer version of it, reporting how each field changed; fields
+    /// of nested structs are compared individually and reported by their dotted path
+    pub fn diff(&self, other: &ConnectionSchema) -> SchemaDiff {
+        let mut changes = vec![];
+        diff_fields("", &self.fields, &other.fields, &mut changes);
+        SchemaDiff { changes }
+    }
+}
+
+fn diff_fields(
+    prefix: &str,
+    old: &[SourceField],
+    new: &[SourceField],
+    changes: &mut Vec<FieldChange>,
+) {
+    let path = |f: &SourceField| format!("{}{}", prefix, f.field_name);
+
+    for o in old {
+        let Some(n) = new.iter().find(|n| n.field_name == o.field_name) else {
+            changes.push(FieldChange::Removed {
+                field: path(o),
+                field_type: o.field_type.r#type.clone(),
+            });
+            continue;
+        };
+
+        match (&o.field_type.r#type, &n.field_type.r#type) {
+            (FieldType::Struct(os), FieldType::Struct(ns)) => {
+                diff_fields(&format!("{}.", path(o)), &os.fields, &ns.fields, changes);
+            }
+            (ot, nt) if ot != nt => changes.push(FieldChange::TypeChanged {
+                field: path(o),
+                from: ot.clone(),
+                to: nt.clone(),
+            }),
+            _ => {}
+        }
+
+        if o.nullable != n.nullable {
+            changes.push(FieldChange::NullabilityChanged {
+                field: path(o),
+                nullable: n.nullable,
+            });
+        }
+    }
+
+    for n in new {
+        if !old.iter().any(|o| o.field_name == n.field_name) {
+            changes.push(FieldChange::Added {
+                field: path(n),
+                field_type: n.field_type.r#type.clone(),
+                nullable: n.nullable,
+            });
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "change")]
+pub enum FieldChange {
+    Added {
+        field: String,
+        field_type: FieldType,
+        nullable: bool,
+    },
+    Removed {
+        field: String,
+        field_type: FieldType,
+    },
+    TypeChanged {
+        field: String,
+        from: FieldType,
+        to: FieldType,
+    },
+    NullabilityChanged {
+        field: String,
+        nullable: bool,
+    },
+}
+
+impl FieldChange {
+    /// Whether data and queries using the old schema keep working with the new one; only adding
+    /// nullable fields and making fields nullable are compatible
+    pub fn is_compatible(&self) -> bool {
+        match self {
+            FieldChange::Added { nullable, .. } => *nullable,
+            FieldChange::NullabilityChanged { nullable, .. } => *nullable,
+            FieldChange::Removed { .. } | FieldChange::TypeChanged { .. } => false,
+        }
+    }
+}
+
+/// The changes between two versions of a [`ConnectionSchema`], as returned by
+/// [`ConnectionSchema::diff`]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaDiff {
+    pub changes: Vec<FieldChange>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn is_compatible(&self) -> bool {
+        self.changes.iter().all(|c| c.is_compatible())
+    }
+
+    pub fn breaking_changes(&self) -> impl Iterator<Item = &FieldChange> {
+        self.changes.iter().filter(|c| !c.is_compatible())
+    }
 }
 
 impl From<ConnectionSchema> for ArroyoSchema {
@@ -414,6 +576,36 @@ impl TestSourceMessage {
             message: message.into(),
         }
     }
+
+    /// Runs a connection test to completion, returning the messages it produced (ending with the
+    /// final `done` message) if it succeeded, or the `fail` message if it failed. The stream must
+    /// end with exactly one terminal message; anything else is reported as a failure.
+    pub async fn collect(
+        mut messages: impl Stream<Item = TestSourceMessage> + Unpin,
+    ) -> Result<Vec<String>, String> {
+        let mut collected = vec![];
+        let mut result = None;
+
+        while let Some(msg) = messages.next().await {
+            if result.is_some() {
+                return Err(format!(
+                    "connection test sent a message after it finished: {}",
+                    msg.message
+                ));
+            }
+
+            match (msg.done, msg.error) {
+                (true, true) => result = Some(Err(msg.message)),
+                (true, false) => {
+                    collected.push(msg.message);
+                    result = Some(Ok(std::mem::take(&mut collected)));
+                }
+                (false, _) => collected.push(msg.message),
+            }
+        }
+
+        result.unwrap_or_else(|| Err("connection test ended without a result".to_string()))
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -428,3 +620,311 @@ pub struct ConfluentSchemaQueryParams {
     pub endpoint: String,
     pub topic: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, t: FieldType, nullable: bool) -> SourceField {
+        SourceField {
+            field_name: name.to_string(),
+            field_type: SourceFieldType {
+                r#type: t,
+                sql_name: None,
+            },
+            nullable,
+            metadata_key: None,
+        }
+    }
+
+    fn schema(fields: Vec<SourceField>) -> ConnectionSchema {
+        ConnectionSchema::try_new(
+            None,
+            None,
+            None,
+            None,
+            fields,
+            None,
+            None,
+            Default::default(),
+        )
+        .unwrap()
+    }
+
+    fn primitive(p: PrimitiveType) -> FieldType {
+        FieldType::Primitive(p)
+    }
+
+    #[test]
+    fn test_connection_type_round_trip() {
+        for connection_type in [
+            ConnectionType::Source,
+            ConnectionType::Sink,
+            ConnectionType::Lookup,
+        ] {
+            assert_eq!(
+                ConnectionType::try_from(connection_type.to_string()),
+                Ok(connection_type)
+            );
+        }
+
+        assert_eq!(
+            ConnectionType::try_from("lookup".to_string()),
+            Ok(ConnectionType::Lookup)
+        );
+        assert!(ConnectionType::try_from("lookups".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_duration_and_interval_round_trip() {
+        for (data_type, primitive_type) in [
+            (
+                DataType::Duration(TimeUnit::Second),
+                PrimitiveType::DurationSeconds,
+            ),
+            (
+                DataType::Duration(TimeUnit::Millisecond),
+                PrimitiveType::DurationMillis,
+            ),
+            (
+                DataType::Duration(TimeUnit::Microsecond),
+                PrimitiveType::DurationMicros,
+            ),
+            (
+                DataType::Duration(TimeUnit::Nanosecond),
+                PrimitiveType::DurationNanos,
+            ),
+            (
+                DataType::Interval(IntervalUnit::YearMonth),
+                PrimitiveType::IntervalYearMonth,
+            ),
+            (
+                DataType::Interval(IntervalUnit::DayTime),
+                PrimitiveType::IntervalDayTime,
+            ),
+            (
+                DataType::Interval(IntervalUnit::MonthDayNano),
+                PrimitiveType::IntervalMonthDayNano,
+            ),
+        ] {
+            let source_field: SourceField = Field::new("elapsed", data_type.clone(), true)
+                .try_into()
+                .unwrap();
+            assert_eq!(
+                source_field.field_type.r#type,
+                FieldType::Primitive(primitive_type)
+            );
+            assert_eq!(
+                source_field.field_type.sql_name.as_deref(),
+                Some("INTERVAL")
+            );
+
+            let field: Field = source_field.into();
+            assert_eq!(field.data_type(), &data_type);
+            assert!(field.is_nullable());
+        }
+    }
+
+    #[test]
+    fn test_duration_seconds_round_trip() {
+        let source_field: SourceField =
+            Field::new("elapsed", DataType::Duration(TimeUnit::Second), false)
+                .try_into()
+                .unwrap();
+
+        // the SQL name is shared with every other duration and interval type, but the unit
+        // survives being stored and read back through the API
+        let json = serde_json::to_string(&source_field).unwrap();
+        let source_field: SourceField = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            source_field.field_type.r#type,
+            primitive(PrimitiveType::DurationSeconds)
+        );
+        assert_eq!(
+            source_field.field_type.sql_name.as_deref(),
+            Some("INTERVAL")
+        );
+
+        let field: Field = source_field.into();
+        assert_eq!(field.data_type(), &DataType::Duration(TimeUnit::Second));
+        assert!(!field.is_nullable());
+    }
+
+    #[test]
+    fn test_dictionary_fields() {
+        let dictionary =
+            |value_type| DataType::Dictionary(Box::new(DataType::Int32), Box::new(value_type));
+
+        let source_field: SourceField = Field::new("country", dictionary(DataType::Utf8), false)
+            .try_into()
+            .unwrap();
+        assert_eq!(source_field.field_name, "country");
+        assert_eq!(
+            source_field.field_type.r#type,
+            primitive(PrimitiveType::String)
+        );
+        assert_eq!(source_field.field_type.sql_name.as_deref(), Some("TEXT"));
+        assert!(!source_field.nullable);
+
+        let json = ArroyoExtensionType::add_metadata(
+            Some(ArroyoExtensionType::JSON),
+            Field::new("payload", dictionary(DataType::Utf8), true),
+        );
+        let source_field: SourceField = json.try_into().unwrap();
+        assert_eq!(
+            source_field.field_type.r#type,
+            primitive(PrimitiveType::Json)
+        );
+
+        let nested = Field::new(
+            "request",
+            DataType::Struct(Fields::from(vec![Field::new(
+                "status",
+                dictionary(DataType::Int64),
+                true,
+            )])),
+            true,
+        );
+        let source_field: SourceField = nested.try_into().unwrap();
+        let FieldType::Struct(st) = source_field.field_type.r#type else {
+            panic!("expected a struct");
+        };
+        assert_eq!(
+            st.fields[0].field_type.r#type,
+            primitive(PrimitiveType::Int64)
+        );
+    }
+
+    #[test]
+    fn test_unsigned_round_trip() {
+        for (data_type, primitive_type, sql_name) in [
+            (DataType::UInt32, PrimitiveType::UInt32, "INTEGER UNSIGNED"),
+            (DataType::UInt64, PrimitiveType::UInt64, "BIGINT UNSIGNED"),
+            (DataType::Int64, PrimitiveType::Int64, "BIGINT"),
+        ] {
+            let source_field: SourceField = Field::new("id", data_type.clone(), false)
+                .try_into()
+                .unwrap();
+            assert_eq!(
+                source_field.field_type.r#type,
+                FieldType::Primitive(primitive_type)
+            );
+            assert_eq!(source_field.field_type.sql_name.as_deref(), Some(sql_name));
+
+            let field: Field = source_field.into();
+            assert_eq!(field.data_type(), &data_type);
+        }
+    }
+
+    #[test]
+    fn test_schema_diff() {
+        let address = |fields| FieldType::Struct(StructType { name: None, fields });
+
+        let old = schema(vec![
+            field("id", primitive(PrimitiveType::Int64), false),
+            field("name", primitive(PrimitiveType::String), false),
+            field(
+                "address",
+                address(vec![field("city", primitive(PrimitiveType::String), true)]),
+                true,
+            ),
+        ]);
+
+        assert!(old.diff(&old).is_empty());
+        assert!(old.diff(&old).is_compatible());
+
+        // additive nullable fields and relaxed nullability are compatible
+        let new = schema(vec![
+            field("id", primitive(PrimitiveType::Int64), false),
+            field("name", primitive(PrimitiveType::String), true),
+            field(
+                "address",
+                address(vec![
+                    field("city", primitive(PrimitiveType::String), true),
+                    field("zip", primitive(PrimitiveType::String), true),
+                ]),
+                true,
+            ),
+            field("email", primitive(PrimitiveType::String), true),
+        ]);
+
+        let diff = old.diff(&new);
+        assert!(diff.is_compatible());
+        assert_eq!(
+            diff.changes,
+            vec![
+                FieldChange::NullabilityChanged {
+                    field: "name".to_string(),
+                    nullable: true
+                },
+                FieldChange::Added {
+                    field: "address.zip".to_string(),
+                    field_type: primitive(PrimitiveType::String),
+                    nullable: true
+                },
+                FieldChange::Added {
+                    field: "email".to_string(),
+                    field_type: primitive(PrimitiveType::String),
+                    nullable: true
+                },
+            ]
+        );
+
+        // type changes, removals, and new required fields are breaking
+        let new = schema(vec![
+            field("id", primitive(PrimitiveType::String), false),
+            field("email", primitive(PrimitiveType::String), false),
+        ]);
+
+        let diff = old.diff(&new);
+        assert!(!diff.is_compatible());
+        assert_eq!(diff.breaking_changes().count(), 4);
+        assert!(diff.changes.contains(&FieldChange::TypeChanged {
+            field: "id".to_string(),
+            from: primitive(PrimitiveType::Int64),
+            to: primitive(PrimitiveType::String),
+        }));
+        assert!(diff.changes.contains(&FieldChange::Removed {
+            field: "address".to_string(),
+            field_type: address(vec![field("city", primitive(PrimitiveType::String), true)]),
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_collect_test_messages() {
+        let collect = |messages: Vec<TestSourceMessage>| {
+            TestSourceMessage::collect(futures::stream::iter(messages))
+        };
+
+        assert_eq!(
+            collect(vec![
+                TestSourceMessage::info("connecting"),
+                TestSourceMessage::done("connected"),
+            ])
+            .await,
+            Ok(vec!["connecting".to_string(), "connected".to_string()])
+        );
+
+        assert_eq!(
+            collect(vec![
+                TestSourceMessage::info("connecting"),
+                TestSourceMessage::fail("connection refused"),
+            ])
+            .await,
+            Err("connection refused".to_string())
+        );
+
+        assert!(collect(vec![TestSourceMessage::info("connecting")])
+            .await
+            .unwrap_err()
+            .contains("without a result"));
+
+        assert!(collect(vec![
+            TestSourceMessage::done("connected"),
+            TestSourceMessage::fail("connection refused"),
+        ])
+        .await
+        .unwrap_err()
+        .contains("after it finished"));
+    }
+}
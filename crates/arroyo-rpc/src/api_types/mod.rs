@@ -19,6 +19,7 @@ pub mod udfs;
     PipelineCollection = PaginatedCollection<Pipeline>,
     JobLogMessageCollection = PaginatedCollection<JobLogMessage>,
     ConnectionTableCollection = PaginatedCollection<ConnectionTable>,
+    GlobalUdfCollection = PaginatedCollection<GlobalUdf>,
 )]
 pub struct PaginatedCollection<T> {
     pub data: Vec<T>,
@@ -34,7 +35,6 @@ pub struct PaginatedCollection<T> {
     OperatorMetricGroupCollection = NonPaginatedCollection<OperatorMetricGroup>,
     ConnectorCollection = NonPaginatedCollection<Connector>,
     ConnectionProfileCollection = NonPaginatedCollection<ConnectionProfile>,
-    GlobalUdfCollection = NonPaginatedCollection<GlobalUdf>,
 )]
 pub struct NonPaginatedCollection<T> {
     pub data: Vec<T>,
@@ -1,6 +1,7 @@
 use crate::api_types::udfs::Udf;
 use crate::grpc as grpc_proto;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use utoipa::ToSchema;
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -81,6 +82,7 @@ pub struct PipelineNode {
     pub operator: String,
     pub description: String,
     pub parallelism: u32,
+    pub metadata: BTreeMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
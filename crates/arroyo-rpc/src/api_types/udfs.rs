@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -16,6 +16,9 @@ pub struct ValidateUdfPost {
     pub definition: String,
     #[serde(default)]
     pub language: UdfLanguage,
+    /// A Cargo.lock to build the UDF against; if set the build fails rather than resolving
+    /// different dependency versions
+    pub cargo_lock: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -23,6 +26,20 @@ pub struct ValidateUdfPost {
 pub struct UdfValidationResult {
     pub udf_name: Option<String>,
     pub errors: Vec<String>,
+    /// The Cargo.lock the UDF's dependencies were resolved to
+    pub cargo_lock: Option<String>,
+    /// The Cargo.toml of the crate generated to build a Rust UDF, if requested
+    pub cargo_toml: Option<String>,
+    /// The lib.rs of the crate generated to build a Rust UDF, if requested
+    pub lib_rs: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "snake_case")]
+pub struct ValidateUdfQueryParams {
+    /// Whether to include the crate generated to build a Rust UDF in the result
+    pub include_generated: Option<bool>,
 }
 
 #[derive(
@@ -53,6 +70,10 @@ pub struct UdfPost {
     pub language: UdfLanguage,
     pub definition: String,
     pub description: Option<String>,
+    pub cargo_lock: Option<String>,
+    /// If true, an existing UDF with the same name is updated rather than causing a conflict
+    #[serde(default)]
+    pub upsert: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -67,4 +88,5 @@ pub struct GlobalUdf {
     pub definition: String,
     pub description: Option<String>,
     pub dylib_url: Option<String>,
+    pub cargo_lock: Option<String>,
 }
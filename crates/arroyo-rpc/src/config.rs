@@ -324,6 +324,10 @@ pub struct CompilerConfig {
     /// Directory to build artifacts in
     pub build_dir: String,
 
+    /// How long the API waits for a UDF build before giving up; the build is cancelled on the
+    /// compiler when this elapses
+    pub build_timeout: HumanReadableDuration,
+
     /// Whether to use a local version of the UDF library or the published crate (only
     /// enable in development environments)
     #[serde(default)]
@@ -1,18 +1,23 @@
 use crate::grpc::api;
-use crate::{Converter, TIMESTAMP_FIELD};
+use crate::{get_hasher, Converter, TIMESTAMP_FIELD};
 use anyhow::{anyhow, bail, Result};
+use arrow::buffer::NullBuffer;
 use arrow::compute::kernels::numeric::div;
-use arrow::compute::{filter_record_batch, take};
+use arrow::compute::{and, filter_record_batch, sort_to_indices, take, take_record_batch};
 use arrow::datatypes::{DataType, Field, Schema, SchemaBuilder, TimeUnit};
 use arrow::row::SortField;
 use arrow_array::builder::{make_builder, ArrayBuilder};
 use arrow_array::types::UInt64Type;
-use arrow_array::{Array, PrimitiveArray, RecordBatch, TimestampNanosecondArray, UInt64Array};
-use arrow_ord::cmp::gt_eq;
+use arrow_array::{
+    make_array, Array, ArrayRef, AsArray, PrimitiveArray, RecordBatch, TimestampNanosecondArray,
+    UInt64Array,
+};
+use arrow_ord::cmp::{gt_eq, lt};
 use arrow_ord::partition::partition;
 use arrow_ord::sort::{lexsort_to_indices, SortColumn};
 use arrow_schema::FieldRef;
 use arroyo_types::to_nanos;
+use datafusion::common::hash_utils::create_hashes;
 use datafusion::common::{DataFusionError, Result as DFResult};
 use std::ops::Range;
 use std::sync::Arc;
@@ -27,6 +32,9 @@ pub struct ArroyoSchema {
     key_indices: Option<Vec<usize>>,
     /// If defined, these indices are used for routing (i.e., which subtask gets which piece of data)
     routing_key_indices: Option<Vec<usize>>,
+    /// If defined, holds for each key the path of struct field indices to follow within its
+    /// key column to reach the value actually keyed on; an empty path keys on the whole column
+    nested_key_paths: Option<Vec<Vec<usize>>>,
 }
 
 impl TryFrom<api::ArroyoSchema> for ArroyoSchema {
@@ -52,12 +60,23 @@ impl TryFrom<api::ArroyoSchema> for ArroyoSchema {
                 .collect()
         });
 
-        Ok(Self {
+        let nested_key_paths = (!schema_proto.nested_key_paths.is_empty()).then(|| {
+            schema_proto
+                .nested_key_paths
+                .into_iter()
+                .map(|path| path.field_indices.into_iter().map(|i| i as usize).collect())
+                .collect()
+        });
+
+        let schema = Self {
             schema: Arc::new(schema),
             timestamp_index,
             key_indices,
             routing_key_indices,
-        })
+            nested_key_paths,
+        };
+        schema.validate()?;
+        Ok(schema)
     }
 }
 
@@ -78,6 +97,18 @@ impl From<ArroyoSchema> for api::ArroyoSchema {
             .map(|ks| ks.into_iter().map(|index| index as u32).collect())
             .unwrap_or_default();
 
+        let nested_key_paths = schema
+            .nested_key_paths
+            .map(|paths| {
+                paths
+                    .into_iter()
+                    .map(|path| api::KeyPath {
+                        field_indices: path.into_iter().map(|i| i as u32).collect(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
         Self {
             arrow_schema,
             timestamp_index,
@@ -85,6 +116,7 @@ impl From<ArroyoSchema> for api::ArroyoSchema {
             has_keys,
             routing_key_indices,
             has_routing_keys,
+            nested_key_paths,
         }
     }
 }
@@ -101,6 +133,7 @@ impl ArroyoSchema {
             timestamp_index,
             key_indices,
             routing_key_indices,
+            nested_key_paths: None,
         }
     }
     pub fn new_unkeyed(schema: Arc<Schema>, timestamp_index: usize) -> Self {
@@ -109,6 +142,7 @@ impl ArroyoSchema {
             timestamp_index,
             key_indices: None,
             routing_key_indices: None,
+            nested_key_paths: None,
         }
     }
     pub fn new_keyed(schema: Arc<Schema>, timestamp_index: usize, key_indices: Vec<usize>) -> Self {
@@ -117,6 +151,7 @@ impl ArroyoSchema {
             timestamp_index,
             key_indices: Some(key_indices),
             routing_key_indices: None,
+            nested_key_paths: None,
         }
     }
 
@@ -143,12 +178,15 @@ impl ArroyoSchema {
             })?
             .0;
 
-        Ok(Self {
+        let schema = Self {
             schema,
             timestamp_index,
             key_indices: None,
             routing_key_indices: None,
-        })
+            nested_key_paths: None,
+        };
+        schema.validate()?;
+        Ok(schema)
     }
 
     pub fn from_schema_keys(schema: Arc<Schema>, key_indices: Vec<usize>) -> DFResult<Self> {
@@ -162,12 +200,184 @@ impl ArroyoSchema {
             })?
             .0;
 
-        Ok(Self {
+        let schema = Self {
             schema,
             timestamp_index,
             key_indices: Some(key_indices),
             routing_key_indices: None,
-        })
+            nested_key_paths: None,
+        };
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    /// Creates a schema keyed on fields that may be nested within struct columns. Each key path
+    /// starts with the index of a top-level column, followed by the indices of the struct fields
+    /// to descend through; e.g., `[2, 0]` keys on the first field of the struct in column 2.
+    pub fn from_schema_key_paths(
+        schema: Arc<Schema>,
+        key_paths: Vec<Vec<usize>>,
+    ) -> DFResult<Self> {
+        let mut key_indices = vec![];
+        let mut nested_key_paths = vec![];
+        for path in key_paths {
+            let Some((column, nested)) = path.split_first() else {
+                return Err(DataFusionError::Plan(
+                    "key paths must not be empty".to_string(),
+                ));
+            };
+            key_indices.push(*column);
+            nested_key_paths.push(nested.to_vec());
+        }
+
+        let mut schema = Self::from_schema_keys(schema, key_indices)?;
+        if nested_key_paths.iter().any(|path| !path.is_empty()) {
+            schema.nested_key_paths = Some(nested_key_paths);
+        }
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    /// Checks that the timestamp and key indices are consistent with the arrow schema: the
+    /// timestamp must be an in-range nanosecond timestamp column, and every key must be an
+    /// in-range column other than the timestamp.
+    pub fn validate(&self) -> DFResult<()> {
+        let fields = self.schema.fields();
+        let Some(timestamp_field) = fields.get(self.timestamp_index) else {
+            return Err(DataFusionError::Plan(format!(
+                "timestamp index {} is out of range for schema with {} fields",
+                self.timestamp_index,
+                fields.len()
+            )));
+        };
+
+        if !matches!(
+            timestamp_field.data_type(),
+            DataType::Timestamp(TimeUnit::Nanosecond, _)
+        ) {
+            return Err(DataFusionError::Plan(format!(
+                "timestamp field '{}' must be a nanosecond timestamp, but is {}",
+                timestamp_field.name(),
+                timestamp_field.data_type()
+            )));
+        }
+
+        for (kind, indices) in [
+            ("key", &self.key_indices),
+            ("routing key", &self.routing_key_indices),
+        ] {
+            for index in indices.iter().flatten() {
+                if *index >= fields.len() {
+                    return Err(DataFusionError::Plan(format!(
+                        "{} index {} is out of range for schema with {} fields",
+                        kind,
+                        index,
+                        fields.len()
+                    )));
+                }
+
+                if *index == self.timestamp_index && kind == "key" {
+                    return Err(DataFusionError::Plan(format!(
+                        "timestamp field '{}' cannot be used as a key",
+                        timestamp_field.name()
+                    )));
+                }
+            }
+        }
+
+        if let Some(paths) = &self.nested_key_paths {
+            let keys = self.key_indices.as_deref().unwrap_or_default();
+            if paths.len() != keys.len() {
+                return Err(DataFusionError::Plan(format!(
+                    "schema has {} nested key paths but {} keys",
+                    paths.len(),
+                    keys.len()
+                )));
+            }
+
+            for (index, path) in keys.iter().zip(paths) {
+                let mut field = fields[*index].clone();
+                for child in path {
+                    let DataType::Struct(children) = field.data_type() else {
+                        return Err(DataFusionError::Plan(format!(
+                            "cannot key on a nested field of '{}', which is not a struct",
+                            field.name()
+                        )));
+                    };
+                    let Some(child_field) = children.get(*child) else {
+                        return Err(DataFusionError::Plan(format!(
+                            "nested key index {} is out of range for struct '{}' with {} fields",
+                            child,
+                            field.name(),
+                            children.len()
+                        )));
+                    };
+                    field = child_field.clone();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn key_path(&self, key: usize) -> &[usize] {
+        self.nested_key_paths
+            .as_ref()
+            .map(|paths| paths[key].as_slice())
+            .unwrap_or_default()
+    }
+
+    fn key_data_type(&self, key: usize) -> DataType {
+        let index = self.key_indices.as_ref().unwrap()[key];
+        let mut data_type = self.schema.field(index).data_type().clone();
+        for child in self.key_path(key) {
+            let DataType::Struct(children) = data_type else {
+                unreachable!("nested key paths are validated on construction");
+            };
+            data_type = children[*child].data_type().clone();
+        }
+        data_type
+    }
+
+    /// Returns the values each row is keyed on, in key order. Nested keys are extracted from
+    /// their struct columns; a row whose enclosing struct is null has a null key.
+    pub fn key_columns(&self, batch: &RecordBatch) -> Vec<ArrayRef> {
+        self.key_indices
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(key, index)| nested_column(batch.column(*index), self.key_path(key)))
+            .collect()
+    }
+
+    /// Returns the columns used to route rows to subtasks, if this schema is routed by key
+    pub fn routing_key_columns(&self, batch: &RecordBatch) -> Option<Vec<ArrayRef>> {
+        match &self.routing_key_indices {
+            Some(indices) => Some(
+                indices
+                    .iter()
+                    .map(|index| batch.column(*index).clone())
+                    .collect(),
+            ),
+            None => self.key_indices.as_ref().map(|_| self.key_columns(batch)),
+        }
+    }
+
+    /// Hashes the routing key columns of each row, as used to assign rows to subtasks and to
+    /// partition keyed state. Rows of an unkeyed schema all hash to 0.
+    ///
+    /// The hash is seeded with fixed values, so within a build equal keys hash identically in
+    /// every operator, process and run; two streams keyed on columns of the same types are
+    /// therefore co-partitioned. The hash is only stable within a build: the underlying hasher
+    /// may produce different values across versions of its dependencies or CPU targets, so keyed
+    /// state checkpointed by one build may be routed to the wrong subtasks when restored by
+    /// another.
+    pub fn hash_keys(&self, batch: &RecordBatch) -> DFResult<UInt64Array> {
+        let mut hashes = vec![0; batch.num_rows()];
+        if let Some(columns) = self.routing_key_columns(batch) {
+            create_hashes(&columns, &get_hasher(), &mut hashes)?;
+        }
+        Ok(UInt64Array::from(hashes))
     }
 
     pub fn schema_without_timestamp(&self) -> Schema {
@@ -176,6 +386,30 @@ impl ArroyoSchema {
         builder.finish()
     }
 
+    /// Looks up the index of the column named `name`, returning an error that lists the available
+    /// columns if it does not exist
+    pub fn field_index(&self, name: &str) -> DFResult<usize> {
+        self.schema.index_of(name).map_err(|_| {
+            DataFusionError::Plan(format!(
+                "no field named '{}' in schema; available fields are {}",
+                name,
+                self.schema
+                    .fields()
+                    .iter()
+                    .map(|f| format!("'{}'", f.name()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })
+    }
+
+    pub fn field_indices<S: AsRef<str>>(&self, names: &[S]) -> DFResult<Vec<usize>> {
+        names
+            .iter()
+            .map(|name| self.field_index(name.as_ref()))
+            .collect()
+    }
+
     pub fn remove_timestamp_column(&self, batch: &mut RecordBatch) {
         batch.remove_column(self.timestamp_index);
     }
@@ -211,29 +445,64 @@ impl ArroyoSchema {
         batch: RecordBatch,
         cutoff: Option<SystemTime>,
     ) -> anyhow::Result<RecordBatch> {
-        let Some(cutoff) = cutoff else {
-            // no watermark, so we just return the same batch.
-            return Ok(batch);
-        };
         // filter out late data
+        self.filter_by_time_range(batch, cutoff, None)
+    }
+
+    /// Returns the rows of the batch whose timestamps fall within `[lower, upper)`; a missing
+    /// bound is unbounded on that side
+    pub fn filter_by_time_range(
+        &self,
+        batch: RecordBatch,
+        lower: Option<SystemTime>,
+        upper: Option<SystemTime>,
+    ) -> anyhow::Result<RecordBatch> {
+        if lower.is_none() && upper.is_none() {
+            return Ok(batch);
+        }
+
         let timestamp_column = batch
             .column(self.timestamp_index)
             .as_any()
             .downcast_ref::<TimestampNanosecondArray>()
             .ok_or_else(|| anyhow!("failed to downcast column {} of {:?} to timestamp. Schema is supposed to be {:?}", self.timestamp_index, batch, self.schema))?;
-        let cutoff_scalar = TimestampNanosecondArray::new_scalar(to_nanos(cutoff) as i64);
-        let on_time = gt_eq(timestamp_column, &cutoff_scalar).unwrap();
-        Ok(filter_record_batch(&batch, &on_time)?)
+
+        let lower = lower
+            .map(|lower| {
+                gt_eq(
+                    timestamp_column,
+                    &TimestampNanosecondArray::new_scalar(to_nanos(lower) as i64),
+                )
+            })
+            .transpose()?;
+
+        let upper = upper
+            .map(|upper| {
+                lt(
+                    timestamp_column,
+                    &TimestampNanosecondArray::new_scalar(to_nanos(upper) as i64),
+                )
+            })
+            .transpose()?;
+
+        let mask = match (lower, upper) {
+            (Some(lower), Some(upper)) => and(&lower, &upper)?,
+            (Some(mask), None) | (None, Some(mask)) => mask,
+            (None, None) => unreachable!(),
+        };
+
+        Ok(filter_record_batch(&batch, &mask)?)
     }
 
     pub fn sort_columns(&self, batch: &RecordBatch, with_timestamp: bool) -> Vec<SortColumn> {
-        let mut columns = vec![];
-        if let Some(keys) = &self.key_indices {
-            columns.extend(keys.iter().map(|index| SortColumn {
-                values: batch.column(*index).clone(),
+        let mut columns: Vec<_> = self
+            .key_columns(batch)
+            .into_iter()
+            .map(|values| SortColumn {
+                values,
                 options: None,
-            }));
-        }
+            })
+            .collect();
         if with_timestamp {
             columns.push(SortColumn {
                 values: batch.column(self.timestamp_index).clone(),
@@ -244,14 +513,18 @@ impl ArroyoSchema {
     }
 
     pub fn sort_fields(&self, with_timestamp: bool) -> Vec<SortField> {
-        let mut sort_fields = vec![];
-        if let Some(keys) = &self.key_indices {
-            sort_fields.extend(keys.iter());
-        }
+        let key_count = self
+            .key_indices
+            .as_ref()
+            .map(|keys| keys.len())
+            .unwrap_or(0);
+        let mut sort_fields: Vec<_> = (0..key_count)
+            .map(|key| SortField::new(self.key_data_type(key)))
+            .collect();
         if with_timestamp {
-            sort_fields.push(self.timestamp_index);
+            sort_fields.extend(self.sort_fields_by_indices(&[self.timestamp_index]));
         }
-        self.sort_fields_by_indices(&sort_fields)
+        sort_fields
     }
 
     fn sort_fields_by_indices(&self, indices: &[usize]) -> Vec<SortField> {
@@ -312,8 +585,12 @@ impl ArroyoSchema {
     }
 
     pub fn sort(&self, batch: RecordBatch, with_timestamp: bool) -> Result<RecordBatch> {
-        if self.key_indices.is_none() && !with_timestamp {
-            return Ok(batch);
+        if self.key_indices.iter().all(|keys| keys.is_empty()) {
+            return if with_timestamp {
+                self.sort_by_timestamp(batch)
+            } else {
+                Ok(batch)
+            };
         }
         let sort_columns = self.sort_columns(&batch, with_timestamp);
         let sort_indices = lexsort_to_indices(&sort_columns, None).expect("should be able to sort");
@@ -326,6 +603,18 @@ impl ArroyoSchema {
         Ok(RecordBatch::try_new(batch.schema(), columns)?)
     }
 
+    /// Sorts the batch by its timestamp column alone, ignoring any keys. Batches that are
+    /// already in time order (the common case for most sources) are returned without copying.
+    pub fn sort_by_timestamp(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let timestamps = self.timestamp_column(&batch);
+        if timestamps.null_count() == 0 && timestamps.values().windows(2).all(|w| w[0] <= w[1]) {
+            return Ok(batch);
+        }
+
+        let sort_indices = sort_to_indices(timestamps, None, None)?;
+        Ok(take_record_batch(&batch, &sort_indices)?)
+    }
+
     pub fn partition(
         &self,
         batch: &RecordBatch,
@@ -336,11 +625,8 @@ impl ArroyoSchema {
             return Ok(vec![0..batch.num_rows()]);
         }
 
-        let mut partition_columns = vec![];
+        let mut partition_columns = self.routing_key_columns(batch).unwrap_or_default();
 
-        if let Some(keys) = &self.routing_keys() {
-            partition_columns.extend(keys.iter().map(|index| batch.column(*index).clone()));
-        }
         if with_timestamp {
             partition_columns.push(batch.column(self.timestamp_index).clone());
         }
@@ -378,6 +664,7 @@ impl ArroyoSchema {
             timestamp_index,
             key_indices: None,
             routing_key_indices: None,
+            nested_key_paths: None,
         })
     }
 
@@ -403,12 +690,15 @@ impl ArroyoSchema {
             );
         }
 
-        Ok(Self {
+        let schema = Self {
             schema,
             timestamp_index,
             key_indices: self.key_indices.clone(),
             routing_key_indices: self.routing_key_indices.clone(),
-        })
+            nested_key_paths: self.nested_key_paths.clone(),
+        };
+        schema.validate()?;
+        Ok(schema)
     }
 
     pub fn with_additional_fields(&self, new_fields: impl Iterator<Item = Field>) -> Result<Self> {
@@ -417,6 +707,76 @@ impl ArroyoSchema {
 
         self.with_fields(fields)
     }
+
+    /// Returns a copy of this schema with the field `from` renamed to `to`, keeping its position
+    /// so that the timestamp and key indices still apply
+    pub fn rename_field(&self, from: &str, to: &str) -> Result<Self> {
+        let index = self.field_index(from)?;
+
+        if from == to {
+            return Ok(self.clone());
+        }
+
+        if index == self.timestamp_index {
+            bail!("cannot rename the timestamp field '{}'", from);
+        }
+
+        if self.schema.column_with_name(to).is_some() {
+            bail!(
+                "cannot rename field '{}' to '{}'; a field with that name already exists",
+                from,
+                to
+            );
+        }
+
+        let mut fields = self.schema.fields.to_vec();
+        fields[index] = Arc::new(fields[index].as_ref().clone().with_name(to));
+
+        self.with_fields(fields)
+    }
+
+    /// Returns whether the two schemas have the same shape: the same field names, types, and
+    /// nullability, and the same timestamp and key columns. Unlike `==`, this ignores field and
+    /// schema metadata, which doesn't affect how data flows between operators.
+    pub fn structurally_equals(&self, other: &Self) -> bool {
+        self.timestamp_index == other.timestamp_index
+            && self.key_indices == other.key_indices
+            && self.routing_key_indices == other.routing_key_indices
+            && self.nested_key_paths == other.nested_key_paths
+            && self.schema.fields.len() == other.schema.fields.len()
+            && self
+                .schema
+                .fields
+                .iter()
+                .zip(other.schema.fields.iter())
+                .all(|(a, b)| {
+                    a.name() == b.name()
+                        && a.is_nullable() == b.is_nullable()
+                        && a.data_type().equals_datatype(b.data_type())
+                })
+    }
+}
+
+fn nested_column(column: &ArrayRef, path: &[usize]) -> ArrayRef {
+    let mut column = column.clone();
+    for index in path {
+        let parent = column.as_struct();
+        let child = parent.column(*index).clone();
+        column = match parent.nulls() {
+            // a struct's children may hold arbitrary values for rows where the struct itself is
+            // null, so those rows must be masked out of the key
+            Some(_) => make_array(
+                child
+                    .to_data()
+                    .into_builder()
+                    .nulls(NullBuffer::union(parent.nulls(), child.nulls()))
+                    .build()
+                    .expect("nested key nulls should match the column length"),
+            ),
+            None => child,
+        };
+    }
+    column
 }
 
 pub fn server_for_hash_array(
@@ -429,3 +789,343 @@ pub fn server_for_hash_array(
     let result: &PrimitiveArray<UInt64Type> = division.as_any().downcast_ref().unwrap();
     Ok(result.clone())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ArroyoSchema;
+    use crate::grpc::api;
+    use arrow::buffer::NullBuffer;
+    use arrow::datatypes::{DataType, Field, Fields, Schema, TimeUnit};
+    use arrow_array::{
+        Array, Int64Array, RecordBatch, StringArray, StructArray, TimestampNanosecondArray,
+        UInt64Array,
+    };
+    use arroyo_types::from_nanos;
+    use std::sync::Arc;
+
+    fn batch(schema: &ArroyoSchema, timestamps: Vec<i64>) -> RecordBatch {
+        let values: UInt64Array = timestamps.iter().map(|t| *t as u64).collect();
+        RecordBatch::try_new(
+            schema.schema.clone(),
+            vec![
+                Arc::new(values),
+                Arc::new(TimestampNanosecondArray::from(timestamps)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn values(batch: &RecordBatch) -> Vec<u64> {
+        batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap()
+            .values()
+            .to_vec()
+    }
+
+    fn validation_schema() -> Arc<Schema> {
+        Arc::new(Schema::new(vec![
+            Field::new("key", DataType::Utf8, false),
+            Field::new("value", DataType::UInt64, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]))
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = validation_schema();
+
+        assert!(ArroyoSchema::from_schema_keys(schema.clone(), vec![0, 1]).is_ok());
+        assert!(ArroyoSchema::from_schema_unkeyed(schema.clone()).is_ok());
+
+        let err = ArroyoSchema::from_schema_keys(schema.clone(), vec![0, 3]).unwrap_err();
+        assert!(
+            err.to_string().contains("key index 3 is out of range"),
+            "{}",
+            err
+        );
+
+        let err = ArroyoSchema::from_schema_keys(schema.clone(), vec![2]).unwrap_err();
+        assert!(
+            err.to_string().contains("cannot be used as a key"),
+            "{}",
+            err
+        );
+
+        assert!(ArroyoSchema::new_unkeyed(schema.clone(), 3)
+            .validate()
+            .is_err());
+        assert!(ArroyoSchema::new_unkeyed(schema.clone(), 1)
+            .validate()
+            .is_err());
+
+        // routing on the timestamp is allowed, as it is not used as a storage key
+        assert!(ArroyoSchema::new(schema.clone(), 2, None, Some(vec![2]))
+            .validate()
+            .is_ok());
+        assert!(ArroyoSchema::new(schema, 2, None, Some(vec![5]))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_field_index() {
+        let schema = ArroyoSchema::from_schema_unkeyed(validation_schema()).unwrap();
+
+        assert_eq!(schema.field_index("value").unwrap(), 1);
+        assert_eq!(
+            schema.field_indices(&["_timestamp", "key"]).unwrap(),
+            vec![2, 0]
+        );
+
+        let err = schema.field_indices(&["key", "missing"]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("no field named 'missing' in schema; available fields are 'key', 'value', '_timestamp'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_rename_field() {
+        let schema = ArroyoSchema::from_schema_keys(validation_schema(), vec![0]).unwrap();
+
+        let renamed = schema.rename_field("value", "amount").unwrap();
+        assert_eq!(renamed.field_index("amount").unwrap(), 1);
+        assert!(renamed.field_index("value").is_err());
+        assert_eq!(renamed.timestamp_index, schema.timestamp_index);
+        assert_eq!(renamed.storage_keys(), schema.storage_keys());
+
+        let err = schema.rename_field("value", "key").unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{}", err);
+
+        let err = schema.rename_field("missing", "other").unwrap_err();
+        assert!(
+            err.to_string().contains("no field named 'missing'"),
+            "{}",
+            err
+        );
+
+        assert!(schema.rename_field("_timestamp", "ts").is_err());
+    }
+
+    #[test]
+    fn test_hash_keys() {
+        let schema = validation_schema();
+        let keyed = ArroyoSchema::from_schema_keys(schema.clone(), vec![0]).unwrap();
+
+        let batch = |keys: Vec<&str>, values: Vec<u64>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(StringArray::from(keys)),
+                    Arc::new(UInt64Array::from(values)),
+                    Arc::new(TimestampNanosecondArray::from(vec![0, 1, 2])),
+                ],
+            )
+            .unwrap()
+        };
+
+        let first = keyed
+            .hash_keys(&batch(vec!["a", "b", "a"], vec![1, 2, 3]))
+            .unwrap();
+        let second = keyed
+            .hash_keys(&batch(vec!["b", "a", "c"], vec![4, 5, 6]))
+            .unwrap();
+
+        // equal keys hash identically regardless of the other columns or the batch
+        assert_eq!(first.value(0), first.value(2));
+        assert_eq!(first.value(0), second.value(1));
+        assert_eq!(first.value(1), second.value(0));
+        assert_ne!(first.value(0), first.value(1));
+
+        let unkeyed = ArroyoSchema::from_schema_unkeyed(schema.clone()).unwrap();
+        let hashes = unkeyed
+            .hash_keys(&batch(vec!["a", "b", "c"], vec![1, 2, 3]))
+            .unwrap();
+        assert_eq!(hashes.values().to_vec(), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn test_nested_keys() {
+        let payload_fields = Fields::from(vec![
+            Field::new("user_id", DataType::Utf8, true),
+            Field::new("count", DataType::Int64, true),
+        ]);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("payload", DataType::Struct(payload_fields.clone()), true),
+            Field::new("value", DataType::UInt64, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+
+        let keyed = ArroyoSchema::from_schema_key_paths(schema.clone(), vec![vec![0, 0]]).unwrap();
+        assert_eq!(keyed.storage_keys(), Some(&vec![0]));
+        assert_eq!(keyed.sort_fields(false).len(), 1);
+
+        let payload = StructArray::new(
+            payload_fields,
+            vec![
+                Arc::new(StringArray::from(vec!["b", "a", "b", "a", "a"])),
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4, 5])),
+            ],
+            // the last row has a null payload, so its key is null despite its child's value
+            Some(NullBuffer::from(vec![true, true, true, true, false])),
+        );
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(payload),
+                Arc::new(UInt64Array::from(vec![1, 2, 3, 4, 5])),
+                Arc::new(TimestampNanosecondArray::from(vec![1, 2, 3, 4, 5])),
+            ],
+        )
+        .unwrap();
+
+        let keys = keyed.key_columns(&batch);
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].null_count(), 1);
+        assert!(keys[0].is_null(4));
+
+        let sorted = keyed.sort(batch, false).unwrap();
+        let ranges = keyed.partition(&sorted, false).unwrap();
+        let groups: Vec<_> = ranges
+            .into_iter()
+            .map(|range| {
+                let mut group = sorted
+                    .column(1)
+                    .as_any()
+                    .downcast_ref::<UInt64Array>()
+                    .unwrap()
+                    .slice(range.start, range.end - range.start)
+                    .values()
+                    .to_vec();
+                group.sort();
+                group
+            })
+            .collect();
+        // nulls sort first
+        assert_eq!(groups, vec![vec![5], vec![2, 4], vec![1, 3]]);
+
+        // nested paths survive serialization
+        let proto: api::ArroyoSchema = keyed.clone().into();
+        assert_eq!(ArroyoSchema::try_from(proto).unwrap(), keyed);
+
+        assert!(ArroyoSchema::from_schema_key_paths(schema.clone(), vec![vec![0, 2]]).is_err());
+        assert!(ArroyoSchema::from_schema_key_paths(schema, vec![vec![1, 0]]).is_err());
+    }
+
+    #[test]
+    fn test_sort_by_timestamp() {
+        let schema = ArroyoSchema::new_unkeyed(
+            Arc::new(Schema::new(vec![
+                Field::new("value", DataType::UInt64, false),
+                Field::new(
+                    "_timestamp",
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    false,
+                ),
+            ])),
+            1,
+        );
+
+        let sorted = schema
+            .sort(batch(&schema, vec![30, 10, 40, 20]), true)
+            .unwrap();
+        assert_eq!(values(&sorted), vec![10, 20, 30, 40]);
+
+        let sorted = schema
+            .sort_by_timestamp(batch(&schema, vec![1, 2, 2, 5]))
+            .unwrap();
+        assert_eq!(values(&sorted), vec![1, 2, 2, 5]);
+
+        let sorted = schema.sort(batch(&schema, vec![]), true).unwrap();
+        assert_eq!(sorted.num_rows(), 0);
+
+        // an empty key list sorts the same way as no keys
+        let keyed = ArroyoSchema::from_schema_keys(schema.schema.clone(), vec![]).unwrap();
+        let sorted = keyed.sort(batch(&schema, vec![3, 1, 2]), true).unwrap();
+        assert_eq!(values(&sorted), vec![1, 2, 3]);
+
+        let unsorted = schema.sort(batch(&schema, vec![3, 1, 2]), false).unwrap();
+        assert_eq!(values(&unsorted), vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn test_filter_by_time_range() {
+        let schema = ArroyoSchema::new_unkeyed(
+            Arc::new(Schema::new(vec![
+                Field::new("value", DataType::UInt64, false),
+                Field::new(
+                    "_timestamp",
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    false,
+                ),
+            ])),
+            1,
+        );
+
+        let b = batch(&schema, vec![10, 20, 30, 40]);
+        let t = |n| Some(from_nanos(n));
+
+        let filtered = schema.filter_by_time_range(b.clone(), None, None).unwrap();
+        assert_eq!(values(&filtered), vec![10, 20, 30, 40]);
+
+        let filtered = schema
+            .filter_by_time_range(b.clone(), t(20), t(40))
+            .unwrap();
+        assert_eq!(values(&filtered), vec![20, 30]);
+
+        let filtered = schema.filter_by_time_range(b.clone(), None, t(30)).unwrap();
+        assert_eq!(values(&filtered), vec![10, 20]);
+
+        let filtered = schema.filter_by_time(b.clone(), t(30)).unwrap();
+        assert_eq!(values(&filtered), vec![30, 40]);
+
+        let filtered = schema
+            .filter_by_time_range(batch(&schema, vec![]), t(0), t(100))
+            .unwrap();
+        assert_eq!(filtered.num_rows(), 0);
+    }
+
+    #[test]
+    fn test_structurally_equals() {
+        let schema = ArroyoSchema::new_keyed(validation_schema(), 2, vec![0]);
+
+        let mut fields: Vec<Field> = validation_schema()
+            .fields()
+            .iter()
+            .map(|f| f.as_ref().clone())
+            .collect();
+        fields[1] = fields[1].clone().with_metadata(
+            [("description".to_string(), "the value".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        let documented = ArroyoSchema::new_keyed(Arc::new(Schema::new(fields.clone())), 2, vec![0]);
+
+        assert_ne!(schema, documented);
+        assert!(schema.structurally_equals(&documented));
+
+        let unkeyed = ArroyoSchema::new_unkeyed(validation_schema(), 2);
+        assert!(!schema.structurally_equals(&unkeyed));
+
+        fields[1] = Field::new("value", DataType::Int64, false);
+        let retyped = ArroyoSchema::new_keyed(Arc::new(Schema::new(fields.clone())), 2, vec![0]);
+        assert!(!schema.structurally_equals(&retyped));
+
+        fields[1] = Field::new("value", DataType::UInt64, true);
+        let nullable = ArroyoSchema::new_keyed(Arc::new(Schema::new(fields)), 2, vec![0]);
+        assert!(!schema.structurally_equals(&nullable));
+    }
+}
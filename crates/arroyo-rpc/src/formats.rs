@@ -16,6 +16,7 @@ pub enum TimestampFormat {
     #[serde(rename = "rfc3339")]
     RFC3339,
     UnixMillis,
+    UnixMicros,
 }
 
 impl TryFrom<&str> for TimestampFormat {
@@ -25,6 +26,7 @@ impl TryFrom<&str> for TimestampFormat {
         match value {
             "RFC3339" => Ok(TimestampFormat::RFC3339),
             "UnixMillis" | "unix_millis" => Ok(TimestampFormat::UnixMillis),
+            "UnixMicros" | "unix_micros" => Ok(TimestampFormat::UnixMicros),
             _ => Err(()),
         }
     }
@@ -241,6 +243,23 @@ impl ProtobufFormat {
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ArrowIpcFormat {
+    /// Whether each message is written in the IPC file format (with a footer, as used by
+    /// Feather files) rather than the IPC stream format
+    #[serde(default)]
+    pub file: bool,
+}
+
+impl ArrowIpcFormat {
+    pub fn from_opts(opts: &mut ConnectorOptions) -> DFResult<Self> {
+        Ok(Self {
+            file: opts.pull_opt_bool("arrow_ipc.file")?.unwrap_or(false),
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Format {
@@ -250,6 +269,7 @@ pub enum Format {
     Parquet(ParquetFormat),
     RawString(RawStringFormat),
     RawBytes(RawBytesFormat),
+    ArrowIpc(ArrowIpcFormat),
 }
 
 impl Format {
@@ -266,10 +286,25 @@ impl Format {
             "raw_string" => Format::RawString(RawStringFormat {}),
             "raw_bytes" => Format::RawBytes(RawBytesFormat {}),
             "parquet" => Format::Parquet(ParquetFormat {}),
+            "arrow_ipc" => Format::ArrowIpc(ArrowIpcFormat::from_opts(opts)?),
             f => return plan_err!("unknown format '{}'", f),
         }))
     }
 
+    /// The name of this format, as used in the `format` option
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Json(JsonFormat { debezium: true, .. }) => "debezium_json",
+            Format::Json(_) => "json",
+            Format::Avro(_) => "avro",
+            Format::Protobuf(_) => "protobuf",
+            Format::Parquet(_) => "parquet",
+            Format::RawString(_) => "raw_string",
+            Format::RawBytes(_) => "raw_bytes",
+            Format::ArrowIpc(_) => "arrow_ipc",
+        }
+    }
+
     pub fn is_updating(&self) -> bool {
         match self {
             Format::Json(JsonFormat { debezium: true, .. }) => true,
@@ -278,7 +313,7 @@ impl Format {
             | Format::Parquet(_)
             | Format::RawString(_)
             | Format::Protobuf(_) => false,
-            Format::RawBytes(_) => false,
+            Format::RawBytes(_) | Format::ArrowIpc(_) => false,
         }
     }
 }
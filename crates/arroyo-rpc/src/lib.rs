@@ -23,6 +23,7 @@ use datafusion::sql::planner::{ContextProvider, PlannerContext, SqlToRel};
 use datafusion::sql::sqlparser::ast::{Expr, SqlOption, Value as SqlValue};
 use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
 use datafusion::sql::sqlparser::parser::Parser;
+use grpc::api::CheckpointAlignment;
 use grpc::rpc::{StopMode, TableCheckpointMetadata, TaskCheckpointEventType};
 use log::warn;
 use serde::{Deserialize, Serialize};
@@ -102,6 +103,40 @@ pub struct CompactionResult {
     pub compacted_tables: HashMap<String, TableCheckpointMetadata>,
 }
 
+impl grpc::rpc::UdfCrate {
+    /// Generates the Cargo.toml the UDF crate is built with. Environment variables referenced by
+    /// the dependencies are only substituted if `sub_env_vars` is set, so that a manifest shown to
+    /// users doesn't expose secrets (like registry tokens) from the compiler's environment.
+    pub fn cargo_toml(&self, sub_env_vars: bool) -> Result<String> {
+        let dependencies = if sub_env_vars {
+            var_str::VarStr::new(self.dependencies.clone()).sub_env_vars()?
+        } else {
+            self.dependencies.clone()
+        };
+
+        let dependencies: toml::Table = toml::from_str(&dependencies)
+            .map_err(|e| anyhow::anyhow!("Invalid dependency in RPC: {:?}", e))?;
+
+        let mut cargo_toml = toml::toml! {
+            [package]
+            name = "udf"
+            version = "1.0.0"
+            edition = "2021"
+
+            [lib]
+            crate-type = ["cdylib"]
+        };
+        cargo_toml.insert("dependencies".to_string(), toml::Value::Table(dependencies));
+
+        Ok(cargo_toml.to_string())
+    }
+
+    /// The source of the UDF crate's lib.rs, which is the UDF definition as written
+    pub fn lib_rs(&self) -> &str {
+        &self.definition
+    }
+}
+
 impl From<LoadCompactedDataReq> for CompactionResult {
     fn from(req: LoadCompactedDataReq) -> Self {
         Self {
@@ -185,6 +220,10 @@ impl Interceptor for FileAuthInterceptor {
     }
 }
 
+/// The SQL type name shown for a primitive type. SQL has a single INTERVAL type, so every
+/// duration and interval type maps to it and the name doesn't identify the unit; fields are
+/// always converted from the `PrimitiveType`, which does, rather than from this name. A column
+/// declared as INTERVAL in SQL is an `Interval(MonthDayNano)`.
 pub fn primitive_to_sql(primitive_type: PrimitiveType) -> &'static str {
     match primitive_type {
         PrimitiveType::Int32 => "INTEGER",
@@ -200,6 +239,13 @@ pub fn primitive_to_sql(primitive_type: PrimitiveType) -> &'static str {
         | PrimitiveType::UnixMicros
         | PrimitiveType::UnixNanos
         | PrimitiveType::DateTime => "TIMESTAMP",
+        PrimitiveType::DurationSeconds
+        | PrimitiveType::DurationMillis
+        | PrimitiveType::DurationMicros
+        | PrimitiveType::DurationNanos
+        | PrimitiveType::IntervalYearMonth
+        | PrimitiveType::IntervalDayTime
+        | PrimitiveType::IntervalMonthDayNano => "INTERVAL",
         PrimitiveType::Json => "JSON",
     }
 }
@@ -227,6 +273,10 @@ pub struct OperatorConfig {
     pub rate_limit: Option<RateLimit>,
     #[serde(default)]
     pub metadata_fields: Vec<MetadataField>,
+    /// How the pipeline's checkpoint barriers are aligned; set by the planner for sinks, which
+    /// can't provide exactly-once delivery unless checkpoints are aligned
+    #[serde(default)]
+    pub checkpoint_alignment: CheckpointAlignment,
 }
 
 impl Default for OperatorConfig {
@@ -239,6 +289,7 @@ impl Default for OperatorConfig {
             framing: None,
             rate_limit: None,
             metadata_fields: vec![],
+            checkpoint_alignment: CheckpointAlignment::Aligned,
         }
     }
 }
@@ -722,7 +773,47 @@ macro_rules! retry {
 
 #[cfg(test)]
 mod tests {
-    use crate::parse_expr;
+    use crate::api_types::connections::PrimitiveType;
+    use crate::grpc::rpc::UdfCrate;
+    use crate::{parse_expr, primitive_to_sql};
+
+    #[test]
+    fn test_udf_cargo_toml() {
+        let udf_crate = UdfCrate {
+            name: "my_udf".to_string(),
+            definition: "fn my_udf() {}".to_string(),
+            dependencies: r#"regex = "1.10.2"
+private = { version = "1", registry-token = "{{ UDF_TEST_TOKEN }}" }"#
+                .to_string(),
+            cargo_lock: None,
+        };
+
+        let cargo_toml: toml::Table = udf_crate.cargo_toml(false).unwrap().parse().unwrap();
+        assert_eq!(cargo_toml["package"]["name"].as_str(), Some("udf"));
+        assert_eq!(cargo_toml["lib"]["crate-type"][0].as_str(), Some("cdylib"));
+        assert_eq!(cargo_toml["dependencies"]["regex"].as_str(), Some("1.10.2"));
+        assert_eq!(
+            cargo_toml["dependencies"]["private"]["registry-token"].as_str(),
+            Some("{{ UDF_TEST_TOKEN }}")
+        );
+        assert_eq!(udf_crate.lib_rs(), "fn my_udf() {}");
+    }
+
+    #[test]
+    fn test_primitive_to_sql_intervals() {
+        // lossy: the unit of durations and intervals isn't reflected in the SQL name
+        for primitive_type in [
+            PrimitiveType::DurationSeconds,
+            PrimitiveType::DurationMillis,
+            PrimitiveType::DurationMicros,
+            PrimitiveType::DurationNanos,
+            PrimitiveType::IntervalYearMonth,
+            PrimitiveType::IntervalDayTime,
+            PrimitiveType::IntervalMonthDayNano,
+        ] {
+            assert_eq!(primitive_to_sql(primitive_type), "INTERVAL");
+        }
+    }
 
     #[test]
     fn test_parse_expr() {
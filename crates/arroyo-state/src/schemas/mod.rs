@@ -10,10 +10,9 @@ use arrow_array::{
 use arrow_ord::cmp::{gt_eq, lt_eq};
 use arrow_schema::{DataType, Field};
 use arroyo_rpc::df::ArroyoSchemaRef;
-use arroyo_rpc::get_hasher;
 use arroyo_types::from_nanos;
 use bincode::config;
-use datafusion::common::{hash_utils::create_hashes, ScalarValue};
+use datafusion::common::ScalarValue;
 use tracing::warn;
 
 use crate::{parquet::ParquetStats, DataOperation};
@@ -143,17 +142,7 @@ impl SchemaWithHashAndOperation {
         &mut self,
         record_batch: &RecordBatch,
     ) -> Result<(RecordBatch, ParquetStats)> {
-        let key_batch = self
-            .memory_schema
-            .routing_keys()
-            .as_ref()
-            .map(|key_indices| record_batch.project(key_indices))
-            .transpose()?
-            .unwrap_or_else(|| record_batch.project(&[]).unwrap());
-
-        let mut hash_buffer = vec![0u64; key_batch.num_rows()];
-        let _hashes = create_hashes(key_batch.columns(), &get_hasher(), &mut hash_buffer)?;
-        let hash_array = PrimitiveArray::<UInt64Type>::from(hash_buffer);
+        let hash_array = self.memory_schema.hash_keys(record_batch)?;
 
         let hash_min = min(&hash_array).unwrap();
         let hash_max = max(&hash_array).unwrap();
@@ -35,6 +35,7 @@ use super::{ErasedCheckpointer, ErasedTable};
 pub struct TableManager {
     epoch: u32,
     min_epoch: u32,
+    restored_epoch: Option<u32>,
     // ordered by table, then epoch.
     tables: HashMap<String, Arc<dyn ErasedTable>>,
     writer: BackendWriter,
@@ -332,6 +333,7 @@ impl TableManager {
             Self {
                 epoch,
                 min_epoch,
+                restored_epoch: restore_from.map(|metadata| metadata.epoch),
                 tables,
                 writer,
                 task_info,
@@ -342,6 +344,11 @@ impl TableManager {
         ))
     }
 
+    /// The epoch of the checkpoint the tables were restored from, if any
+    pub fn restored_epoch(&self) -> Option<u32> {
+        self.restored_epoch
+    }
+
     pub async fn checkpoint(&mut self, barrier: CheckpointBarrier, watermark: Option<SystemTime>) {
         self.writer
             .sender
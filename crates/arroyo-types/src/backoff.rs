@@ -0,0 +1,134 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// Computes delays for retrying a failing operation, growing exponentially from `initial` by
+/// `multiplier` on each attempt up to `max`. A random jitter is subtracted from each delay so
+/// that many tasks retrying at once don't do so in lockstep.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    multiplier: f64,
+    jitter: f64,
+    max_attempts: Option<u32>,
+    attempts: u32,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration, multiplier: f64) -> Self {
+        assert!(multiplier >= 1.0, "backoff multiplier must be at least 1");
+        Self {
+            initial,
+            max,
+            multiplier,
+            jitter: 0.1,
+            max_attempts: None,
+            attempts: 0,
+        }
+    }
+
+    /// Limits the number of delays that will be returned; once exhausted, `next_delay` returns
+    /// `None`
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Sets the fraction of each delay (between 0 and 1) that may be randomly removed from it
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&jitter),
+            "backoff jitter must be between 0 and 1"
+        );
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+
+    /// Returns how long to wait before the next attempt, or `None` if the maximum number of
+    /// attempts has been reached
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self
+            .max_attempts
+            .is_some_and(|max_attempts| self.attempts >= max_attempts)
+        {
+            return None;
+        }
+
+        let base = (self.initial.as_secs_f64() * self.multiplier.powi(self.attempts as i32))
+            .min(self.max.as_secs_f64());
+        self.attempts = self.attempts.saturating_add(1);
+
+        let jitter = if self.jitter > 0.0 {
+            rand::thread_rng().gen_range(0.0..=self.jitter)
+        } else {
+            0.0
+        };
+
+        Some(Duration::from_secs_f64(base * (1.0 - jitter)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_growth_capped_at_max() {
+        let mut backoff =
+            Backoff::new(Duration::from_millis(50), Duration::from_secs(1), 2.0).with_jitter(0.0);
+
+        let delays: Vec<_> = (0..7).map(|_| backoff.next_delay().unwrap()).collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(50),
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(400),
+                Duration::from_millis(800),
+                Duration::from_secs(1),
+                Duration::from_secs(1),
+            ]
+        );
+
+        // large attempt counts must not overflow
+        for _ in 0..2_000 {
+            assert_eq!(backoff.next_delay(), Some(Duration::from_secs(1)));
+        }
+    }
+
+    #[test]
+    fn test_max_attempts() {
+        let mut backoff = Backoff::new(Duration::from_millis(10), Duration::from_secs(1), 2.0)
+            .with_max_attempts(3);
+
+        for _ in 0..3 {
+            assert!(backoff.next_delay().is_some());
+        }
+        assert_eq!(backoff.next_delay(), None);
+        assert_eq!(backoff.attempts(), 3);
+
+        backoff.reset();
+        assert!(backoff.next_delay().is_some());
+    }
+
+    #[test]
+    fn test_jitter_bounds() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(100), 2.0)
+            .with_jitter(0.5);
+
+        for _ in 0..100 {
+            let delay = backoff.next_delay().unwrap();
+            assert!(delay >= Duration::from_millis(50), "{:?} too short", delay);
+            assert!(delay <= Duration::from_millis(100), "{:?} too long", delay);
+        }
+    }
+}
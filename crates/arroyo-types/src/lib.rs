@@ -10,6 +10,9 @@ use std::hash::Hash;
 use std::ops::{Range, RangeInclusive};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+mod backoff;
+pub use backoff::Backoff;
+
 #[derive(Copy, Hash, Debug, Clone, Eq, PartialEq, Encode, Decode, PartialOrd, Ord, Deserialize)]
 pub struct Window {
     pub start: SystemTime,
@@ -476,6 +479,9 @@ pub static BATCHES_SENT: &str = "arroyo_worker_batches_sent";
 pub static TX_QUEUE_SIZE: &str = "arroyo_worker_tx_queue_size";
 pub static TX_QUEUE_REM: &str = "arroyo_worker_tx_queue_rem";
 pub static DESERIALIZATION_ERRORS: &str = "arroyo_worker_deserialization_errors";
+pub static BACKPRESSURE_MICROS: &str = "arroyo_worker_backpressure_micros";
+pub static RX_QUEUE_DEPTH: &str = "arroyo_worker_rx_queue_depth";
+pub static WATERMARK_LAG_SECONDS: &str = "arroyo_worker_watermark_lag_seconds";
 
 #[derive(Debug, Copy, Clone, Encode, Decode, PartialEq, Eq)]
 pub struct CheckpointBarrier {
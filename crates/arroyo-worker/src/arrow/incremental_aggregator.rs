@@ -206,6 +206,7 @@ pub struct IncrementalAggregatingFunc {
     batch_state_schema: Arc<ArroyoSchema>,
     schema_without_metadata: Arc<Schema>,
     ttl: Duration,
+    max_keys: Option<u64>,
     key_converter: RowConverter,
     new_generation: u64,
 }
@@ -857,6 +858,16 @@ impl IncrementalAggregatingFunc {
             if self.accumulators.contains_key(key.as_ref()) {
                 self.accumulators.get_mut(key.as_ref()).unwrap()
             } else {
+                // exceeding the configured key limit fails the job rather than evicting state,
+                // as evicted keys would silently produce incorrect results
+                if let Some(max_keys) = self.max_keys {
+                    if self.accumulators.len() as u64 >= max_keys {
+                        bail!(
+                            "updating aggregate exceeded the maximum of {} keys (set via `updating_max_keys`)",
+                            max_keys
+                        );
+                    }
+                }
                 let new_accumulators = self.make_accumulators();
                 self.accumulators.insert(
                     Arc::new(key.as_ref().to_vec()),
@@ -917,6 +928,7 @@ impl ArrowOperator for IncrementalAggregatingFunc {
             fields: vec![
                 ("flush_interval", AsDisplayable::Debug(&self.flush_interval)),
                 ("ttl", AsDisplayable::Debug(&self.ttl)),
+                ("max_keys", AsDisplayable::Debug(&self.max_keys)),
                 (
                     "state_schema",
                     AsDisplayable::Schema(&self.sliding_state_schema.schema),
@@ -1174,6 +1186,7 @@ impl OperatorConstructor for IncrementalAggregatingConstructor {
                 flush_interval: Duration::from_micros(config.flush_interval_micros),
                 metadata_expr,
                 ttl,
+                max_keys: config.max_keys,
                 aggregates,
                 accumulators: UpdatingCache::with_time_to_idle(ttl),
                 schema_without_metadata: Arc::new(schema_without_metadata.finish()),
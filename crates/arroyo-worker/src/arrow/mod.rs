@@ -40,6 +40,7 @@ pub mod lookup_join;
 pub mod session_aggregating_window;
 pub mod sliding_aggregating_window;
 pub(crate) mod sync;
+pub mod top_n;
 pub mod tumbling_aggregating_window;
 mod updating_cache;
 pub mod watermark_generator;
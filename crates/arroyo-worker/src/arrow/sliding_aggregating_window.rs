@@ -1,12 +1,12 @@
 use anyhow::{anyhow, bail, Result};
-use arrow::compute::{partition, sort_to_indices, take};
+use arrow::compute::{concat_batches, partition, sort_to_indices, take};
 use arrow_array::{types::TimestampNanosecondType, Array, PrimitiveArray, RecordBatch};
 use arrow_schema::SchemaRef;
 use arroyo_operator::{
     context::OperatorContext,
     operator::{ArrowOperator, ConstructedOperator, OperatorConstructor},
 };
-use arroyo_rpc::grpc::{api, rpc::TableConfig};
+use arroyo_rpc::grpc::{api, api::SlidingWindowEmit, rpc::TableConfig};
 use arroyo_state::timestamp_table_config;
 use arroyo_types::{from_nanos, print_time, to_nanos, CheckpointBarrier, Watermark};
 use datafusion::common::ScalarValue;
@@ -59,6 +59,10 @@ pub struct SlidingAggregatingWindowFunc<K: Copy> {
     projection_input_schema: SchemaRef,
     final_projection: Arc<dyn ExecutionPlan>,
     state: SlidingWindowState,
+    emit: SlidingWindowEmit,
+    // the earliest bin this operator has received data for; with `SlidingWindowEmit::OnWindowClose`
+    // windows that start before it are not emitted, as they don't cover a full width of data
+    first_bin: Option<SystemTime>,
 }
 
 #[allow(clippy::enum_variant_names)]
@@ -158,6 +162,8 @@ impl SlidingAggregatingWindowFunc<SystemTime> {
                 self.tiered_record_batches.insert(batch, bin_start)?;
             }
         }
+        // the bin is now complete, so its partial batches will always be emitted together
+        self.tiered_record_batches.compact_before(bin_end)?;
         partial_table.flush_timestamp(bin_end).await?;
         partial_table.expire_timestamp(bin_end - self.width + self.slide);
         let interval_start = bin_end - self.width;
@@ -188,6 +194,12 @@ impl SlidingAggregatingWindowFunc<SystemTime> {
                 next_window_start: bin_end,
             }
         };
+        if self.emit == SlidingWindowEmit::OnWindowClose
+            && self.first_bin.is_some_and(|first| interval_start < first)
+        {
+            return Ok(());
+        }
+
         let mut aggregate_results = Vec::new();
         while let Some(batch) = final_exec.next().await {
             let batch = batch.expect("should be able to compute batch");
@@ -304,6 +316,28 @@ impl RecordBatchTier {
         Ok(self.panes[bin_index].batches.clone())
     }
 
+    /// Concatenates the batches of every pane that ends at or before `cutoff` into a single batch.
+    /// Those panes can no longer receive data, and are always read as a unit. This bounds the
+    /// number of batches retained, but not the number of rows: partial aggregates for the same
+    /// key in a pane are not merged.
+    fn compact_before(&mut self, cutoff: SystemTime) -> Result<()> {
+        let Some(start_time) = self.start_time else {
+            return Ok(());
+        };
+        let mut pane_end = start_time + self.width;
+        for pane in self.panes.iter_mut() {
+            if pane_end > cutoff {
+                break;
+            }
+            if pane.batches.len() > 1 {
+                let schema = pane.batches[0].schema();
+                pane.batches = vec![concat_batches(&schema, &pane.batches)?];
+            }
+            pane_end += self.width;
+        }
+        Ok(())
+    }
+
     fn delete_before(&mut self, cutoff: SystemTime) -> Result<()> {
         let bin_start = self.bin_start(cutoff);
         if self
@@ -423,6 +457,22 @@ impl TieredRecordBatchHolder {
         Ok(())
     }
 
+    fn compact_before(&mut self, cutoff: SystemTime) -> Result<()> {
+        for tier in self.tiers.iter_mut() {
+            tier.compact_before(cutoff)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn batch_count(&self) -> usize {
+        self.tiers
+            .iter()
+            .flat_map(|tier| tier.panes.iter())
+            .map(|pane| pane.batches.len())
+            .sum()
+    }
+
     fn is_empty(&self) -> bool {
         self.tiers[0]
             .panes
@@ -527,6 +577,8 @@ impl OperatorConstructor for SlidingAggregatingWindowConstructor {
                 projection_input_schema: final_projection.children()[0].schema().clone(),
                 final_projection,
                 state: SlidingWindowState::NoData,
+                emit: config.emit(),
+                first_bin: None,
             },
         )))
     }
@@ -545,6 +597,7 @@ impl ArrowOperator for SlidingAggregatingWindowFunc<SystemTime> {
             fields: vec![
                 ("slide", AsDisplayable::Debug(&self.slide)),
                 ("width", AsDisplayable::Debug(&self.width)),
+                ("emit", AsDisplayable::Debug(&self.emit)),
                 (
                     "partial_aggregation_plan",
                     self.partial_aggregation_plan.as_ref().into(),
@@ -586,6 +639,7 @@ impl ArrowOperator for SlidingAggregatingWindowFunc<SystemTime> {
         if self.tiered_record_batches.is_empty() {
             match table.get_min_time() {
                 Some(min_time) => {
+                    self.first_bin = Some(self.bin_start(min_time));
                     self.state = SlidingWindowState::OnlyBufferedData {
                         earliest_bin_time: self.bin_start(min_time),
                     }
@@ -593,6 +647,9 @@ impl ArrowOperator for SlidingAggregatingWindowFunc<SystemTime> {
                 None => self.state = SlidingWindowState::NoData,
             }
         } else {
+            // we were already emitting windows before the restore, and the earliest data may have
+            // since expired from state, so we can't tell where the first full window starts
+            self.first_bin = Some(SystemTime::UNIX_EPOCH);
             self.state = SlidingWindowState::InMemoryData {
                 next_window_start: watermark_bin,
             };
@@ -637,6 +694,8 @@ impl ArrowOperator for SlidingAggregatingWindowFunc<SystemTime> {
                 continue;
             }
 
+            self.first_bin = Some(self.first_bin.map_or(bin_start, |b| b.min(bin_start)));
+
             self.state = match self.state {
                 SlidingWindowState::NoData => SlidingWindowState::OnlyBufferedData {
                     earliest_bin_time: bin_start,
@@ -753,3 +812,59 @@ impl ArrowOperator for SlidingAggregatingWindowFunc<SystemTime> {
         .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TieredRecordBatchHolder;
+    use arrow_array::{Array, Int64Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_compaction_bounds_retained_batches() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "count",
+            DataType::Int64,
+            false,
+        )]));
+        let slide = Duration::from_secs(1);
+        let width = Duration::from_secs(10);
+        let mut holder = TieredRecordBatchHolder::new(vec![slide]).unwrap();
+
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        for bin in 0..100u32 {
+            let bin_start = start + slide * bin;
+            let bin_end = bin_start + slide;
+            for i in 0..5 {
+                let batch = RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int64Array::from(vec![i, i + 1]))],
+                )
+                .unwrap();
+                holder.insert(batch, bin_start).unwrap();
+            }
+            holder.compact_before(bin_end).unwrap();
+
+            let batches = holder
+                .batches_for_interval(bin_end - width, bin_end)
+                .unwrap();
+            let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+            assert_eq!(rows, 10 * ((bin + 1).min(10) as usize));
+
+            holder.delete_before(bin_end + slide - width).unwrap();
+            assert!(
+                holder.batch_count() <= (width.as_nanos() / slide.as_nanos()) as usize,
+                "retained {} batches after bin {}",
+                holder.batch_count(),
+                bin
+            );
+        }
+
+        let batches = holder
+            .batches_for_interval(start + slide * 95, start + slide * 100)
+            .unwrap();
+        assert_eq!(batches.len(), 5);
+        assert!(batches.iter().all(|b| b.column(0).len() == 10));
+    }
+}
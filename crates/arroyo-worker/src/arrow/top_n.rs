@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use arrow::compute::{
+    concat, concat_batches, filter_record_batch, lexsort_to_indices, max, min, not, take,
+    SortColumn,
+};
+use arrow_array::{Array, BooleanArray, RecordBatch};
+use arroyo_operator::context::{Collector, OperatorContext};
+use arroyo_operator::operator::{
+    ArrowOperator, ConstructedOperator, OperatorConstructor, Registry,
+};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::rpc::TableConfig;
+use arroyo_rpc::{df::ArroyoSchemaRef, grpc::api};
+use arroyo_state::timestamp_table_config;
+use arroyo_types::{from_nanos, CheckpointBarrier, Watermark};
+use datafusion::physical_expr::PhysicalSortExpr;
+use datafusion_proto::physical_plan::from_proto::parse_physical_sort_expr;
+use datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec;
+use datafusion_proto::protobuf::PhysicalSortExprNode;
+use prost::Message;
+use tracing::warn;
+
+/// Emits the first `limit` rows of each window, as ordered by `sort_exprs`, once the watermark
+/// passes the window. Only the current top rows of each open window are retained, so memory is
+/// bounded by `limit` rows per window.
+pub struct TopNOperator {
+    input_schema: ArroyoSchemaRef,
+    // this is for time bucketing
+    input_schema_unkeyed: ArroyoSchemaRef,
+    sort_exprs: Vec<PhysicalSortExpr>,
+    limit: usize,
+    windows: BTreeMap<SystemTime, WindowTopN>,
+}
+
+struct WindowTopN {
+    rows: RecordBatch,
+    // whether each row has already been written to state by a previous checkpoint
+    persisted: BooleanArray,
+}
+
+impl TopNOperator {
+    fn split_batches(
+        &self,
+        batch: RecordBatch,
+        watermark: Option<SystemTime>,
+    ) -> Result<Vec<(RecordBatch, SystemTime)>> {
+        if batch.num_rows() == 0 {
+            warn!("empty batch received");
+            return Ok(vec![]);
+        }
+        let timestamp_column = self.input_schema.timestamp_column(&batch);
+        let min_timestamp = from_nanos(min(timestamp_column).unwrap() as u128);
+        let max_timestamp = from_nanos(max(timestamp_column).unwrap() as u128);
+
+        // early exit if all rows should be filtered.
+        if let Some(watermark) = watermark {
+            if max_timestamp < watermark {
+                return Ok(vec![]);
+            }
+        }
+
+        if min_timestamp == max_timestamp {
+            return Ok(vec![(batch, max_timestamp)]);
+        }
+        let sorted_batch = self.input_schema_unkeyed.sort(batch, true)?;
+        let filtered_batch = self
+            .input_schema_unkeyed
+            .filter_by_time(sorted_batch, watermark)?;
+        let filtered_timestamps = self.input_schema.timestamp_column(&filtered_batch);
+        Ok(self
+            .input_schema_unkeyed
+            .partition(&filtered_batch, true)?
+            .into_iter()
+            .map(|range| {
+                (
+                    filtered_batch.slice(range.start, range.end - range.start),
+                    from_nanos(filtered_timestamps.value(range.start) as u128),
+                )
+            })
+            .collect())
+    }
+
+    /// Merges `batch` into the retained rows for the window at `timestamp`, keeping only the
+    /// first `limit` rows in sort order.
+    fn insert(&mut self, timestamp: SystemTime, batch: RecordBatch, persisted: bool) -> Result<()> {
+        let new_persisted = BooleanArray::from(vec![persisted; batch.num_rows()]);
+        let (rows, persisted) = match self.windows.remove(&timestamp) {
+            Some(existing) => (
+                concat_batches(&batch.schema(), [&existing.rows, &batch])?,
+                concat(&[&existing.persisted as &dyn Array, &new_persisted])?,
+            ),
+            None => (batch, Arc::new(new_persisted) as Arc<dyn Array>),
+        };
+
+        let sort_columns = self
+            .sort_exprs
+            .iter()
+            .map(|e| {
+                Ok(SortColumn {
+                    values: e.expr.evaluate(&rows)?.into_array(rows.num_rows())?,
+                    options: Some(e.options),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let indices = lexsort_to_indices(&sort_columns, Some(self.limit))?;
+
+        let columns = rows
+            .columns()
+            .iter()
+            .map(|c| take(c, &indices, None))
+            .collect::<Result<Vec<_>, _>>()?;
+        let persisted = take(&persisted, &indices, None)?;
+
+        self.windows.insert(
+            timestamp,
+            WindowTopN {
+                rows: RecordBatch::try_new(rows.schema(), columns)?,
+                persisted: persisted
+                    .as_any()
+                    .downcast_ref::<BooleanArray>()
+                    .expect("persisted should be a boolean array")
+                    .clone(),
+            },
+        );
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for TopNOperator {
+    fn name(&self) -> String {
+        "TopN".to_string()
+    }
+
+    async fn on_start(&mut self, ctx: &mut OperatorContext) {
+        let watermark = ctx.last_present_watermark();
+        let table = ctx
+            .table_manager
+            .get_expiring_time_key_table("top_n", watermark)
+            .await
+            .unwrap();
+        let restored: Vec<_> = table
+            .all_batches_for_watermark(watermark)
+            .flat_map(|(timestamp, batches)| batches.iter().map(|b| (*timestamp, b.clone())))
+            .collect();
+        for (timestamp, batch) in restored {
+            self.insert(timestamp, batch, true)
+                .expect("should be able to restore top-n state");
+        }
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut OperatorContext,
+        _: &mut dyn Collector,
+    ) {
+        let current_watermark = ctx.last_present_watermark();
+        for (batch, timestamp) in self.split_batches(batch, current_watermark).unwrap() {
+            self.insert(timestamp, batch, false)
+                .expect("should be able to compute top-n");
+        }
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut OperatorContext,
+        collector: &mut dyn Collector,
+    ) -> Option<Watermark> {
+        let Some(watermark) = ctx.last_present_watermark() else {
+            return Some(watermark);
+        };
+        while self
+            .windows
+            .first_key_value()
+            .is_some_and(|(timestamp, _)| *timestamp < watermark)
+        {
+            let (_timestamp, window) = self.windows.pop_first().unwrap();
+            collector.collect(window.rows).await;
+        }
+        Some(Watermark::EventTime(watermark))
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        ctx: &mut OperatorContext,
+        _: &mut dyn Collector,
+    ) {
+        let watermark = ctx.last_present_watermark();
+        let table = ctx
+            .table_manager
+            .get_expiring_time_key_table("top_n", watermark)
+            .await
+            .expect("should have top_n table");
+
+        // rows that were persisted by an earlier checkpoint are already in the table; rows that
+        // have since been evicted will be evicted again when state is restored
+        for (timestamp, window) in self.windows.iter_mut() {
+            let unpersisted = not(&window.persisted).unwrap();
+            let batch = filter_record_batch(&window.rows, &unpersisted).unwrap();
+            if batch.num_rows() > 0 {
+                table.insert(*timestamp, batch);
+            }
+            window.persisted = BooleanArray::from(vec![true; window.rows.num_rows()]);
+        }
+
+        table.flush(watermark).await.expect("should flush");
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        let mut tables = HashMap::new();
+        tables.insert(
+            "top_n".to_string(),
+            timestamp_table_config(
+                "top_n",
+                "top-n rows per window",
+                Duration::ZERO,
+                false,
+                self.input_schema.as_ref().clone(),
+            ),
+        );
+        tables
+    }
+}
+
+pub struct TopNConstructor;
+impl OperatorConstructor for TopNConstructor {
+    type ConfigT = api::TopNOperator;
+    fn with_config(
+        &self,
+        config: Self::ConfigT,
+        registry: Arc<Registry>,
+    ) -> anyhow::Result<ConstructedOperator> {
+        let input_schema = Arc::new(ArroyoSchema::try_from(
+            config
+                .input_schema
+                .ok_or_else(|| anyhow!("missing input schema"))?,
+        )?);
+
+        let sort_exprs = config
+            .sort_exprs
+            .iter()
+            .map(|expr| {
+                let expr = PhysicalSortExprNode::decode(&mut expr.as_slice())?;
+                Ok(parse_physical_sort_expr(
+                    &expr,
+                    registry.as_ref(),
+                    &input_schema.schema,
+                    &DefaultPhysicalExtensionCodec {},
+                )?)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let input_schema_unkeyed = Arc::new(ArroyoSchema::from_schema_unkeyed(
+            input_schema.schema.clone(),
+        )?);
+
+        Ok(ConstructedOperator::from_operator(Box::new(TopNOperator {
+            input_schema,
+            input_schema_unkeyed,
+            sort_exprs,
+            limit: config.limit as usize,
+            windows: BTreeMap::new(),
+        })))
+    }
+}
@@ -249,6 +249,14 @@ impl<T: Send + Sync> UpdatingCache<T> {
         Some(Ok(()))
     }
 
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     pub fn contains_key(&self, k: &[u8]) -> bool {
         self.data.contains_key(k)
     }
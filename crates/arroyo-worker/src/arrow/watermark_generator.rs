@@ -1,5 +1,6 @@
 use arrow::compute::kernels;
 use arrow_array::RecordBatch;
+use arroyo_metrics::record_watermark_lag;
 use arroyo_operator::context::{Collector, OperatorContext};
 use arroyo_operator::get_timestamp_col;
 use arroyo_operator::operator::{
@@ -10,7 +11,7 @@ use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::grpc::api::ExpressionWatermarkConfig;
 use arroyo_rpc::grpc::rpc::TableConfig;
 use arroyo_state::global_table_config;
-use arroyo_types::{from_nanos, to_millis, CheckpointBarrier, SignalMessage, Watermark};
+use arroyo_types::{from_nanos, to_millis, CheckpointBarrier, SignalMessage, TaskInfo, Watermark};
 use async_trait::async_trait;
 use bincode::{Decode, Encode};
 use datafusion::physical_expr::PhysicalExpr;
@@ -20,10 +21,60 @@ use datafusion_proto::protobuf::PhysicalExprNode;
 use prost::Message;
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use tracing::{debug, info};
 
+/// How long a single batch may be held back waiting for slower sources to catch up. Bounding
+/// this keeps a throttled source from blocking checkpoints indefinitely.
+const THROTTLE_MAX_PAUSE: Duration = Duration::from_secs(1);
+const THROTTLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Current watermarks of the throttled watermark generators running in this process, by job and
+/// then by (node_id, task_index). Idle tasks are recorded as `None` and don't hold others back.
+///
+/// Throttling is best-effort: only generators in the same worker process can see each other, so
+/// it has no effect between sources scheduled on different workers.
+static THROTTLED_WATERMARKS: LazyLock<
+    Mutex<HashMap<String, HashMap<(u32, u32), Option<SystemTime>>>>,
+> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Forgets the watermarks reported by a previous run of the job, which may have crashed or run
+/// with a different parallelism before reporting that its tasks had finished
+pub fn reset_throttled_watermarks(job_id: &str) {
+    THROTTLED_WATERMARKS.lock().unwrap().remove(job_id);
+}
+
+fn report_throttled_watermark(task_info: &TaskInfo, watermark: Option<SystemTime>) {
+    THROTTLED_WATERMARKS
+        .lock()
+        .unwrap()
+        .entry(task_info.job_id.clone())
+        .or_default()
+        .insert((task_info.node_id, task_info.task_index), watermark);
+}
+
+fn remove_throttled_watermark(task_info: &TaskInfo) {
+    let mut watermarks = THROTTLED_WATERMARKS.lock().unwrap();
+    if let Some(tasks) = watermarks.get_mut(&task_info.job_id) {
+        tasks.remove(&(task_info.node_id, task_info.task_index));
+        if tasks.is_empty() {
+            watermarks.remove(&task_info.job_id);
+        }
+    }
+}
+
+fn min_throttled_watermark(job_id: &str) -> Option<SystemTime> {
+    THROTTLED_WATERMARKS
+        .lock()
+        .unwrap()
+        .get(job_id)?
+        .values()
+        .flatten()
+        .min()
+        .copied()
+}
+
 #[derive(Encode, Decode, Copy, Clone, Debug, PartialEq)]
 pub struct WatermarkGeneratorState {
     last_watermark_emitted_at: SystemTime,
@@ -37,6 +88,8 @@ pub struct WatermarkGenerator {
     last_event: SystemTime,
     idle: bool,
     expression: Arc<dyn PhysicalExpr>,
+    throttle_max_skew: Option<Duration>,
+    current_watermark: Option<SystemTime>,
 }
 
 impl WatermarkGenerator {
@@ -44,6 +97,7 @@ impl WatermarkGenerator {
         interval: Duration,
         idle_time: Option<Duration>,
         expression: Arc<dyn PhysicalExpr>,
+        throttle_max_skew: Option<Duration>,
     ) -> WatermarkGenerator {
         WatermarkGenerator {
             interval,
@@ -55,6 +109,30 @@ impl WatermarkGenerator {
             last_event: SystemTime::now(),
             idle: false,
             expression,
+            throttle_max_skew,
+            current_watermark: None,
+        }
+    }
+
+    /// Slows down a source whose watermark has run more than `throttle_max_skew` ahead of the
+    /// slowest throttled source in this process by holding back its batch, for at most
+    /// `THROTTLE_MAX_PAUSE`. This doesn't pause the source outright, so a source that is far
+    /// ahead still makes progress.
+    async fn throttle(&self, task_info: &TaskInfo) {
+        let Some(max_skew) = self.throttle_max_skew else {
+            return;
+        };
+
+        report_throttled_watermark(task_info, Some(self.state_cache.max_watermark));
+
+        let start = Instant::now();
+        while let Some(min) = min_throttled_watermark(&task_info.job_id) {
+            if self.state_cache.max_watermark <= min + max_skew
+                || start.elapsed() >= THROTTLE_MAX_PAUSE
+            {
+                break;
+            }
+            tokio::time::sleep(THROTTLE_POLL_INTERVAL).await;
         }
     }
 }
@@ -82,6 +160,7 @@ impl OperatorConstructor for WatermarkGeneratorConstructor {
                 Duration::from_micros(config.period_micros),
                 config.idle_time_micros.map(Duration::from_micros),
                 expression,
+                config.throttle_max_skew_micros.map(Duration::from_micros),
             ),
         )))
     }
@@ -104,6 +183,10 @@ impl ArrowOperator for WatermarkGenerator {
                 ("interval", AsDisplayable::Debug(&self.interval)),
                 ("idle_time", AsDisplayable::Debug(&self.idle_time)),
                 ("expression", AsDisplayable::Debug(&self.expression)),
+                (
+                    "throttle_max_skew",
+                    AsDisplayable::Debug(&self.throttle_max_skew),
+                ),
             ],
         }
     }
@@ -133,9 +216,13 @@ impl ArrowOperator for WatermarkGenerator {
     async fn on_close(
         &mut self,
         final_message: &Option<SignalMessage>,
-        _: &mut OperatorContext,
+        ctx: &mut OperatorContext,
         collector: &mut dyn Collector,
     ) {
+        if self.throttle_max_skew.is_some() {
+            remove_throttled_watermark(&ctx.task_info);
+        }
+
         if let Some(SignalMessage::EndOfData) = final_message {
             // send final watermark on close
             collector
@@ -194,8 +281,15 @@ impl ArrowOperator for WatermarkGenerator {
                 .broadcast_watermark(Watermark::EventTime(watermark))
                 .await;
             self.state_cache.last_watermark_emitted_at = max_timestamp;
+            self.current_watermark = Some(watermark);
             self.idle = false;
         }
+
+        if let Some(watermark) = self.current_watermark {
+            record_watermark_lag(ctx.task_info.node_id, ctx.task_info.task_index, watermark);
+        }
+
+        self.throttle(&ctx.task_info).await;
     }
 
     async fn handle_checkpoint(
@@ -219,6 +313,11 @@ impl ArrowOperator for WatermarkGenerator {
         ctx: &mut OperatorContext,
         collector: &mut dyn Collector,
     ) {
+        // keep the lag current even when no new data is arriving
+        if let (false, Some(watermark)) = (self.idle, self.current_watermark) {
+            record_watermark_lag(ctx.task_info.node_id, ctx.task_info.task_index, watermark);
+        }
+
         if let Some(idle_time) = self.idle_time {
             if self.last_event.elapsed().unwrap_or(Duration::ZERO) > idle_time && !self.idle {
                 info!(
@@ -227,6 +326,9 @@ impl ArrowOperator for WatermarkGenerator {
                 );
                 collector.broadcast_watermark(Watermark::Idle).await;
                 self.idle = true;
+                if self.throttle_max_skew.is_some() {
+                    report_throttled_watermark(&ctx.task_info, None);
+                }
             }
         }
     }
@@ -5,8 +5,11 @@ use crate::arrow::join_with_expiration::JoinWithExpirationConstructor;
 use crate::arrow::lookup_join::LookupJoinConstructor;
 use crate::arrow::session_aggregating_window::SessionAggregatingWindowConstructor;
 use crate::arrow::sliding_aggregating_window::SlidingAggregatingWindowConstructor;
+use crate::arrow::top_n::TopNConstructor;
 use crate::arrow::tumbling_aggregating_window::TumblingAggregateWindowConstructor;
-use crate::arrow::watermark_generator::WatermarkGeneratorConstructor;
+use crate::arrow::watermark_generator::{
+    reset_throttled_watermarks, WatermarkGeneratorConstructor,
+};
 use crate::arrow::window_fn::WindowFunctionConstructor;
 use crate::arrow::{KeyExecutionConstructor, ValueExecutionConstructor};
 use crate::network_manager::{NetworkManager, Quad, Senders};
@@ -22,7 +25,7 @@ use arroyo_planner::physical::new_registry;
 use arroyo_rpc::config::config;
 use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::grpc::{
-    api,
+    api::{self, CheckpointAlignment},
     rpc::{CheckpointMetadata, TaskAssignment},
 };
 use arroyo_rpc::{ControlMessage, ControlResp};
@@ -207,10 +210,12 @@ impl Program {
             registry,
             restore_epoch,
             control_tx,
+            CheckpointAlignment::default(),
         )
         .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn from_logical(
         name: String,
         job_id: &str,
@@ -219,6 +224,7 @@ impl Program {
         registry: Registry,
         restore_epoch: Option<u32>,
         control_tx: Sender<ControlResp>,
+        checkpoint_alignment: CheckpointAlignment,
     ) -> Program {
         let mut physical = DiGraph::new();
 
@@ -291,6 +297,7 @@ impl Program {
                         checkpoint_metadata.as_ref(),
                         control_tx.clone(),
                         registry.clone(),
+                        checkpoint_alignment,
                     )
                     .await,
                 }));
@@ -521,6 +528,8 @@ impl Engine {
     pub async fn start(mut self) -> RunningEngine {
         info!("Starting job {}", self.job_id);
 
+        reset_throttled_watermarks(&self.job_id);
+
         let node_indexes: Vec<_> = self.program.graph.read().unwrap().node_indices().collect();
 
         let worker_id = self.worker_id;
@@ -779,6 +788,7 @@ pub async fn construct_node(
     restore_from: Option<&CheckpointMetadata>,
     control_tx: Sender<ControlResp>,
     registry: Arc<Registry>,
+    checkpoint_alignment: CheckpointAlignment,
 ) -> OperatorNode {
     if chain.is_source() {
         let (head, _) = chain.iter().next().unwrap();
@@ -855,7 +865,9 @@ pub async fn construct_node(
             }
         }
 
-        OperatorNode::Chained(head.unwrap())
+        let mut head = head.unwrap();
+        head.context.checkpoint_alignment = checkpoint_alignment;
+        OperatorNode::Chained(head)
     }
 }
 
@@ -877,6 +889,7 @@ pub fn construct_operator(
         OperatorName::InstantJoin => Box::new(InstantJoinConstructor),
         OperatorName::LookupJoin => Box::new(LookupJoinConstructor),
         OperatorName::WindowFunction => Box::new(WindowFunctionConstructor),
+        OperatorName::TopN => Box::new(TopNConstructor),
         OperatorName::ConnectorSource | OperatorName::ConnectorSink => {
             let op: api::ConnectorOp = prost::Message::decode(config).unwrap();
             return connectors()
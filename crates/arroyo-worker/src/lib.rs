@@ -462,6 +462,7 @@ impl WorkerGrpc for WorkerServer {
                 registry,
                 req.restore_epoch,
                 control_tx.clone(),
+                logical.program_config.checkpoint_alignment,
             )
             .await;
 
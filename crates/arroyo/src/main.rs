@@ -6,6 +6,7 @@ use arroyo_rpc::config;
 use arroyo_rpc::config::{config, DatabaseType};
 use arroyo_server_common::shutdown::{Shutdown, SignalBehavior};
 use arroyo_server_common::{log_event, start_admin_server};
+use arroyo_types::Backoff;
 use arroyo_worker::{utils, WorkerServer};
 use clap::{Args, Parser, Subcommand};
 use clio::Input;
@@ -322,6 +323,7 @@ async fn connect(
     Connection<impl AsyncRead + AsyncWrite + Unpin, impl AsyncRead + AsyncWrite + Unpin>,
 )> {
     let config = &config().database.postgres;
+    let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(5), 2.0);
 
     loop {
         match tokio_postgres::config::Config::new()
@@ -339,7 +341,7 @@ async fn connect(
             Err(e) => {
                 if !e.to_string().contains("authentication") && retry {
                     debug!("Received error from database while waiting: {}", e);
-                    tokio::time::sleep(Duration::from_millis(500)).await;
+                    tokio::time::sleep(backoff.next_delay().unwrap()).await;
                     continue;
                 }
 
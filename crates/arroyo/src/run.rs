@@ -7,7 +7,7 @@ use arroyo_rpc::{config, init_db_notifier, notify_db, retry};
 use arroyo_server_common::log_event;
 use arroyo_server_common::shutdown::{Shutdown, ShutdownHandler, SignalBehavior};
 use arroyo_storage::StorageProvider;
-use arroyo_types::to_millis;
+use arroyo_types::{to_millis, Backoff};
 use async_trait::async_trait;
 use rand::random;
 use rusqlite::{Connection, DatabaseName, OpenFlags};
@@ -64,11 +64,18 @@ async fn wait_for_state(
 }
 
 async fn wait_for_connect(client: &Client) -> anyhow::Result<()> {
-    for _ in 0..50 {
+    let mut backoff =
+        Backoff::new(Duration::from_millis(10), Duration::from_secs(1), 2.0).with_max_attempts(10);
+
+    loop {
         if client.ping().send().await.is_ok() {
             return Ok(());
         }
-        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let Some(delay) = backoff.next_delay() else {
+            break;
+        };
+        tokio::time::sleep(delay).await;
     }
 
     bail!("API server did not start up successfully; see logs for more details");
@@ -422,6 +429,8 @@ pub async fn run(args: RunArgs) {
         &format!("http://localhost:{http_port}/api",),
         reqwest::ClientBuilder::new()
             .timeout(Duration::from_secs(60))
+            // the API is on localhost, so it must not be routed through a configured HTTP proxy
+            .no_proxy()
             .build()
             .unwrap(),
     ));
@@ -4,11 +4,11 @@ use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 
 use arroyo_openapi::types::{
-    builder, ConnectionProfilePost, ConnectionSchema, ConnectionTablePost, Format, JsonFormat,
-    MetricName, PipelinePatch, PipelinePost, SchemaDefinition, StopType, Udf, ValidateQueryPost,
-    ValidateUdfPost,
+    builder, ConnectionProfilePost, ConnectionSchema, ConnectionTablePost, ErrorCode, Format,
+    JsonFormat, MetricName, PipelinePatch, PipelinePost, SchemaDefinition, StopType, Udf, UdfPost,
+    ValidateQueryPost, ValidateUdfPost,
 };
-use arroyo_openapi::Client;
+use arroyo_openapi::{Client, Error};
 use rand::random;
 use rdkafka::admin::{AdminClient, AdminOptions, NewTopic};
 use rdkafka::{ClientConfig, ClientContext};
@@ -395,6 +395,24 @@ fn my_double(x: i64) -> i64 {
         .into_inner();
 
     assert_eq!(valid.errors, Vec::<String>::new());
+    assert_eq!(valid.cargo_toml, None);
+    assert_eq!(valid.lib_rs, None);
+
+    // the generated crate is returned when requested
+    let valid = get_client()
+        .validate_udf()
+        .include_generated(true)
+        .body(ValidateUdfPost::builder().definition(udf))
+        .send()
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(valid.errors, Vec::<String>::new());
+    let cargo_toml = valid.cargo_toml.expect("Cargo.toml should be included");
+    assert!(cargo_toml.contains("regex"), "{}", cargo_toml);
+    assert!(cargo_toml.contains("arroyo-udf-plugin"), "{}", cargo_toml);
+    assert_eq!(valid.lib_rs.as_deref(), Some(udf));
 
     let query = r#"
 create table impulse with (
@@ -429,6 +447,176 @@ select my_double(cast(counter as bigint)) from impulse;
         .unwrap();
 }
 
+#[tokio::test]
+async fn udf_name_conflict() {
+    let name = format!("conflicting_udf_{}", random::<u32>());
+    let udf = format!(
+        r#"
+use arroyo_udf_plugin::udf;
+
+#[udf]
+fn {}(x: i64) -> i64 {{
+    x + 1
+}}"#,
+        name
+    );
+
+    let created = get_client()
+        .create_udf()
+        .body(UdfPost::builder().prefix("").definition(&udf))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(created.status(), 201);
+    let created = created.into_inner();
+
+    let result = get_client()
+        .create_udf()
+        .body(UdfPost::builder().prefix("").definition(&udf))
+        .send()
+        .await;
+
+    let Err(Error::ErrorResponse(err)) = result else {
+        panic!("expected an error response, got {:?}", result);
+    };
+    assert_eq!(err.status(), 400);
+    assert_eq!(err.into_inner().code, Some(ErrorCode::UdfNameConflict));
+
+    get_client()
+        .delete_udf()
+        .id(&created.id)
+        .send()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn upsert_udf() {
+    let name = format!("upserted_udf_{}", random::<u32>());
+    let udf = |n: i64| {
+        format!(
+            r#"
+use arroyo_udf_plugin::udf;
+
+#[udf]
+fn {}(x: i64) -> i64 {{
+    x + {}
+}}"#,
+            name, n
+        )
+    };
+
+    let created = get_client()
+        .create_udf()
+        .body(
+            UdfPost::builder()
+                .prefix("")
+                .definition(udf(1))
+                .upsert(true),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(created.status(), 201);
+    let created = created.into_inner();
+
+    let updated = get_client()
+        .create_udf()
+        .body(
+            UdfPost::builder()
+                .prefix("")
+                .definition(udf(2))
+                .upsert(true),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(updated.status(), 200);
+    let updated = updated.into_inner();
+
+    assert_eq!(updated.id, created.id);
+    assert_eq!(updated.definition, udf(2));
+
+    get_client()
+        .delete_udf()
+        .id(&created.id)
+        .send()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn paginate_udfs() {
+    let suffix = random::<u32>();
+    let mut ids = vec![];
+    for i in 0..3 {
+        let udf = format!(
+            r#"
+use arroyo_udf_plugin::udf;
+
+#[udf]
+fn paginated_udf_{}_{}(x: i64) -> i64 {{
+    x + {}
+}}"#,
+            suffix, i, i
+        );
+
+        let created = get_client()
+            .create_udf()
+            .body(UdfPost::builder().prefix("").definition(&udf))
+            .send()
+            .await
+            .unwrap()
+            .into_inner();
+        ids.push(created.id);
+    }
+
+    // UDFs are returned newest first
+    let first = get_client()
+        .get_udfs()
+        .limit(1)
+        .send()
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(first.data.len(), 1);
+    assert!(first.has_more);
+
+    let middle = get_client()
+        .get_udfs()
+        .starting_after(&ids[2])
+        .limit(1)
+        .send()
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(
+        middle.data.iter().map(|u| &u.id).collect::<Vec<_>>(),
+        vec![&ids[1]]
+    );
+    assert!(middle.has_more);
+
+    let last = get_client()
+        .get_udfs()
+        .starting_after(&ids[1])
+        .limit(1000)
+        .send()
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(last.data.iter().any(|u| u.id == ids[0]));
+    assert!(!last.has_more);
+
+    // without a limit, every UDF is returned
+    let all = get_client().get_udfs().send().await.unwrap().into_inner();
+    assert!(ids.iter().all(|id| all.data.iter().any(|u| &u.id == id)));
+    assert!(!all.has_more);
+
+    for id in ids {
+        get_client().delete_udf().id(&id).send().await.unwrap();
+    }
+}
+
 fn create_kafka_admin() -> AdminClient<impl ClientContext> {
     ClientConfig::new()
         .set("bootstrap.servers", "localhost:9092")
@@ -131,6 +131,13 @@ export type webhooks = Record<string, never>;
 
 export interface components {
   schemas: {
+    ArrowIpcFormat: {
+      /**
+       * @description Whether each message is written in the IPC file format (with a footer, as used by
+       * Feather files) rather than the IPC stream format
+       */
+      file?: boolean;
+    };
     AvroFormat: {
       confluentSchemaRegistry?: boolean;
       intoUnstructuredJson?: boolean;
@@ -238,7 +245,10 @@ export interface components {
     ConnectorCollection: {
       data: (components["schemas"]["Connector"])[];
     };
+    /** @enum {string} */
+    ErrorCode: "not_found" | "udf_name_conflict" | "udf_compile_error" | "udf_build_timeout";
     ErrorResp: {
+      code?: components["schemas"]["ErrorCode"] | null;
       error: string;
     };
     FieldType: OneOf<[{
@@ -260,6 +270,8 @@ export interface components {
       raw_string: components["schemas"]["RawStringFormat"];
     }, {
       raw_bytes: components["schemas"]["RawBytesFormat"];
+    }, {
+      arrow_ipc: components["schemas"]["ArrowIpcFormat"];
     }]>;
     Framing: {
       method: components["schemas"]["FramingMethod"];
@@ -268,6 +280,7 @@ export interface components {
       newline: components["schemas"]["NewlineDelimitedFraming"];
     };
     GlobalUdf: {
+      cargoLock?: string | null;
       /** Format: int64 */
       createdAt: number;
       definition: string;
@@ -282,6 +295,7 @@ export interface components {
     };
     GlobalUdfCollection: {
       data: (components["schemas"]["GlobalUdf"])[];
+      hasMore: boolean;
     };
     Job: {
       /** Format: int64 */
@@ -339,7 +353,7 @@ export interface components {
       subtasks: (components["schemas"]["SubtaskMetrics"])[];
     };
     /** @enum {string} */
-    MetricName: "bytes_recv" | "bytes_sent" | "messages_recv" | "messages_sent" | "backpressure" | "tx_queue_size" | "tx_queue_rem";
+    MetricName: "bytes_recv" | "bytes_sent" | "messages_recv" | "messages_sent" | "backpressure" | "tx_queue_size" | "tx_queue_rem" | "backpressure_micros";
     NewlineDelimitedFraming: {
       /** Format: int64 */
       maxLineLength?: number | null;
@@ -411,6 +425,9 @@ export interface components {
     };
     PipelineNode: {
       description: string;
+      metadata: {
+        [key: string]: string | undefined;
+      };
       /** Format: int32 */
       nodeId: number;
       operator: string;
@@ -442,7 +459,7 @@ export interface components {
       udfs?: (components["schemas"]["Udf"])[] | null;
     };
     /** @enum {string} */
-    PrimitiveType: "Int32" | "Int64" | "UInt32" | "UInt64" | "F32" | "F64" | "Bool" | "String" | "Bytes" | "UnixMillis" | "UnixMicros" | "UnixNanos" | "DateTime" | "Json";
+    PrimitiveType: "Int32" | "Int64" | "UInt32" | "UInt64" | "F32" | "F64" | "Bool" | "String" | "Bytes" | "UnixMillis" | "UnixMicros" | "UnixNanos" | "DateTime" | "DurationSeconds" | "DurationMillis" | "DurationMicros" | "DurationNanos" | "IntervalYearMonth" | "IntervalDayTime" | "IntervalMonthDayNano" | "Json";
     ProtobufFormat: {
       /** Format: binary */
       compiledSchema?: string | null;
@@ -504,7 +521,7 @@ export interface components {
       message: string;
     };
     /** @enum {string} */
-    TimestampFormat: "rfc3339" | "unix_millis";
+    TimestampFormat: "rfc3339" | "unix_millis" | "unix_micros";
     Udf: {
       definition: string;
       language?: components["schemas"]["UdfLanguage"];
@@ -512,13 +529,22 @@ export interface components {
     /** @enum {string} */
     UdfLanguage: "python" | "rust";
     UdfPost: {
+      cargoLock?: string | null;
       definition: string;
       description?: string | null;
       language?: components["schemas"]["UdfLanguage"];
       prefix: string;
+      /** @description If true, an existing UDF with the same name is updated rather than causing a conflict */
+      upsert?: boolean;
     };
     UdfValidationResult: {
+      /** @description The Cargo.lock the UDF's dependencies were resolved to */
+      cargoLock?: string | null;
+      /** @description The Cargo.toml of the crate generated to build a Rust UDF, if requested */
+      cargoToml?: string | null;
       errors: (string)[];
+      /** @description The lib.rs of the crate generated to build a Rust UDF, if requested */
+      libRs?: string | null;
       udfName?: string | null;
     };
     ValidateQueryPost: {
@@ -526,6 +552,11 @@ export interface components {
       udfs?: (components["schemas"]["Udf"])[] | null;
     };
     ValidateUdfPost: {
+      /**
+       * @description A Cargo.lock to build the UDF against; if set the build fails rather than resolving
+       * different dependency versions
+       */
+      cargoLock?: string | null;
       definition: string;
       language?: components["schemas"]["UdfLanguage"];
     };
@@ -985,6 +1016,12 @@ export interface operations {
   };
   /** Get Global UDFs */
   get_udfs: {
+    parameters: {
+      query?: {
+        starting_after?: string | null;
+        limit?: number | null;
+      };
+    };
     responses: {
       /** @description List of UDFs */
       200: {
@@ -1002,16 +1039,34 @@ export interface operations {
       };
     };
     responses: {
-      /** @description Created UDF */
+      /** @description Updated UDF, when `upsert` was set */
       200: {
         content: {
           "application/json": components["schemas"]["Udf"];
         };
       };
+      /** @description Created UDF */
+      201: {
+        content: {
+          "application/json": components["schemas"]["Udf"];
+        };
+      };
+      /** @description Invalid UDF or name conflict */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ErrorResp"];
+        };
+      };
     };
   };
   /** Validate UDFs */
   validate_udf: {
+    parameters: {
+      query?: {
+        /** @description Whether to include the crate generated to build a Rust UDF in the result */
+        include_generated?: boolean | null;
+      };
+    };
     requestBody: {
       content: {
         "application/json": components["schemas"]["ValidateUdfPost"];
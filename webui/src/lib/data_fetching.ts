@@ -666,8 +666,24 @@ export const useConnectionTableTest = async (
 };
 
 const udfsFetcher = async () => {
-  const { data, error } = await get('/v1/udfs', {});
-  return processResponse(data, error);
+  let udfs: GlobalUdf[] = [];
+  let startingAfter: string | undefined = undefined;
+  while (true) {
+    const { data, error } = await get('/v1/udfs', {
+      params: {
+        query: {
+          starting_after: startingAfter,
+          limit: 100,
+        },
+      },
+    });
+    const page: schemas['GlobalUdfCollection'] = await processResponse(data, error);
+    udfs = udfs.concat(page.data);
+    if (!page.hasMore || page.data.length == 0) {
+      return { data: udfs, hasMore: false };
+    }
+    startingAfter = page.data[page.data.length - 1].id;
+  }
 };
 
 export const useGlobalUdfs = () => {